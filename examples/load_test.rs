@@ -0,0 +1,239 @@
+//! Fires concurrent GraphQL queries at a running RocketQL server and reports
+//! throughput and latency.
+//!
+//! Start the MongoDB from `.docker/docker-compose.yml`, run the server against
+//! it, then:
+//!
+//! ```sh
+//! cargo run --release --example load_test
+//! ```
+//!
+//! Settings come from the environment:
+//!
+//! - `LOAD_TEST_URL`: the GraphQL endpoint, `http://127.0.0.1:8000/graphql`
+//!   by default.
+//! - `LOAD_TEST_CONCURRENCY`: clients sending requests at once, 64 by
+//!   default.
+//! - `LOAD_TEST_REQUESTS`: requests sent by each client, 100 by default.
+//! - `LOAD_TEST_SEED`: employees to create before measuring, none by default.
+//! - `LOAD_TEST_QUERY`: the query to send, all employees with their stores
+//!   and rank by default.
+//! - `LOAD_TEST_TOKEN`: a bearer token sent with every request. Seeding
+//!   needs one allowed to create ranks and employees.
+//!
+//! The client speaks plain HTTP/1.1 over one connection per request, so it
+//! measures the server the same way whichever commit it was built from.
+
+use rocket::{
+    serde::json::{json, Value},
+    tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        task::JoinHandle,
+    },
+};
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_QUERY: &str =
+    "{ getAllEmployees { id firstName lastName stores { name location { state } } rank { name } } }";
+
+struct Target {
+    address: String,
+    host: String,
+    path: String,
+    token: Option<String>,
+}
+
+impl Target {
+    fn from_env() -> Result<Self, String> {
+        let url: String = env::var("LOAD_TEST_URL")
+            .unwrap_or_else(|_| String::from("http://127.0.0.1:8000/graphql"));
+        let rest: &str = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("LOAD_TEST_URL '{}' must start with 'http://'", url))?;
+        let (host, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/graphql"),
+        };
+        let address: String = if host.contains(':') {
+            String::from(host)
+        } else {
+            format!("{}:80", host)
+        };
+
+        Ok(Target {
+            address,
+            host: String::from(host),
+            path: String::from(path),
+            token: env::var("LOAD_TEST_TOKEN").ok(),
+        })
+    }
+
+    /// Posts a GraphQL request and returns the response data, or why there
+    /// is none.
+    async fn post(&self, query: &str, variables: Value) -> Result<Value, String> {
+        let body: String = json!({"query": query, "variables": variables}).to_string();
+        let mut request: String = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.host,
+            body.len()
+        );
+        if let Some(token) = &self.token {
+            request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+
+        let mut stream: TcpStream = TcpStream::connect(&self.address)
+            .await
+            .map_err(|error| error.to_string())?;
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|error| error.to_string())?;
+        let mut response: Vec<u8> = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .await
+            .map_err(|error| error.to_string())?;
+
+        let response: String = String::from_utf8_lossy(&response).into_owned();
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| String::from("malformed HTTP response"))?;
+        let status: &str = head.split(' ').nth(1).unwrap_or_default();
+        if status != "200" {
+            return Err(format!("HTTP {}", status));
+        }
+
+        let body: Value = rocket::serde::json::from_str(body).map_err(|error| error.to_string())?;
+        match body.get("errors") {
+            Some(errors) => Err(errors.to_string()),
+            None => Ok(body.get("data").cloned().unwrap_or(Value::Null)),
+        }
+    }
+}
+
+fn setting(name: &str, default: usize) -> usize {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} '{}' is not a number", name, value)),
+        Err(_) => default,
+    }
+}
+
+/// Creates a rank and `count` employees holding it.
+async fn seed(target: &Target, count: usize) -> Result<(), String> {
+    let rank: Value = target
+        .post(
+            "mutation($input: CreateRank!) { createRank(input: $input) { id } }",
+            json!({"input": {"name": format!("Load test {}", std::process::id())}}),
+        )
+        .await?;
+    let rank_id: &str = rank["createRank"]["id"]
+        .as_str()
+        .ok_or_else(|| String::from("createRank returned no id"))?;
+
+    for index in 0..count {
+        target
+            .post(
+                "mutation($input: CreateEmployee!) { createEmployee(input: $input) { id } }",
+                json!({"input": {
+                    "firstName": format!("Load{}", index),
+                    "lastName": "Test",
+                    "rankId": rank_id,
+                }}),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index: usize = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+#[rocket::main]
+async fn main() {
+    let target: Target = Target::from_env().unwrap_or_else(|error| panic!("{}", error));
+    let concurrency: usize = setting("LOAD_TEST_CONCURRENCY", 64);
+    let requests: usize = setting("LOAD_TEST_REQUESTS", 100);
+    let seed_count: usize = setting("LOAD_TEST_SEED", 0);
+    let query: String = env::var("LOAD_TEST_QUERY").unwrap_or_else(|_| String::from(DEFAULT_QUERY));
+
+    if seed_count > 0 {
+        if let Err(error) = seed(&target, seed_count).await {
+            eprintln!("Seeding failed: {}", error);
+            std::process::exit(1);
+        }
+        println!("Seeded {} employees", seed_count);
+    }
+
+    let target: &'static Target = Box::leak(Box::new(target));
+    let query: &'static str = Box::leak(query.into_boxed_str());
+    let started: Instant = Instant::now();
+    let workers: Vec<JoinHandle<(Vec<Duration>, usize)>> = (0..concurrency)
+        .map(|_| {
+            tokio::spawn(async move {
+                let mut latencies: Vec<Duration> = Vec::with_capacity(requests);
+                let mut failures: usize = 0;
+                for _ in 0..requests {
+                    let sent: Instant = Instant::now();
+                    match target.post(query, Value::Null).await {
+                        Ok(_) => latencies.push(sent.elapsed()),
+                        Err(error) => {
+                            if failures == 0 {
+                                eprintln!("Request failed: {}", error);
+                            }
+                            failures += 1;
+                        }
+                    }
+                }
+                (latencies, failures)
+            })
+        })
+        .collect();
+
+    let mut latencies: Vec<Duration> = Vec::new();
+    let mut failures: usize = 0;
+    for worker in workers {
+        let (worker_latencies, worker_failures) = worker.await.expect("worker panicked");
+        latencies.extend(worker_latencies);
+        failures += worker_failures;
+    }
+    let elapsed: Duration = started.elapsed();
+    latencies.sort();
+
+    println!(
+        "{} clients x {} requests against {}{}",
+        concurrency, requests, target.host, target.path
+    );
+    println!(
+        "succeeded: {}, failed: {}, elapsed: {:.2?}",
+        latencies.len(),
+        failures,
+        elapsed
+    );
+    println!(
+        "throughput: {:.1} requests/s",
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "latency p50: {:.2?}, p95: {:.2?}, p99: {:.2?}, max: {:.2?}",
+        percentile(&latencies, 0.50),
+        percentile(&latencies, 0.95),
+        percentile(&latencies, 0.99),
+        latencies.last().copied().unwrap_or_default()
+    );
+}
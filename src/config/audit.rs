@@ -0,0 +1,101 @@
+use crate::config::error::RepositoryError;
+use crate::schema::project_schema::AuditEntry;
+use mongodb::bson::{doc, to_document, Bson, DateTime, Document};
+use serde::Serialize;
+
+/// The fields that differ between two versions of a document, each as
+/// `{"before": ..., "after": ...}`. A missing version has no fields, so a
+/// creation lists every field with `before: null` and a deletion every field
+/// with `after: null`.
+pub fn diff(before: Option<&Document>, after: Option<&Document>) -> Document {
+    let empty: Document = Document::new();
+    let (before, after) = (before.unwrap_or(&empty), after.unwrap_or(&empty));
+
+    let mut changes: Document = Document::new();
+    for field in after.keys().chain(
+        before
+            .keys()
+            .filter(|field| !after.contains_key(field.as_str())),
+    ) {
+        let (old, new) = (
+            before.get(field).unwrap_or(&Bson::Null),
+            after.get(field).unwrap_or(&Bson::Null),
+        );
+        if field != "_id" && old != new {
+            changes.insert(field, doc! {"before": old.clone(), "after": new.clone()});
+        }
+    }
+
+    changes
+}
+
+impl AuditEntry {
+    /// The entry for a write that turned `before` into `after`. Creations
+    /// have no `before`, deletions no `after`.
+    pub fn new<T: Serialize>(
+        operation: &str,
+        entity_type: &str,
+        before: Option<&T>,
+        after: Option<&T>,
+        actor: Option<String>,
+        client_ip: Option<String>,
+    ) -> Result<Self, RepositoryError> {
+        let to_doc = |entity: &T| {
+            to_document(entity).map_err(|error| RepositoryError::Backend(error.to_string()))
+        };
+        let before: Option<Document> = before.map(to_doc).transpose()?;
+        let after: Option<Document> = after.map(to_doc).transpose()?;
+        let entity_id: String = after
+            .as_ref()
+            .or(before.as_ref())
+            .and_then(|entity| entity.get_object_id("_id").ok())
+            .map(|id| id.to_hex())
+            .unwrap_or_default();
+
+        Ok(AuditEntry {
+            id: None,
+            actor,
+            operation: String::from(operation),
+            entity_type: String::from(entity_type),
+            entity_id,
+            at: DateTime::now(),
+            diff: diff(before.as_ref(), after.as_ref()),
+            client_ip,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+    use mongodb::bson::{doc, Bson};
+
+    #[test]
+    fn lists_only_changed_fields() {
+        let before =
+            doc! {"_id": 1, "name": "Staff", "description": "Floor staff", "permissions": []};
+        let after = doc! {"_id": 1, "name": "Staff", "description": Bson::Null, "permissions": ["ManageShifts"]};
+
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            doc! {
+                "description": {"before": "Floor staff", "after": Bson::Null},
+                "permissions": {"before": [], "after": ["ManageShifts"]},
+            }
+        );
+    }
+
+    #[test]
+    fn creation_lists_every_field() {
+        let after = doc! {"_id": 1, "name": "Staff"};
+
+        assert_eq!(
+            diff(None, Some(&after)),
+            doc! {"name": {"before": Bson::Null, "after": "Staff"}}
+        );
+        assert_eq!(
+            diff(Some(&after), None),
+            doc! {"name": {"before": "Staff", "after": Bson::Null}}
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::{env, fs};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: u64,
+}
+
+/// The authenticated caller of a request, as vouched for by a bearer token.
+/// The subject is the ID of the caller's employee entry, whose rank decides
+/// what the caller may do; administrators are allowed everything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub subject: String,
+    pub is_admin: bool,
+}
+
+#[derive(Clone)]
+pub struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+    admin_subjects: Vec<String>,
+}
+
+impl JwtVerifier {
+    pub fn hs256(secret: &[u8]) -> Self {
+        JwtVerifier {
+            key: DecodingKey::from_secret(secret),
+            validation: Validation::new(Algorithm::HS256),
+            admin_subjects: Vec::new(),
+        }
+    }
+
+    pub fn rs256(public_key_pem: &[u8]) -> Result<Self, String> {
+        let key: DecodingKey = DecodingKey::from_rsa_pem(public_key_pem)
+            .map_err(|error| format!("invalid RS256 public key: {}", error))?;
+
+        Ok(JwtVerifier {
+            key,
+            validation: Validation::new(Algorithm::RS256),
+            admin_subjects: Vec::new(),
+        })
+    }
+
+    /// Uses `JWT_SECRET` for HS256, or otherwise the PEM encoded RS256 public
+    /// key in `JWT_PUBLIC_KEY` (or the file named by `JWT_PUBLIC_KEY_FILE`).
+    /// `JWT_ISSUER` and `JWT_AUDIENCE` are checked when set, and the
+    /// comma-separated subjects in `ADMIN_SUBJECTS` become administrators.
+    pub fn from_env() -> Result<Self, String> {
+        let mut verifier: JwtVerifier = if let Ok(secret) = env::var("JWT_SECRET") {
+            JwtVerifier::hs256(secret.as_bytes())
+        } else if let Ok(public_key) = env::var("JWT_PUBLIC_KEY") {
+            JwtVerifier::rs256(public_key.as_bytes())?
+        } else if let Ok(path) = env::var("JWT_PUBLIC_KEY_FILE") {
+            let public_key: Vec<u8> =
+                fs::read(&path).map_err(|error| format!("cannot read '{}': {}", path, error))?;
+            JwtVerifier::rs256(&public_key)?
+        } else {
+            return Err(String::from(
+                "one of JWT_SECRET, JWT_PUBLIC_KEY or JWT_PUBLIC_KEY_FILE must be set",
+            ));
+        };
+
+        if let Ok(issuer) = env::var("JWT_ISSUER") {
+            verifier.validation.set_issuer(&[issuer]);
+        }
+        if let Ok(audience) = env::var("JWT_AUDIENCE") {
+            verifier.validation.set_audience(&[audience]);
+        }
+        if let Ok(admin_subjects) = env::var("ADMIN_SUBJECTS") {
+            verifier = verifier.with_admins(
+                admin_subjects
+                    .split(',')
+                    .map(|subject| String::from(subject.trim()))
+                    .collect(),
+            );
+        }
+
+        Ok(verifier)
+    }
+
+    pub fn with_admins(mut self, admin_subjects: Vec<String>) -> Self {
+        self.admin_subjects = admin_subjects;
+        self
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Principal, String> {
+        let claims: Claims = decode::<Claims>(token, &self.key, &self.validation)
+            .map_err(|error| format!("invalid bearer token: {}", error))?
+            .claims;
+        let is_admin: bool = self.admin_subjects.contains(&claims.sub);
+
+        Ok(Principal {
+            subject: claims.sub,
+            is_admin,
+        })
+    }
+}
+
+/// Mints an HS256 token for `subject` that expires `ttl_seconds` from now.
+#[cfg(test)]
+pub fn mint_token(secret: &[u8], subject: &str, ttl_seconds: i64) -> String {
+    use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+
+    let claims: Claims = Claims {
+        sub: String::from(subject),
+        exp: (get_current_timestamp() as i64 + ttl_seconds) as u64,
+    };
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(secret),
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test-secret";
+
+    #[test]
+    fn accepts_valid_token() {
+        let verifier: JwtVerifier = JwtVerifier::hs256(SECRET);
+        let principal: Principal = verifier.verify(&mint_token(SECRET, "alice", 300)).unwrap();
+
+        assert_eq!(principal.subject, "alice");
+        assert!(!principal.is_admin);
+    }
+
+    #[test]
+    fn marks_configured_admins() {
+        let verifier: JwtVerifier =
+            JwtVerifier::hs256(SECRET).with_admins(vec![String::from("root")]);
+
+        assert!(
+            verifier
+                .verify(&mint_token(SECRET, "root", 300))
+                .unwrap()
+                .is_admin
+        );
+        assert!(
+            !verifier
+                .verify(&mint_token(SECRET, "alice", 300))
+                .unwrap()
+                .is_admin
+        );
+    }
+
+    #[test]
+    fn rejects_token_signed_with_other_secret() {
+        let verifier: JwtVerifier = JwtVerifier::hs256(SECRET);
+
+        assert!(verifier
+            .verify(&mint_token(b"other-secret", "alice", 300))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let verifier: JwtVerifier = JwtVerifier::hs256(SECRET);
+
+        assert!(verifier
+            .verify(&mint_token(SECRET, "alice", -3600))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let verifier: JwtVerifier = JwtVerifier::hs256(SECRET);
+
+        assert!(verifier.verify("not.a.token").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    config::{
+        event_bus::{Event, EventBus},
+        mongo::MongoDB,
+        repository::SoftDeletable,
+    },
+    schema::project_schema::{
+        ChangeKind, Employee, EmployeeStatusChange, LocationChange, RankChange, StoreChange,
+    },
+};
+use futures_util::TryStreamExt;
+use mongodb::{
+    change_stream::{
+        event::{ChangeStreamEvent, OperationType, ResumeToken},
+        ChangeStream,
+    },
+    error::{Error as MongoError, ErrorKind},
+};
+use rocket::tokio::{self, time::sleep};
+use serde::de::DeserializeOwned;
+use std::{env, time::Duration};
+
+/// How long a watcher waits before reopening a stream that failed.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Server error codes for a resume token the oplog no longer covers.
+const INVALID_RESUME_TOKEN: i32 = 260;
+const CHANGE_STREAM_HISTORY_LOST: i32 = 286;
+
+const DEFAULT_INSTANCE: &str = "default";
+
+/// Whether `MONGO_CHANGE_STREAMS` asks for the bridge. Change streams need
+/// a replica set, so the bridge is off unless explicitly enabled.
+pub fn enabled_from_env() -> Result<bool, String> {
+    match env::var("MONGO_CHANGE_STREAMS") {
+        Ok(value) => match value.to_ascii_lowercase().as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" | "" => Ok(false),
+            _ => Err(format!(
+                "MONGO_CHANGE_STREAMS must be 'true' or 'false', got '{}'",
+                value
+            )),
+        },
+        Err(_) => Ok(false),
+    }
+}
+
+/// The name this instance stores its resume tokens under, from
+/// `MONGO_CHANGE_STREAM_INSTANCE` or else the host name. Replicas must not
+/// share a name, or they would resume from each other's positions.
+pub fn instance_from_env() -> String {
+    env::var("MONGO_CHANGE_STREAM_INSTANCE")
+        .or_else(|_| env::var("HOSTNAME"))
+        .ok()
+        .map(|instance| String::from(instance.trim()))
+        .filter(|instance| !instance.is_empty())
+        .unwrap_or_else(|| String::from(DEFAULT_INSTANCE))
+}
+
+/// Feeds the event bus from MongoDB change streams, so that subscribers see
+/// the writes of every instance and of scripts working on the database
+/// directly. Each collection is watched by its own task, and the resume
+/// token of every handled event is stored under the instance's name, so a
+/// restarted instance picks up where it stopped.
+pub struct ChangeStreamBridge {
+    db: MongoDB,
+    bus: EventBus,
+    instance: String,
+}
+
+impl ChangeStreamBridge {
+    pub fn new(db: MongoDB, bus: EventBus, instance: String) -> Self {
+        ChangeStreamBridge { db, bus, instance }
+    }
+
+    pub fn spawn(self) {
+        self.spawn_watcher("employee", employee_event);
+        self.spawn_watcher("store", |event| {
+            entity_change(event)
+                .map(|(kind, store)| Event::StoreChanged(StoreChange { kind, store }))
+        });
+        self.spawn_watcher("location", |event| {
+            entity_change(event)
+                .map(|(kind, location)| Event::LocationChanged(LocationChange { kind, location }))
+        });
+        self.spawn_watcher("rank", |event| {
+            entity_change(event).map(|(kind, rank)| Event::RankChanged(RankChange { kind, rank }))
+        });
+    }
+
+    fn spawn_watcher<T>(
+        &self,
+        collection_name: &'static str,
+        convert: fn(ChangeStreamEvent<T>) -> Option<Event>,
+    ) where
+        T: DeserializeOwned + Unpin + Send + Sync + 'static,
+    {
+        let db: MongoDB = self.db.clone();
+        let bus: EventBus = self.bus.clone();
+        let instance: String = self.instance.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(error) =
+                    watch_collection(&db, &bus, &instance, collection_name, convert).await
+                {
+                    if resume_point_lost(&error) {
+                        log::warn!("Change stream on '{}' cannot resume, events since the last run are lost", collection_name);
+                        if let Err(error) = db.clear_resume_token(&instance, collection_name).await
+                        {
+                            log::error!(
+                                "Cannot clear the resume token of '{}': {}",
+                                collection_name,
+                                error
+                            );
+                        }
+                    } else {
+                        log::error!("Change stream on '{}' failed: {}", collection_name, error);
+                    }
+                }
+                sleep(RETRY_DELAY).await;
+            }
+        });
+    }
+}
+
+async fn watch_collection<T>(
+    db: &MongoDB,
+    bus: &EventBus,
+    instance: &str,
+    collection_name: &str,
+    convert: fn(ChangeStreamEvent<T>) -> Option<Event>,
+) -> mongodb::error::Result<()>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    let resume_after: Option<ResumeToken> = db.load_resume_token(instance, collection_name).await?;
+    let resuming: bool = resume_after.is_some();
+    let mut stream: ChangeStream<ChangeStreamEvent<T>> =
+        match db.watch::<T>(collection_name, resume_after).await {
+            // The server refusing the stored token will refuse it on every
+            // retry too, so the watcher starts over from the current time.
+            Err(error) if resuming && resume_rejected(&error) => {
+                log::warn!(
+                    "Change stream on '{}' cannot resume ({}), events since the last run are lost",
+                    collection_name,
+                    error
+                );
+                db.clear_resume_token(instance, collection_name).await?;
+                db.watch::<T>(collection_name, None).await?
+            }
+            stream => stream?,
+        };
+
+    while let Some(change) = stream.try_next().await? {
+        let token: ResumeToken = change.id.clone();
+
+        if let Some(event) = convert(change) {
+            bus.publish(event);
+        }
+        db.save_resume_token(instance, collection_name, &token)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Whether the server rejected opening a stream, as opposed to not being
+/// reachable at all.
+fn resume_rejected(error: &MongoError) -> bool {
+    matches!(*error.kind, ErrorKind::Command(_))
+}
+
+fn resume_point_lost(error: &MongoError) -> bool {
+    matches!(*error.kind, ErrorKind::Command(ref command)
+        if command.code == INVALID_RESUME_TOKEN || command.code == CHANGE_STREAM_HISTORY_LOST)
+}
+
+/// Whether an update marked an entry deleted or restored it.
+fn soft_deletion<T>(change: &ChangeStreamEvent<T>) -> Option<ChangeKind> {
+    let description = change.update_description.as_ref()?;
+    if description.updated_fields.contains_key("deleted_at") {
+        Some(ChangeKind::Deleted)
+    } else if description
+        .removed_fields
+        .iter()
+        .any(|field| field == "deleted_at")
+    {
+        Some(ChangeKind::Restored)
+    } else {
+        None
+    }
+}
+
+/// Entries are deleted by marking them, which is reported as the deletion.
+/// Removing a marked entry for good is not reported again. Such removals can
+/// only be told apart when the collection records pre-images; without one
+/// there is no document left to hand to subscribers either.
+fn entity_change<T: SoftDeletable>(change: ChangeStreamEvent<T>) -> Option<(ChangeKind, T)> {
+    match change.operation_type {
+        OperationType::Insert => change
+            .full_document
+            .map(|document| (ChangeKind::Created, document)),
+        OperationType::Update | OperationType::Replace => {
+            let kind: ChangeKind = soft_deletion(&change).unwrap_or(ChangeKind::Updated);
+            change.full_document.map(|document| (kind, document))
+        }
+        OperationType::Delete => change
+            .full_document_before_change
+            .filter(|document| !document.is_deleted())
+            .map(|document| (ChangeKind::Deleted, document)),
+        _ => None,
+    }
+}
+
+/// Marking an employee deleted is published like a delete, restoring it as a
+/// restore. Otherwise only status changes are published for updated
+/// employees. With a pre-image
+/// the previous status is known and no-op writes are skipped; without one,
+/// any write touching `status` counts as a change.
+fn employee_event(change: ChangeStreamEvent<Employee>) -> Option<Event> {
+    match change.operation_type {
+        OperationType::Insert => change.full_document.map(Event::EmployeeCreated),
+        OperationType::Delete => change
+            .full_document_before_change
+            .filter(|employee| !employee.is_deleted())
+            .map(Event::EmployeeDeleted),
+        OperationType::Update | OperationType::Replace => {
+            match soft_deletion(&change) {
+                Some(ChangeKind::Deleted) => {
+                    return change.full_document.map(Event::EmployeeDeleted)
+                }
+                Some(ChangeKind::Restored) => {
+                    return change.full_document.map(Event::EmployeeRestored)
+                }
+                _ => {}
+            }
+            let employee: Employee = change.full_document?;
+            let status_touched: bool =
+                change
+                    .update_description
+                    .as_ref()
+                    .is_none_or(|description| {
+                        description.updated_fields.contains_key("status")
+                            || description
+                                .removed_fields
+                                .iter()
+                                .any(|field| field == "status")
+                    });
+
+            let previous_status = match change.full_document_before_change {
+                Some(previous) if previous.status == employee.status => return None,
+                Some(previous) => previous.status,
+                None if status_touched => None,
+                None => return None,
+            };
+
+            Some(Event::EmployeeStatusChanged(EmployeeStatusChange {
+                employee,
+                previous_status,
+            }))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::validation::FieldError;
+use async_graphql::{to_value, Error as GraphQLError, ErrorExtensions};
+use mongodb::error::{Error as MongoError, ErrorKind, WriteFailure};
+use sqlx::Error as SqlError;
+use std::fmt;
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
+const UNIQUE_VIOLATION_CODE: &str = "23505";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepositoryError {
+    InvalidId(String),
+    NotFound {
+        entity: &'static str,
+        id: String,
+    },
+    Validation(Vec<FieldError>),
+    Duplicate(String),
+    HasDependents {
+        entity: &'static str,
+        id: String,
+        dependent_entity: &'static str,
+        dependents: Vec<String>,
+    },
+    Conflict {
+        entity: &'static str,
+        id: String,
+        expected: i32,
+        actual: i32,
+    },
+    Backend(String),
+}
+
+impl RepositoryError {
+    pub fn not_found(entity: &'static str, id: &str) -> Self {
+        RepositoryError::NotFound {
+            entity,
+            id: String::from(id),
+        }
+    }
+
+    pub fn invalid_field(field: &str, message: impl Into<String>) -> Self {
+        RepositoryError::Validation(vec![FieldError::new(field, message)])
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            RepositoryError::InvalidId(_) => "INVALID_ID",
+            RepositoryError::NotFound { .. } => "NOT_FOUND",
+            RepositoryError::Validation(_) => "VALIDATION_FAILED",
+            RepositoryError::Duplicate(_) => "DUPLICATE",
+            RepositoryError::HasDependents { .. } => "HAS_DEPENDENTS",
+            RepositoryError::Conflict { .. } => "CONFLICT",
+            RepositoryError::Backend(_) => "BACKEND_FAILURE",
+        }
+    }
+}
+
+/// Turns a lookup that failed because the referenced document does not exist
+/// (or could never exist, given a malformed ID) into `Ok(None)`.
+pub fn optional<T>(result: Result<T, RepositoryError>) -> Result<Option<T>, RepositoryError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(RepositoryError::NotFound { .. }) | Err(RepositoryError::InvalidId(_)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepositoryError::InvalidId(id) => write!(f, "'{}' is not a valid ID", id),
+            RepositoryError::NotFound { entity, id } => {
+                write!(f, "No {} with ID '{}' exists", entity, id)
+            }
+            RepositoryError::Validation(errors) => {
+                let details: Vec<String> = errors
+                    .iter()
+                    .map(|error| format!("{} {}", error.field, error.message))
+                    .collect();
+                write!(f, "Validation failed: {}", details.join("; "))
+            }
+            RepositoryError::Duplicate(message) => write!(f, "Duplicate entry: {}", message),
+            RepositoryError::HasDependents {
+                entity,
+                id,
+                dependent_entity,
+                dependents,
+            } => write!(
+                f,
+                "Cannot delete {} '{}' while it is referenced by {} {}",
+                entity,
+                id,
+                dependent_entity,
+                dependents.join(", ")
+            ),
+            RepositoryError::Conflict {
+                entity,
+                id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The {} '{}' has changed since version {} and is at version {} now",
+                entity, id, expected, actual
+            ),
+            RepositoryError::Backend(_) => write!(f, "Internal database error"),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<MongoError> for RepositoryError {
+    fn from(error: MongoError) -> Self {
+        match *error.kind {
+            ErrorKind::Write(WriteFailure::WriteError(ref write_error))
+                if write_error.code == DUPLICATE_KEY_CODE =>
+            {
+                RepositoryError::Duplicate(write_error.message.clone())
+            }
+            ErrorKind::Command(ref command_error) if command_error.code == DUPLICATE_KEY_CODE => {
+                RepositoryError::Duplicate(command_error.message.clone())
+            }
+            _ => RepositoryError::Backend(error.to_string()),
+        }
+    }
+}
+
+impl From<SqlError> for RepositoryError {
+    fn from(error: SqlError) -> Self {
+        match error {
+            SqlError::Database(ref database_error)
+                if database_error.code().as_deref() == Some(UNIQUE_VIOLATION_CODE) =>
+            {
+                RepositoryError::Duplicate(String::from(database_error.message()))
+            }
+            _ => RepositoryError::Backend(error.to_string()),
+        }
+    }
+}
+
+/// Backend failures reach the client only as "Internal database error", so
+/// the driver's own message is logged on the way out.
+impl ErrorExtensions for RepositoryError {
+    fn extend(&self) -> GraphQLError {
+        if let RepositoryError::Backend(source) = self {
+            log::error!("Database error: {}", source);
+        }
+
+        GraphQLError::new(self.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", self.code());
+            match self {
+                RepositoryError::Validation(errors) => {
+                    extensions.set("fieldErrors", to_value(errors).unwrap_or_default())
+                }
+                RepositoryError::HasDependents { dependents, .. } => {
+                    extensions.set("dependents", dependents.clone())
+                }
+                RepositoryError::Conflict { actual, .. } => extensions.set("version", *actual),
+                _ => {}
+            }
+        })
+    }
+}
@@ -0,0 +1,92 @@
+use crate::schema::project_schema::{
+    Employee, EmployeeStatusChange, LocationChange, RankChange, StoreChange,
+};
+use async_graphql::futures_util::stream::{self, Stream};
+use rocket::tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+
+/// How many events a slow subscriber may fall behind before it starts
+/// skipping the oldest ones.
+const DEFAULT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    EmployeeCreated(Employee),
+    EmployeeDeleted(Employee),
+    EmployeeRestored(Employee),
+    EmployeeStatusChanged(EmployeeStatusChange),
+    StoreChanged(StoreChange),
+    LocationChanged(LocationChange),
+    RankChanged(RankChange),
+}
+
+/// In-process fan-out of the changes made through the mutations, or of every
+/// write to the database once the change-stream bridge feeds it. Every
+/// subscription gets its own receiver; publishing never blocks the writer.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: Sender<Event>,
+    fed_by_change_streams: bool,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        EventBus {
+            sender,
+            fed_by_change_streams: false,
+        }
+    }
+
+    /// A bus whose events come from the change-stream bridge alone, which
+    /// also sees the writes made by other instances and scripts.
+    pub fn fed_by_change_streams(self) -> Self {
+        EventBus {
+            fed_by_change_streams: true,
+            ..self
+        }
+    }
+
+    pub fn publish(&self, event: Event) {
+        // Sending only fails while nobody is subscribed, which is not an error.
+        let _ = self.sender.send(event);
+    }
+
+    /// Publishes an event raised by a mutation of this instance, unless the
+    /// bridge will report the same write from the change stream.
+    pub fn publish_local(&self, event: Event) {
+        if !self.fed_by_change_streams {
+            self.publish(event);
+        }
+    }
+
+    /// The events accepted by `select`, in publication order. A subscriber
+    /// that lags behind skips what it missed instead of ending the stream.
+    pub fn subscribe<T, F>(&self, select: F) -> impl Stream<Item = T>
+    where
+        F: Fn(Event) -> Option<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        stream::unfold(
+            (self.sender.subscribe(), select),
+            |(mut receiver, select): (Receiver<Event>, F)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => {
+                            if let Some(item) = select(event) {
+                                return Some((item, (receiver, select)));
+                            }
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )
+    }
+}
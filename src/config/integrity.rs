@@ -0,0 +1,76 @@
+use std::{env, str::FromStr};
+
+/// What happens to the documents referencing an entry when that entry is
+/// deleted. Deleted entries are only marked until they are purged, so the
+/// references are rewritten then; until that, they are hidden from clients
+/// and come back when the entry is restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDelete {
+    /// Refuse the deletion as long as anything still references the entry.
+    Restrict,
+    /// Let the dependents follow. Employees keep their place and only lose
+    /// the reference, be it one of their stores or their rank; stores go
+    /// with their location, and come back with it on a restore.
+    Cascade,
+    /// Keep the dependents but clear their reference to the deleted entry.
+    /// For employees this is the same as `Cascade`.
+    Nullify,
+}
+
+impl FromStr for OnDelete {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "restrict" => Ok(OnDelete::Restrict),
+            "cascade" => Ok(OnDelete::Cascade),
+            "nullify" => Ok(OnDelete::Nullify),
+            _ => Err(format!("unknown on-delete policy '{}'", value)),
+        }
+    }
+}
+
+/// On-delete policy for every reference between the collections.
+#[derive(Debug, Clone, Copy)]
+pub struct DeletePolicies {
+    /// `Employee.stores` when a store is deleted.
+    pub employee_store: OnDelete,
+    /// `Employee.rank_id` when a rank is deleted.
+    pub employee_rank: OnDelete,
+    /// `Store.location_id` when a location is deleted.
+    pub store_location: OnDelete,
+}
+
+impl Default for DeletePolicies {
+    fn default() -> Self {
+        DeletePolicies {
+            employee_store: OnDelete::Restrict,
+            employee_rank: OnDelete::Restrict,
+            store_location: OnDelete::Restrict,
+        }
+    }
+}
+
+impl DeletePolicies {
+    /// Reads `ON_DELETE_EMPLOYEE_STORE`, `ON_DELETE_EMPLOYEE_RANK` and
+    /// `ON_DELETE_STORE_LOCATION`, falling back to `restrict` for every
+    /// variable that is not set.
+    pub fn from_env() -> Result<Self, String> {
+        let defaults: DeletePolicies = DeletePolicies::default();
+
+        Ok(DeletePolicies {
+            employee_store: policy_from_env("ON_DELETE_EMPLOYEE_STORE", defaults.employee_store)?,
+            employee_rank: policy_from_env("ON_DELETE_EMPLOYEE_RANK", defaults.employee_rank)?,
+            store_location: policy_from_env("ON_DELETE_STORE_LOCATION", defaults.store_location)?,
+        })
+    }
+}
+
+fn policy_from_env(key: &str, default: OnDelete) -> Result<OnDelete, String> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse::<OnDelete>()
+            .map_err(|error| format!("{}: {}", key, error)),
+        Err(_) => Ok(default),
+    }
+}
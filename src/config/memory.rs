@@ -0,0 +1,1326 @@
+use crate::config::{
+    error::RepositoryError,
+    integrity::{DeletePolicies, OnDelete},
+    mongo_filter::{employee_sort, location_sort, rank_sort, store_sort, SortKey},
+    pagination::{Page, PageRequest},
+    repository::{
+        absent_statuses, check_version, derived_status, employee_patch, location_patch,
+        new_absence, new_employee, new_location, new_rank, new_shift, new_store, rank_patch,
+        shift_patch, store_patch, today, validate_restored_employee, validate_restored_store,
+        AbsenceDecision, AbsenceRepository, AuditRepository, Deletion, EmployeePatch,
+        EmployeeRepository, LocationPatch, LocationRepository, PurgeReport, PurgeRepository,
+        RankPatch, RankRepository, ShiftPatch, ShiftRepository, SoftDeletable,
+        StatusHistoryRepository, StorePatch, StoreRepository, TimeRange,
+    },
+    validation::ValidationMode,
+};
+use crate::schema::project_schema::{
+    Absence, AbsenceFilter, AbsenceState, AuditEntry, AuditFilter, CreateEmployee, CreateLocation,
+    CreateRank, CreateShift, CreateStore, DeleteShift, Employee, EmployeeFilter, EmployeeOrderBy,
+    Location, LocationFilter, LocationOrderBy, Rank, RankFilter, RankOrderBy, RequestAbsence,
+    Shift, Status, StatusTransition, Store, StoreFilter, StoreOrderBy, UpdateEmployee,
+    UpdateLocation, UpdateRank, UpdateShift, UpdateStore,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use mongodb::bson::{oid::ObjectId, to_document, DateTime};
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+
+#[derive(Default)]
+struct Collections {
+    employees: BTreeMap<ObjectId, Employee>,
+    stores: BTreeMap<ObjectId, Store>,
+    locations: BTreeMap<ObjectId, Location>,
+    ranks: BTreeMap<ObjectId, Rank>,
+    shifts: BTreeMap<ObjectId, Shift>,
+    absences: BTreeMap<ObjectId, Absence>,
+    status_history: BTreeMap<ObjectId, StatusTransition>,
+    audit_log: BTreeMap<ObjectId, AuditEntry>,
+}
+
+fn contains<T>(collection: &BTreeMap<ObjectId, T>, id: &str) -> bool {
+    ObjectId::parse_str(id).is_ok_and(|obj_id| collection.contains_key(&obj_id))
+}
+
+fn live<T: SoftDeletable>(collection: &BTreeMap<ObjectId, T>, id: &str) -> bool {
+    ObjectId::parse_str(id).is_ok_and(|obj_id| {
+        collection
+            .get(&obj_id)
+            .is_some_and(|document| !document.is_deleted())
+    })
+}
+
+impl Collections {
+    /// Shifts stay with their employee and their store, absences with their
+    /// employee, while those are marked deleted; they are hidden like them
+    /// and come back when they are restored.
+    fn shift_visible(&self, shift: &Shift) -> bool {
+        live(&self.employees, &shift.employee_id) && live(&self.stores, &shift.store_id)
+    }
+
+    fn absence_visible(&self, absence: &Absence) -> bool {
+        live(&self.employees, &absence.employee_id)
+    }
+
+    /// Shifts go with their employee or their store, absences with their
+    /// employee, once those are purged. Status history is kept.
+    fn drop_orphans(&mut self) {
+        let (employees, stores) = (&self.employees, &self.stores);
+        self.shifts.retain(|_, shift| {
+            contains(employees, &shift.employee_id) && contains(stores, &shift.store_id)
+        });
+        self.absences
+            .retain(|_, absence| contains(employees, &absence.employee_id));
+    }
+
+    /// Applies the `Cascade` and `Nullify` policies: references to purged
+    /// entries are cleared. Until then they are only hidden, so a restore
+    /// brings them back.
+    fn drop_dangling_references(&mut self) {
+        let (stores, locations, ranks) = (&self.stores, &self.locations, &self.ranks);
+        for employee in self.employees.values_mut() {
+            if let Some(store_ids) = employee.stores.as_mut() {
+                store_ids.retain(|store_id| contains(stores, store_id));
+            }
+            if employee
+                .rank_id
+                .as_deref()
+                .is_some_and(|rank_id| !contains(ranks, rank_id))
+            {
+                employee.rank_id = None;
+            }
+        }
+        for store in self.stores.values_mut().filter(|store| {
+            store
+                .location_id
+                .as_deref()
+                .is_some_and(|location_id| !contains(locations, location_id))
+        }) {
+            store.location_id = None;
+        }
+        for shift in self.shifts.values_mut().filter(|shift| {
+            shift
+                .required_rank_id
+                .as_deref()
+                .is_some_and(|rank_id| !contains(ranks, rank_id))
+        }) {
+            shift.required_rank_id = None;
+        }
+    }
+}
+
+/// A backend keeping every collection in process memory. It validates,
+/// filters, orders, pages and enforces delete policies exactly like the
+/// MongoDB backend, which makes it a stand-in for tests and local demos.
+#[derive(Default)]
+pub struct InMemory {
+    collections: Mutex<Collections>,
+    policies: DeletePolicies,
+}
+
+/// Where a document sits in a listing: its sort value, then its ID.
+type Position = (Option<String>, ObjectId);
+
+impl InMemory {
+    pub fn new(policies: DeletePolicies) -> Self {
+        InMemory {
+            collections: Mutex::default(),
+            policies,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Collections> {
+        self.collections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn parse_id(id: &str) -> Result<ObjectId, RepositoryError> {
+        ObjectId::parse_str(id).map_err(|_| RepositoryError::InvalidId(String::from(id)))
+    }
+
+    fn by_ids<T: SoftDeletable + Clone>(
+        collection: &BTreeMap<ObjectId, T>,
+        ids: &[String],
+    ) -> Vec<T> {
+        ids.iter()
+            .filter_map(|id| ObjectId::parse_str(id).ok())
+            .filter_map(|obj_id| {
+                collection
+                    .get(&obj_id)
+                    .filter(|document| !document.is_deleted())
+                    .cloned()
+            })
+            .collect()
+    }
+
+    fn single<T: Clone>(
+        collection: &BTreeMap<ObjectId, T>,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<T, RepositoryError> {
+        let obj_id: ObjectId = InMemory::parse_id(id)?;
+        collection
+            .get(&obj_id)
+            .cloned()
+            .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    /// An entry that is marked deleted (`deleted: true`) or is not.
+    fn single_where<T: SoftDeletable + Clone>(
+        collection: &BTreeMap<ObjectId, T>,
+        entity: &'static str,
+        id: &str,
+        deleted: bool,
+    ) -> Result<T, RepositoryError> {
+        Some(InMemory::single(collection, entity, id)?)
+            .filter(|document| document.is_deleted() == deleted)
+            .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    /// The value a document is ordered by. Documents are compared through
+    /// their BSON form, so the field names match the MongoDB sort keys.
+    fn sort_value<T: Serialize>(document: &T, sort: Option<SortKey>) -> Option<String> {
+        let key: SortKey = sort?;
+        to_document(document)
+            .ok()?
+            .get_str(key.field)
+            .ok()
+            .map(String::from)
+    }
+
+    fn compare(left: &Position, right: &Position, sort: Option<SortKey>) -> Ordering {
+        let ordering: Ordering = left.cmp(right);
+        if sort.is_none_or(|key| key.ascending) {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    }
+
+    fn ordered<T: Serialize>(
+        collection: &BTreeMap<ObjectId, T>,
+        matches: impl Fn(&T) -> bool,
+        sort: Option<SortKey>,
+    ) -> Vec<(Position, &T)> {
+        let mut positioned: Vec<(Position, &T)> = collection
+            .iter()
+            .filter(|(_, document)| matches(document))
+            .map(|(id, document)| ((InMemory::sort_value(document, sort), *id), document))
+            .collect();
+        positioned.sort_by(|(left, _), (right, _)| InMemory::compare(left, right, sort));
+
+        positioned
+    }
+
+    fn find_all<T: Serialize + Clone>(
+        collection: &BTreeMap<ObjectId, T>,
+        matches: impl Fn(&T) -> bool,
+        sort: Option<SortKey>,
+    ) -> Vec<T> {
+        InMemory::ordered(collection, matches, sort)
+            .into_iter()
+            .map(|(_, document)| document.clone())
+            .collect()
+    }
+
+    fn cursor_position<T: Serialize>(
+        collection: &BTreeMap<ObjectId, T>,
+        cursor: &str,
+        sort: Option<SortKey>,
+        field: &str,
+    ) -> Result<Position, RepositoryError> {
+        let obj_id: ObjectId = InMemory::parse_id(cursor)?;
+        if sort.is_none() {
+            return Ok((None, obj_id));
+        }
+
+        let cursor_doc: &T = collection.get(&obj_id).ok_or_else(|| {
+            RepositoryError::invalid_field(
+                field,
+                format!("cursor '{}' does not point to an existing entry", cursor),
+            )
+        })?;
+
+        Ok((InMemory::sort_value(cursor_doc, sort), obj_id))
+    }
+
+    fn find_page<T: Serialize + Clone>(
+        collection: &BTreeMap<ObjectId, T>,
+        matches: impl Fn(&T) -> bool,
+        sort: Option<SortKey>,
+        request: &PageRequest,
+    ) -> Result<Page<T>, RepositoryError> {
+        let after: Option<Position> = request
+            .after
+            .as_deref()
+            .map(|cursor| InMemory::cursor_position(collection, cursor, sort, "after"))
+            .transpose()?;
+        let before: Option<Position> = request
+            .before
+            .as_deref()
+            .map(|cursor| InMemory::cursor_position(collection, cursor, sort, "before"))
+            .transpose()?;
+
+        let positioned: Vec<(Position, &T)> = InMemory::ordered(collection, matches, sort);
+        let total_count: u64 = positioned.len() as u64;
+
+        let mut window: Vec<&T> = positioned
+            .iter()
+            .filter(|(position, _)| {
+                after.as_ref().is_none_or(|after| {
+                    InMemory::compare(position, after, sort) == Ordering::Greater
+                })
+            })
+            .filter(|(position, _)| {
+                before.as_ref().is_none_or(|before| {
+                    InMemory::compare(position, before, sort) == Ordering::Less
+                })
+            })
+            .map(|(_, document)| *document)
+            .collect();
+        if request.is_backward() {
+            window.reverse();
+        }
+        let items: Vec<T> = window
+            .into_iter()
+            .take(request.size() + 1)
+            .cloned()
+            .collect();
+
+        Ok(Page::from_overfetch(items, request, total_count))
+    }
+
+    fn insert<T>(
+        collection: &mut BTreeMap<ObjectId, T>,
+        mut document: T,
+        set_id: fn(&mut T, ObjectId),
+    ) -> T
+    where
+        T: Clone,
+    {
+        let obj_id: ObjectId = ObjectId::new();
+        set_id(&mut document, obj_id);
+        collection.insert(obj_id, document.clone());
+
+        document
+    }
+
+    fn update<T: Clone>(
+        collection: &mut BTreeMap<ObjectId, T>,
+        entity: &'static str,
+        id: &str,
+        apply: impl FnOnce(&mut T),
+    ) -> Result<T, RepositoryError> {
+        let obj_id: ObjectId = InMemory::parse_id(id)?;
+        let document: &mut T = collection
+            .get_mut(&obj_id)
+            .ok_or_else(|| RepositoryError::not_found(entity, id))?;
+        apply(document);
+
+        Ok(document.clone())
+    }
+
+    /// Like `update`, for an entry that is marked deleted (`deleted: true`)
+    /// or is not.
+    fn update_where<T: SoftDeletable + Clone>(
+        collection: &mut BTreeMap<ObjectId, T>,
+        entity: &'static str,
+        id: &str,
+        deleted: bool,
+        apply: impl FnOnce(&mut T),
+    ) -> Result<T, RepositoryError> {
+        InMemory::single_where(collection, entity, id, deleted)?;
+
+        InMemory::update(collection, entity, id, apply)
+    }
+
+    /// Removes the entries marked deleted before the given time and returns
+    /// how many there were.
+    fn purge<T: SoftDeletable>(
+        collection: &mut BTreeMap<ObjectId, T>,
+        deleted_before: DateTime,
+    ) -> u64 {
+        let count: usize = collection.len();
+        collection.retain(|_, document| {
+            document
+                .deleted_at()
+                .is_none_or(|deleted_at| deleted_at >= deleted_before)
+        });
+
+        (count - collection.len()) as u64
+    }
+
+    /// Mirrors the unique name index the other backends keep on live stores
+    /// and ranks. The entry being updated or restored does not conflict with
+    /// itself.
+    fn unique_name<T: SoftDeletable>(
+        collection: &BTreeMap<ObjectId, T>,
+        entity: &'static str,
+        name: &str,
+        except: Option<&str>,
+        name_of: fn(&T) -> &str,
+    ) -> Result<(), RepositoryError> {
+        let taken: bool = collection
+            .iter()
+            .filter(|(id, _)| except.is_none_or(|except| id.to_hex() != except))
+            .any(|(_, document)| !document.is_deleted() && name_of(document) == name);
+        if taken {
+            return Err(RepositoryError::Duplicate(format!(
+                "a {} named '{}' already exists",
+                entity, name
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn remove<T>(
+        collection: &mut BTreeMap<ObjectId, T>,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<T, RepositoryError> {
+        let obj_id: ObjectId = InMemory::parse_id(id)?;
+        collection
+            .remove(&obj_id)
+            .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    /// The live entries that depend on an entry being deleted.
+    fn dependent_ids<T: SoftDeletable>(
+        collection: &BTreeMap<ObjectId, T>,
+        depends: impl Fn(&T) -> bool,
+    ) -> Vec<String> {
+        collection
+            .iter()
+            .filter(|(_, document)| !document.is_deleted() && depends(document))
+            .map(|(id, _)| id.to_string())
+            .collect()
+    }
+
+    /// Refuses to delete stores live employees are assigned to under
+    /// `Restrict`. The other policies leave the assignments for the purge.
+    fn check_assignments(
+        &self,
+        collections: &Collections,
+        store_ids: &[String],
+    ) -> Result<(), RepositoryError> {
+        let assigned = |employee: &Employee| {
+            employee
+                .stores
+                .as_ref()
+                .is_some_and(|stores| stores.iter().any(|store_id| store_ids.contains(store_id)))
+        };
+
+        if self.policies.employee_store == OnDelete::Restrict {
+            let dependents: Vec<String> = InMemory::dependent_ids(&collections.employees, assigned);
+            if !dependents.is_empty() {
+                return Err(RepositoryError::HasDependents {
+                    entity: "store",
+                    id: store_ids.join(", "),
+                    dependent_entity: "employee",
+                    dependents,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/*
+ * Employee Filters
+ */
+fn name_matches(employee: &Employee, matches: impl Fn(&str) -> bool) -> bool {
+    matches(&employee.first_name.to_lowercase()) || matches(&employee.last_name.to_lowercase())
+}
+
+/// `absent` holds the statuses approved absences give today, which take
+/// precedence over the stored status like they do in the `status` resolver.
+fn employee_matches(
+    filter: &EmployeeFilter,
+    absent: &HashMap<String, Status>,
+    employee: &Employee,
+) -> bool {
+    (filter.include_deleted || !employee.is_deleted())
+        && filter.status_in.as_ref().is_none_or(|status_in| {
+            derived_status(absent, employee).is_some_and(|status| status_in.contains(&status))
+        })
+        && filter.store_id.as_ref().is_none_or(|store_id| {
+            employee
+                .stores
+                .as_ref()
+                .is_some_and(|stores| stores.contains(store_id))
+        })
+        && filter
+            .rank_id
+            .as_ref()
+            .is_none_or(|rank_id| employee.rank_id.as_ref() == Some(rank_id))
+        && filter.name_prefix.as_ref().is_none_or(|prefix| {
+            name_matches(employee, |name| name.starts_with(&prefix.to_lowercase()))
+        })
+        && filter
+            .name_contains
+            .as_ref()
+            .is_none_or(|part| name_matches(employee, |name| name.contains(&part.to_lowercase())))
+}
+
+fn store_matches(filter: &StoreFilter, store: &Store) -> bool {
+    (filter.include_deleted || !store.is_deleted())
+        && filter
+            .location_id
+            .as_ref()
+            .is_none_or(|location_id| store.location_id.as_ref() == Some(location_id))
+        && filter.name.as_ref().is_none_or(|name| &store.name == name)
+}
+
+fn location_matches(filter: &LocationFilter, location: &Location) -> bool {
+    (filter.include_deleted || !location.is_deleted())
+        && filter
+            .country
+            .as_ref()
+            .is_none_or(|country| &location.country == country)
+        && filter
+            .state
+            .as_ref()
+            .is_none_or(|state| &location.state == state)
+}
+
+fn rank_matches(filter: &RankFilter, rank: &Rank) -> bool {
+    (filter.include_deleted || !rank.is_deleted())
+        && filter.name.as_ref().is_none_or(|name| &rank.name == name)
+}
+
+fn audit_matches(filter: &AuditFilter, entry: &AuditEntry) -> bool {
+    filter
+        .actor
+        .as_ref()
+        .is_none_or(|actor| entry.actor.as_ref() == Some(actor))
+        && filter
+            .operation
+            .as_ref()
+            .is_none_or(|operation| &entry.operation == operation)
+        && filter
+            .entity_type
+            .as_ref()
+            .is_none_or(|entity_type| &entry.entity_type == entity_type)
+        && filter
+            .entity_id
+            .as_ref()
+            .is_none_or(|entity_id| &entry.entity_id == entity_id)
+        && TimeRange {
+            from: filter.from,
+            to: filter.to,
+        }
+        .contains(entry.at)
+}
+
+fn absence_matches(filter: &AbsenceFilter, absence: &Absence) -> bool {
+    filter
+        .employee_id
+        .as_ref()
+        .is_none_or(|employee_id| &absence.employee_id == employee_id)
+        && filter
+            .state_in
+            .as_ref()
+            .is_none_or(|state_in| state_in.contains(&absence.state))
+        && filter.from.is_none_or(|from| absence.ends_on >= from)
+        && filter.to.is_none_or(|to| absence.starts_on <= to)
+}
+
+/*
+ * Employee Repository
+ */
+#[async_trait]
+impl EmployeeRepository for InMemory {
+    async fn create_employee(
+        &self,
+        new_entry: CreateEmployee,
+        mode: ValidationMode,
+    ) -> Result<Employee, RepositoryError> {
+        let new_doc: Employee = new_employee(self, new_entry, mode).await?;
+
+        Ok(InMemory::insert(
+            &mut self.lock().employees,
+            new_doc,
+            |employee, id| employee.id = Some(id),
+        ))
+    }
+
+    async fn update_employee(
+        &self,
+        update_entry: UpdateEmployee,
+        mode: ValidationMode,
+    ) -> Result<(Employee, Employee), RepositoryError> {
+        InMemory::parse_id(&update_entry.id)?;
+        let id: String = update_entry.id.clone();
+        let patch: EmployeePatch = employee_patch(self, update_entry, mode).await?;
+
+        let mut collections = self.lock();
+        let current: Employee =
+            InMemory::single_where(&collections.employees, "employee", &id, false)?;
+        check_version("employee", &id, patch.version, current.version)?;
+
+        let updated: Employee =
+            InMemory::update(&mut collections.employees, "employee", &id, |employee| {
+                patch.apply(employee)
+            })?;
+
+        Ok((current, updated))
+    }
+
+    async fn delete_employee(&self, deletion: Deletion) -> Result<Employee, RepositoryError> {
+        let mut collections = self.lock();
+        let deleted: Employee = InMemory::update_where(
+            &mut collections.employees,
+            "employee",
+            &deletion.id,
+            false,
+            |employee| deletion.apply(employee),
+        )?;
+
+        Ok(deleted)
+    }
+
+    async fn restore_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        let deleted: Employee = self.get_deleted_employee(id).await?;
+        validate_restored_employee(self, &self.policies, &deleted).await?;
+
+        InMemory::update_where(
+            &mut self.lock().employees,
+            "deleted employee",
+            id,
+            true,
+            |employee| employee.restore(),
+        )
+    }
+
+    async fn get_all_employees(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let absent: HashMap<String, Status> = self.absent_today();
+        Ok(InMemory::find_all(
+            &self.lock().employees,
+            |employee| employee_matches(filter, &absent, employee),
+            employee_sort(order_by),
+        ))
+    }
+
+    async fn get_employees_by_stores(
+        &self,
+        store_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let assigned = |employee: &Employee| {
+            !employee.is_deleted()
+                && employee
+                    .stores
+                    .as_ref()
+                    .is_some_and(|stores| stores.iter().any(|id| store_ids.contains(id)))
+        };
+
+        Ok(InMemory::find_all(&self.lock().employees, assigned, None))
+    }
+
+    async fn get_employees_by_ranks(
+        &self,
+        rank_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let holds_rank = |employee: &Employee| {
+            !employee.is_deleted()
+                && employee
+                    .rank_id
+                    .as_ref()
+                    .is_some_and(|rank_id| rank_ids.contains(rank_id))
+        };
+
+        Ok(InMemory::find_all(&self.lock().employees, holds_rank, None))
+    }
+
+    async fn get_employees_by_ids(&self, ids: &[String]) -> Result<Vec<Employee>, RepositoryError> {
+        Ok(InMemory::by_ids(&self.lock().employees, ids))
+    }
+
+    async fn get_employee_page(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Employee>, RepositoryError> {
+        let absent: HashMap<String, Status> = self.absent_today();
+        InMemory::find_page(
+            &self.lock().employees,
+            |employee| employee_matches(filter, &absent, employee),
+            employee_sort(order_by),
+            request,
+        )
+    }
+
+    async fn get_single_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        InMemory::single_where(&self.lock().employees, "employee", id, false)
+    }
+
+    async fn get_deleted_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        InMemory::single_where(&self.lock().employees, "deleted employee", id, true)
+    }
+}
+
+/*
+ * Store Repository
+ */
+#[async_trait]
+impl StoreRepository for InMemory {
+    async fn create_store(
+        &self,
+        new_entry: CreateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError> {
+        let new_doc: Store = new_store(self, new_entry, mode).await?;
+        let mut collections = self.lock();
+        InMemory::unique_name(&collections.stores, "store", &new_doc.name, None, |store| {
+            &store.name
+        })?;
+
+        Ok(InMemory::insert(
+            &mut collections.stores,
+            new_doc,
+            |store, id| store.id = Some(id),
+        ))
+    }
+
+    async fn update_store(
+        &self,
+        update_entry: UpdateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: StorePatch = store_patch(self, update_entry, mode).await?;
+        let mut collections = self.lock();
+        if let Some(name) = &patch.name {
+            InMemory::unique_name(&collections.stores, "store", name, Some(&id), |store| {
+                &store.name
+            })?;
+        }
+        let current: Store = InMemory::single_where(&collections.stores, "store", &id, false)?;
+        check_version("store", &id, patch.version, current.version)?;
+
+        InMemory::update(&mut collections.stores, "store", &id, |store| {
+            patch.apply(store)
+        })
+    }
+
+    async fn delete_store(&self, deletion: Deletion) -> Result<Store, RepositoryError> {
+        let mut collections = self.lock();
+        InMemory::single_where(&collections.stores, "store", &deletion.id, false)?;
+        self.check_assignments(&collections, std::slice::from_ref(&deletion.id))?;
+        let deleted: Store =
+            InMemory::update(&mut collections.stores, "store", &deletion.id, |store| {
+                deletion.apply(store)
+            })?;
+
+        Ok(deleted)
+    }
+
+    async fn restore_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        let deleted: Store = self.get_deleted_store(id).await?;
+        validate_restored_store(self, &self.policies, &deleted).await?;
+        let mut collections = self.lock();
+        InMemory::unique_name(
+            &collections.stores,
+            "store",
+            &deleted.name,
+            Some(id),
+            |store| &store.name,
+        )?;
+
+        InMemory::update_where(
+            &mut collections.stores,
+            "deleted store",
+            id,
+            true,
+            |store| store.restore(),
+        )
+    }
+
+    async fn get_all_stores(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+    ) -> Result<Vec<Store>, RepositoryError> {
+        Ok(InMemory::find_all(
+            &self.lock().stores,
+            |store| store_matches(filter, store),
+            store_sort(order_by),
+        ))
+    }
+
+    async fn get_stores_by_locations(
+        &self,
+        location_ids: &[String],
+    ) -> Result<Vec<Store>, RepositoryError> {
+        Ok(InMemory::find_all(
+            &self.lock().stores,
+            |store| {
+                !store.is_deleted()
+                    && store
+                        .location_id
+                        .as_ref()
+                        .is_some_and(|location_id| location_ids.contains(location_id))
+            },
+            None,
+        ))
+    }
+
+    async fn get_stores_by_ids(&self, ids: &[String]) -> Result<Vec<Store>, RepositoryError> {
+        Ok(InMemory::by_ids(&self.lock().stores, ids))
+    }
+
+    async fn get_store_page(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Store>, RepositoryError> {
+        InMemory::find_page(
+            &self.lock().stores,
+            |store| store_matches(filter, store),
+            store_sort(order_by),
+            request,
+        )
+    }
+
+    async fn get_single_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        InMemory::single_where(&self.lock().stores, "store", id, false)
+    }
+
+    async fn get_deleted_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        InMemory::single_where(&self.lock().stores, "deleted store", id, true)
+    }
+}
+
+/*
+ * Location Repository
+ */
+#[async_trait]
+impl LocationRepository for InMemory {
+    async fn create_location(
+        &self,
+        new_entry: CreateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError> {
+        let new_doc: Location = new_location(new_entry, mode)?;
+
+        Ok(InMemory::insert(
+            &mut self.lock().locations,
+            new_doc,
+            |location, id| location.id = Some(id),
+        ))
+    }
+
+    async fn update_location(
+        &self,
+        update_entry: UpdateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: LocationPatch = location_patch(update_entry, mode)?;
+
+        let mut collections = self.lock();
+        let current: Location =
+            InMemory::single_where(&collections.locations, "location", &id, false)?;
+        check_version("location", &id, patch.version, current.version)?;
+
+        InMemory::update(&mut collections.locations, "location", &id, |location| {
+            patch.apply(location)
+        })
+    }
+
+    async fn delete_location(&self, deletion: Deletion) -> Result<Location, RepositoryError> {
+        let mut collections = self.lock();
+        InMemory::single_where(&collections.locations, "location", &deletion.id, false)?;
+        let located = |store: &Store| store.location_id.as_ref() == Some(&deletion.id);
+
+        match self.policies.store_location {
+            OnDelete::Restrict => {
+                let dependents: Vec<String> = InMemory::dependent_ids(&collections.stores, located);
+                if !dependents.is_empty() {
+                    return Err(RepositoryError::HasDependents {
+                        entity: "location",
+                        id: deletion.id,
+                        dependent_entity: "store",
+                        dependents,
+                    });
+                }
+            }
+            OnDelete::Cascade => {
+                let store_ids: Vec<String> = InMemory::dependent_ids(&collections.stores, located);
+                if !store_ids.is_empty() {
+                    self.check_assignments(&collections, &store_ids)?;
+                    for store in collections
+                        .stores
+                        .values_mut()
+                        .filter(|store| !store.is_deleted() && located(store))
+                    {
+                        deletion.apply(store);
+                    }
+                }
+            }
+            OnDelete::Nullify => {}
+        }
+
+        InMemory::update(
+            &mut collections.locations,
+            "location",
+            &deletion.id,
+            |location| deletion.apply(location),
+        )
+    }
+
+    async fn restore_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        let mut collections = self.lock();
+        let deleted: Location =
+            InMemory::single_where(&collections.locations, "deleted location", id, true)?;
+        let cascaded = |store: &Store| {
+            store.location_id.as_deref() == Some(id)
+                && store.deleted_at == deleted.deleted_at
+                && store.deleted_by == deleted.deleted_by
+        };
+        for (store_id, store) in collections
+            .stores
+            .iter()
+            .filter(|(_, store)| cascaded(store))
+        {
+            InMemory::unique_name(
+                &collections.stores,
+                "store",
+                &store.name,
+                Some(&store_id.to_hex()),
+                |store| &store.name,
+            )?;
+        }
+        for store in collections
+            .stores
+            .values_mut()
+            .filter(|store| cascaded(store))
+        {
+            store.restore();
+        }
+
+        InMemory::update_where(
+            &mut collections.locations,
+            "deleted location",
+            id,
+            true,
+            |location| location.restore(),
+        )
+    }
+
+    async fn get_all_locations(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+    ) -> Result<Vec<Location>, RepositoryError> {
+        Ok(InMemory::find_all(
+            &self.lock().locations,
+            |location| location_matches(filter, location),
+            location_sort(order_by),
+        ))
+    }
+
+    async fn get_locations_by_ids(&self, ids: &[String]) -> Result<Vec<Location>, RepositoryError> {
+        Ok(InMemory::by_ids(&self.lock().locations, ids))
+    }
+
+    async fn get_location_page(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Location>, RepositoryError> {
+        InMemory::find_page(
+            &self.lock().locations,
+            |location| location_matches(filter, location),
+            location_sort(order_by),
+            request,
+        )
+    }
+
+    async fn get_single_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        InMemory::single_where(&self.lock().locations, "location", id, false)
+    }
+
+    async fn get_deleted_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        InMemory::single_where(&self.lock().locations, "deleted location", id, true)
+    }
+}
+
+/*
+ * Rank Repository
+ */
+#[async_trait]
+impl RankRepository for InMemory {
+    async fn create_rank(
+        &self,
+        new_entry: CreateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError> {
+        let new_doc: Rank = new_rank(new_entry, mode)?;
+        let mut collections = self.lock();
+        InMemory::unique_name(&collections.ranks, "rank", &new_doc.name, None, |rank| {
+            &rank.name
+        })?;
+
+        Ok(InMemory::insert(
+            &mut collections.ranks,
+            new_doc,
+            |rank, id| rank.id = Some(id),
+        ))
+    }
+
+    async fn update_rank(
+        &self,
+        update_entry: UpdateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: RankPatch = rank_patch(update_entry, mode)?;
+        let mut collections = self.lock();
+        if let Some(name) = &patch.name {
+            InMemory::unique_name(&collections.ranks, "rank", name, Some(&id), |rank| {
+                &rank.name
+            })?;
+        }
+        let current: Rank = InMemory::single_where(&collections.ranks, "rank", &id, false)?;
+        check_version("rank", &id, patch.version, current.version)?;
+
+        InMemory::update(&mut collections.ranks, "rank", &id, |rank| {
+            patch.apply(rank)
+        })
+    }
+
+    async fn delete_rank(&self, deletion: Deletion) -> Result<Rank, RepositoryError> {
+        let mut collections = self.lock();
+        InMemory::single_where(&collections.ranks, "rank", &deletion.id, false)?;
+        let ranked = |employee: &Employee| employee.rank_id.as_ref() == Some(&deletion.id);
+
+        if self.policies.employee_rank == OnDelete::Restrict {
+            let dependents: Vec<String> = InMemory::dependent_ids(&collections.employees, ranked);
+            if !dependents.is_empty() {
+                return Err(RepositoryError::HasDependents {
+                    entity: "rank",
+                    id: deletion.id,
+                    dependent_entity: "employee",
+                    dependents,
+                });
+            }
+        }
+
+        InMemory::update(&mut collections.ranks, "rank", &deletion.id, |rank| {
+            deletion.apply(rank)
+        })
+    }
+
+    async fn restore_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        let mut collections = self.lock();
+        let deleted: Rank = InMemory::single_where(&collections.ranks, "deleted rank", id, true)?;
+        InMemory::unique_name(
+            &collections.ranks,
+            "rank",
+            &deleted.name,
+            Some(id),
+            |rank| &rank.name,
+        )?;
+
+        InMemory::update_where(&mut collections.ranks, "deleted rank", id, true, |rank| {
+            rank.restore()
+        })
+    }
+
+    async fn get_all_ranks(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+    ) -> Result<Vec<Rank>, RepositoryError> {
+        Ok(InMemory::find_all(
+            &self.lock().ranks,
+            |rank| rank_matches(filter, rank),
+            rank_sort(order_by),
+        ))
+    }
+
+    async fn get_ranks_by_ids(&self, ids: &[String]) -> Result<Vec<Rank>, RepositoryError> {
+        Ok(InMemory::by_ids(&self.lock().ranks, ids))
+    }
+
+    async fn get_rank_page(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Rank>, RepositoryError> {
+        InMemory::find_page(
+            &self.lock().ranks,
+            |rank| rank_matches(filter, rank),
+            rank_sort(order_by),
+            request,
+        )
+    }
+
+    async fn get_single_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        InMemory::single_where(&self.lock().ranks, "rank", id, false)
+    }
+
+    async fn get_deleted_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        InMemory::single_where(&self.lock().ranks, "deleted rank", id, true)
+    }
+}
+
+/*
+ * Shift Repository
+ */
+impl InMemory {
+    fn shifts_where(&self, matches: impl Fn(&Shift) -> bool) -> Vec<Shift> {
+        let collections = self.lock();
+        let mut shift_vec: Vec<Shift> = collections
+            .shifts
+            .values()
+            .filter(|shift| collections.shift_visible(shift) && matches(shift))
+            .cloned()
+            .collect();
+        shift_vec.sort_by_key(|shift| (shift.starts_at, shift.id));
+
+        shift_vec
+    }
+}
+
+#[async_trait]
+impl ShiftRepository for InMemory {
+    async fn create_shift(&self, new_entry: CreateShift) -> Result<Shift, RepositoryError> {
+        let new_doc: Shift = new_shift(self, new_entry).await?;
+
+        Ok(InMemory::insert(
+            &mut self.lock().shifts,
+            new_doc,
+            |shift, id| shift.id = Some(id),
+        ))
+    }
+
+    async fn update_shift(&self, update_entry: UpdateShift) -> Result<Shift, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: ShiftPatch = shift_patch(self, update_entry).await?;
+
+        let mut collections = self.lock();
+        let current: Shift = InMemory::single(&collections.shifts, "shift", &id)?;
+        check_version("shift", &id, patch.version, current.version)?;
+
+        InMemory::update(&mut collections.shifts, "shift", &id, |shift| {
+            patch.apply(shift)
+        })
+    }
+
+    async fn delete_shift(&self, delete_entry: DeleteShift) -> Result<Shift, RepositoryError> {
+        InMemory::remove(&mut self.lock().shifts, "shift", &delete_entry.id)
+    }
+
+    async fn get_shifts_by_store(
+        &self,
+        store_id: &str,
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        Ok(self.shifts_where(|shift| shift.store_id == store_id && range.overlaps(shift)))
+    }
+
+    async fn get_shifts_by_employees(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        Ok(self.shifts_where(|shift| {
+            employee_ids.contains(&shift.employee_id) && range.overlaps(shift)
+        }))
+    }
+
+    async fn get_single_shift(&self, id: &str) -> Result<Shift, RepositoryError> {
+        let collections = self.lock();
+        Some(InMemory::single(&collections.shifts, "shift", id)?)
+            .filter(|shift| collections.shift_visible(shift))
+            .ok_or_else(|| RepositoryError::not_found("shift", id))
+    }
+}
+
+/*
+ * Absence Repository
+ */
+impl InMemory {
+    fn absences_where(&self, matches: impl Fn(&Absence) -> bool) -> Vec<Absence> {
+        let collections = self.lock();
+        let mut absence_vec: Vec<Absence> = collections
+            .absences
+            .values()
+            .filter(|absence| collections.absence_visible(absence) && matches(absence))
+            .cloned()
+            .collect();
+        absence_vec.sort_by_key(|absence| (absence.starts_on, absence.id));
+
+        absence_vec
+    }
+
+    fn absent_today(&self) -> HashMap<String, Status> {
+        let day: NaiveDate = today();
+        absent_statuses(&self.absences_where(|absence| {
+            absence.state == AbsenceState::Approved
+                && absence.starts_on <= day
+                && day <= absence.ends_on
+        }))
+    }
+}
+
+#[async_trait]
+impl AbsenceRepository for InMemory {
+    async fn request_absence(&self, new_entry: RequestAbsence) -> Result<Absence, RepositoryError> {
+        let new_doc: Absence = new_absence(self, new_entry).await?;
+
+        Ok(InMemory::insert(
+            &mut self.lock().absences,
+            new_doc,
+            |absence, id| absence.id = Some(id),
+        ))
+    }
+
+    async fn decide_absence(&self, decision: AbsenceDecision) -> Result<Absence, RepositoryError> {
+        let mut collections = self.lock();
+        let obj_id: ObjectId = InMemory::parse_id(&decision.id)?;
+        let absence: &mut Absence = collections
+            .absences
+            .get_mut(&obj_id)
+            .ok_or_else(|| RepositoryError::not_found("absence", &decision.id))?;
+        decision.check(absence)?;
+        decision.apply(absence);
+
+        Ok(absence.clone())
+    }
+
+    async fn get_absences(&self, filter: &AbsenceFilter) -> Result<Vec<Absence>, RepositoryError> {
+        Ok(self.absences_where(|absence| absence_matches(filter, absence)))
+    }
+
+    async fn get_absences_by_employees(
+        &self,
+        employee_ids: &[String],
+    ) -> Result<Vec<Absence>, RepositoryError> {
+        Ok(self.absences_where(|absence| employee_ids.contains(&absence.employee_id)))
+    }
+
+    async fn get_approved_absences_on(
+        &self,
+        employee_ids: &[String],
+        day: NaiveDate,
+    ) -> Result<Vec<Absence>, RepositoryError> {
+        Ok(self.absences_where(|absence| {
+            absence.state == AbsenceState::Approved
+                && employee_ids.contains(&absence.employee_id)
+                && absence.starts_on <= day
+                && day <= absence.ends_on
+        }))
+    }
+
+    async fn get_single_absence(&self, id: &str) -> Result<Absence, RepositoryError> {
+        let collections = self.lock();
+        Some(InMemory::single(&collections.absences, "absence", id)?)
+            .filter(|absence| collections.absence_visible(absence))
+            .ok_or_else(|| RepositoryError::not_found("absence", id))
+    }
+}
+
+/*
+ * Status History Repository
+ */
+#[async_trait]
+impl StatusHistoryRepository for InMemory {
+    async fn record_status_transition(
+        &self,
+        transition: StatusTransition,
+    ) -> Result<StatusTransition, RepositoryError> {
+        Ok(InMemory::insert(
+            &mut self.lock().status_history,
+            transition,
+            |transition, id| transition.id = Some(id),
+        ))
+    }
+
+    async fn get_status_history(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<StatusTransition>, RepositoryError> {
+        let mut transition_vec: Vec<StatusTransition> = self
+            .lock()
+            .status_history
+            .values()
+            .filter(|transition| {
+                employee_ids.contains(&transition.employee_id)
+                    && range.contains(transition.changed_at)
+            })
+            .cloned()
+            .collect();
+        transition_vec.sort_by_key(|transition| (transition.changed_at, transition.id));
+
+        Ok(transition_vec)
+    }
+}
+
+/*
+ * Audit Repository
+ */
+#[async_trait]
+impl AuditRepository for InMemory {
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, RepositoryError> {
+        Ok(InMemory::insert(
+            &mut self.lock().audit_log,
+            entry,
+            |entry, id| entry.id = Some(id),
+        ))
+    }
+
+    async fn get_audit_page(
+        &self,
+        filter: &AuditFilter,
+        request: &PageRequest,
+    ) -> Result<Page<AuditEntry>, RepositoryError> {
+        InMemory::find_page(
+            &self.lock().audit_log,
+            |entry| audit_matches(filter, entry),
+            None,
+            request,
+        )
+    }
+}
+
+/*
+ * Purge Repository
+ */
+#[async_trait]
+impl PurgeRepository for InMemory {
+    async fn purge_deleted(
+        &self,
+        deleted_before: DateTime,
+    ) -> Result<PurgeReport, RepositoryError> {
+        let mut collections = self.lock();
+        let report: PurgeReport = PurgeReport {
+            employees: InMemory::purge(&mut collections.employees, deleted_before),
+            stores: InMemory::purge(&mut collections.stores, deleted_before),
+            locations: InMemory::purge(&mut collections.locations, deleted_before),
+            ranks: InMemory::purge(&mut collections.ranks, deleted_before),
+        };
+        collections.drop_orphans();
+        collections.drop_dangling_references();
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,647 @@
+use crate::schema::project_schema::{Status, FIRST_VERSION};
+use futures_util::{
+    future::{select, BoxFuture, Either},
+    TryStreamExt,
+};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, DateTime, Document},
+    error::{Error as MongoError, ErrorKind, WriteFailure},
+    options::IndexOptions,
+    Collection, Database, IndexModel,
+};
+use rocket::tokio::time::sleep;
+use std::time::Duration;
+
+/// Records which migrations a database has seen, one document per version.
+pub const MIGRATION_COLLECTION: &str = "_migrations";
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+type MigrationFn = for<'a> fn(&'a Database) -> BoxFuture<'a, mongodb::error::Result<()>>;
+
+/// A single, versioned change to the database. Applied migrations are never
+/// edited; later changes get a new version.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: MigrationFn,
+}
+
+/// Every migration in the order it is applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Index the fields employees and stores are filtered by",
+        apply: index_filter_fields,
+    },
+    Migration {
+        version: 2,
+        description: "Make rank and store names unique",
+        apply: unique_names,
+    },
+    Migration {
+        version: 3,
+        description: "Store every employee status under its canonical name",
+        apply: normalize_status,
+    },
+    Migration {
+        version: 4,
+        description: "Index shifts by employee and by store",
+        apply: index_shifts,
+    },
+    Migration {
+        version: 5,
+        description: "Index absences by employee and by day",
+        apply: index_absences,
+    },
+    Migration {
+        version: 6,
+        description: "Index status history by employee and time",
+        apply: index_status_history,
+    },
+    Migration {
+        version: 7,
+        description: "Index the audit log by entity and by actor",
+        apply: index_audit_log,
+    },
+    Migration {
+        version: 8,
+        description: "Index when employees, stores, locations and ranks were deleted",
+        apply: index_deleted_at,
+    },
+    Migration {
+        version: 9,
+        description: "Start every entry at version 1",
+        apply: initial_versions,
+    },
+    Migration {
+        version: 10,
+        description: "Only keep names unique among ranks and stores that are not deleted",
+        apply: unique_live_names,
+    },
+    Migration {
+        version: 11,
+        description: "Store missing rank and location references as null",
+        apply: null_missing_references,
+    },
+    Migration {
+        version: 12,
+        description: "Mark the shifts and absences of deleted employees and stores",
+        apply: mark_deleted_owners,
+    },
+    Migration {
+        version: 13,
+        description: "Start every absence at version 1",
+        apply: initial_absence_versions,
+    },
+];
+
+/// How long a claim holds without being renewed. A running migration renews
+/// its claim well before then; a claim left by an instance that died halfway
+/// runs out and is taken over by the next one.
+const CLAIM_TTL: Duration = Duration::from_secs(120);
+
+/// How often an instance looks again at a migration another one is applying.
+const CLAIM_POLL: Duration = Duration::from_secs(1);
+
+/// Where another instance's claim on a migration stands.
+#[derive(Debug, PartialEq, Eq)]
+enum ClaimState {
+    Applied,
+    Held,
+    Expired,
+}
+
+fn claim_state(claim: &Document, now: DateTime) -> ClaimState {
+    if claim.contains_key("applied_at") {
+        return ClaimState::Applied;
+    }
+    // Claims written before claims expired only carry their start.
+    let expires_at: Option<DateTime> =
+        claim.get_datetime("expires_at").ok().copied().or_else(|| {
+            claim
+                .get_datetime("started_at")
+                .ok()
+                .map(|started_at| expires_after(*started_at))
+        });
+    match expires_at {
+        Some(expires_at) if expires_at > now => ClaimState::Held,
+        _ => ClaimState::Expired,
+    }
+}
+
+fn expires_after(time: DateTime) -> DateTime {
+    DateTime::from_millis(time.timestamp_millis() + CLAIM_TTL.as_millis() as i64)
+}
+
+/// Applies every migration the database has not seen yet and returns them.
+///
+/// A migration is claimed by inserting its entry before it runs, so that two
+/// instances starting at once do not apply it twice. An instance finding a
+/// migration claimed waits until it is applied, or takes it over once the
+/// claim has expired. A failed migration releases its claim and stops the
+/// run.
+pub async fn migrate(db: &Database) -> Result<Vec<&'static Migration>, String> {
+    let col: Collection<Document> = db.collection(MIGRATION_COLLECTION);
+    let applied: Vec<u32> = applied_versions(db)
+        .await
+        .map_err(|error| format!("cannot read {}: {}", MIGRATION_COLLECTION, error))?;
+    let owner: String = ObjectId::new().to_hex();
+
+    let mut newly_applied: Vec<&'static Migration> = Vec::new();
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+    {
+        if !claim(&col, migration, &owner).await? {
+            continue;
+        }
+
+        let apply: BoxFuture<'_, mongodb::error::Result<()>> = (migration.apply)(db);
+        let renew: BoxFuture<'_, ()> = Box::pin(renew_claim(&col, migration.version, &owner));
+        let result: mongodb::error::Result<()> = match select(apply, renew).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), _)) => unreachable!("claims are renewed until the migration ends"),
+        };
+        if let Err(error) = result {
+            col.delete_one(doc! {"_id": migration.version, "owner": &owner}, None)
+                .await
+                .ok();
+            return Err(format!(
+                "migration {} ({}) failed: {}",
+                migration.version, migration.description, error
+            ));
+        }
+
+        let recorded = col
+            .update_one(
+                doc! {"_id": migration.version, "owner": &owner},
+                doc! {"$set": {"applied_at": DateTime::now()}, "$unset": {"expires_at": ""}},
+                None,
+            )
+            .await
+            .map_err(|error| format!("cannot record migration {}: {}", migration.version, error))?;
+        if recorded.matched_count == 0 {
+            return Err(format!(
+                "lost the claim on migration {} while applying it",
+                migration.version
+            ));
+        }
+        newly_applied.push(migration);
+    }
+
+    Ok(newly_applied)
+}
+
+/// Claims a migration for `owner`. Returns `false` once another instance
+/// has applied it instead.
+async fn claim(
+    col: &Collection<Document>,
+    migration: &Migration,
+    owner: &str,
+) -> Result<bool, String> {
+    let cannot_claim =
+        |error: MongoError| format!("cannot claim migration {}: {}", migration.version, error);
+
+    loop {
+        let now: DateTime = DateTime::now();
+        let entry: Document = doc! {
+            "_id": migration.version,
+            "description": migration.description,
+            "owner": owner,
+            "started_at": now,
+            "expires_at": expires_after(now),
+        };
+        match col.insert_one(&entry, None).await {
+            Ok(_) => return Ok(true),
+            Err(error) if is_duplicate_key(&error) => {}
+            Err(error) => return Err(cannot_claim(error)),
+        }
+
+        let current: Document = match col
+            .find_one(doc! {"_id": migration.version}, None)
+            .await
+            .map_err(cannot_claim)?
+        {
+            Some(current) => current,
+            // Released in the meantime; claim it afresh.
+            None => continue,
+        };
+        match claim_state(&current, now) {
+            ClaimState::Applied => return Ok(false),
+            ClaimState::Held => sleep(CLAIM_POLL).await,
+            ClaimState::Expired => {
+                // Only one instance gets to replace a given stale claim.
+                let mut stale: Document =
+                    doc! {"_id": migration.version, "applied_at": {"$exists": false}};
+                stale.insert("owner", current.get("owner").cloned().unwrap_or(Bson::Null));
+                let taken_over = col
+                    .replace_one(stale, &entry, None)
+                    .await
+                    .map_err(cannot_claim)?;
+                if taken_over.modified_count == 1 {
+                    log::warn!(
+                        "Took over the expired claim on migration {}",
+                        migration.version
+                    );
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+
+/// Keeps extending `owner`'s claim on a migration while it runs.
+async fn renew_claim(col: &Collection<Document>, version: u32, owner: &str) {
+    loop {
+        sleep(CLAIM_TTL / 4).await;
+        if let Err(error) = col
+            .update_one(
+                doc! {"_id": version, "owner": owner},
+                doc! {"$set": {"expires_at": expires_after(DateTime::now())}},
+                None,
+            )
+            .await
+        {
+            log::warn!("Cannot renew the claim on migration {}: {}", version, error);
+        }
+    }
+}
+
+/// Versions that finished applying. Claims without `applied_at` belong to a
+/// run that is still going, or that died halfway and expires.
+async fn applied_versions(db: &Database) -> mongodb::error::Result<Vec<u32>> {
+    let col: Collection<Document> = db.collection(MIGRATION_COLLECTION);
+    let entries: Vec<Document> = col
+        .find(doc! {"applied_at": {"$exists": true}}, None)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| entry.get_i32("_id").ok())
+        .map(|version| version as u32)
+        .collect())
+}
+
+fn is_duplicate_key(error: &MongoError) -> bool {
+    matches!(*error.kind, ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == DUPLICATE_KEY_CODE)
+}
+
+fn index(keys: Document, name: &str, unique: bool) -> IndexModel {
+    let options: IndexOptions = IndexOptions::builder()
+        .name(Some(String::from(name)))
+        .unique(unique.then_some(true))
+        .build();
+
+    IndexModel::builder().keys(keys).options(options).build()
+}
+
+/*
+ * Migrations
+ */
+fn index_filter_fields(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        db.collection::<Document>("employee")
+            .create_indexes(
+                [
+                    index(doc! {"status": 1}, "status", false),
+                    index(doc! {"stores": 1}, "stores", false),
+                    index(doc! {"rank_id": 1}, "rank_id", false),
+                ],
+                None,
+            )
+            .await?;
+        db.collection::<Document>("store")
+            .create_index(index(doc! {"location_id": 1}, "location_id", false), None)
+            .await?;
+
+        Ok(())
+    })
+}
+
+/// Existing duplicates keep the name on their oldest entry; the others get
+/// their ID appended, so the unique index can be built.
+fn unique_names(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        for collection_name in ["rank", "store"] {
+            let col: Collection<Document> = db.collection(collection_name);
+            let pipeline: Vec<Document> = vec![
+                doc! {"$sort": {"_id": 1}},
+                doc! {"$group": {"_id": "$name", "ids": {"$push": "$_id"}}},
+                doc! {"$match": {"ids.1": {"$exists": true}}},
+            ];
+            let duplicates: Vec<Document> =
+                col.aggregate(pipeline, None).await?.try_collect().await?;
+
+            for duplicate in duplicates {
+                let name: &str = duplicate.get_str("_id").unwrap_or_default();
+                let ids: Vec<ObjectId> = duplicate
+                    .get_array("ids")
+                    .map(|ids| ids.iter().filter_map(|id| id.as_object_id()).collect())
+                    .unwrap_or_default();
+
+                for id in ids.iter().skip(1) {
+                    col.update_one(
+                        doc! {"_id": id},
+                        doc! {"$set": {"name": format!("{} ({})", name, id)}},
+                        None,
+                    )
+                    .await?;
+                }
+            }
+
+            col.create_index(index(doc! {"name": 1}, "name_unique", true), None)
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Legacy spellings are rewritten to the canonical name. Values that match
+/// no status become `Unknown`, with the original kept in `legacy_status`.
+fn normalize_status(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let col: Collection<Document> = db.collection("employee");
+        let canonical: Vec<Bson> = Status::KNOWN
+            .iter()
+            .chain([&Status::Unknown])
+            .map(|status| Bson::from(status.as_str()))
+            .chain([Bson::Null])
+            .collect();
+        let filter: Document = doc! {"status": {"$exists": true, "$nin": canonical}};
+        let employees: Vec<Document> = col.find(filter, None).await?.try_collect().await?;
+
+        for employee in employees {
+            let stored: Bson = employee.get("status").cloned().unwrap_or(Bson::Null);
+            let update: Document = match &stored {
+                Bson::String(value) if Status::decode(value) != Status::Unknown => {
+                    doc! {"$set": {"status": Status::decode(value).as_str()}}
+                }
+                _ => doc! {"$set": {"status": Status::Unknown.as_str(), "legacy_status": stored}},
+            };
+            col.update_one(doc! {"_id": employee.get("_id")}, update, None)
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+fn index_shifts(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        db.collection::<Document>("shift")
+            .create_indexes(
+                [
+                    index(
+                        doc! {"employee_id": 1, "starts_at": 1},
+                        "employee_id_starts_at",
+                        false,
+                    ),
+                    index(
+                        doc! {"store_id": 1, "starts_at": 1},
+                        "store_id_starts_at",
+                        false,
+                    ),
+                ],
+                None,
+            )
+            .await?;
+
+        Ok(())
+    })
+}
+
+fn index_absences(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        db.collection::<Document>("absence")
+            .create_indexes(
+                [
+                    index(
+                        doc! {"employee_id": 1, "starts_on": 1},
+                        "employee_id_starts_on",
+                        false,
+                    ),
+                    index(doc! {"state": 1, "starts_on": 1}, "state_starts_on", false),
+                ],
+                None,
+            )
+            .await?;
+
+        Ok(())
+    })
+}
+
+fn index_status_history(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        db.collection::<Document>("status_transition")
+            .create_index(
+                index(
+                    doc! {"employee_id": 1, "changed_at": 1},
+                    "employee_id_changed_at",
+                    false,
+                ),
+                None,
+            )
+            .await?;
+
+        Ok(())
+    })
+}
+
+fn index_audit_log(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        db.collection::<Document>("audit_entry")
+            .create_indexes(
+                [
+                    index(doc! {"entity_type": 1, "entity_id": 1}, "entity", false),
+                    index(doc! {"actor": 1}, "actor", false),
+                ],
+                None,
+            )
+            .await?;
+
+        Ok(())
+    })
+}
+
+fn index_deleted_at(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        for collection_name in ["employee", "store", "location", "rank"] {
+            db.collection::<Document>(collection_name)
+                .create_index(index(doc! {"deleted_at": 1}, "deleted_at", false), None)
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Entries written before versioning count as the first version, so that
+/// conditional updates can match them.
+fn initial_versions(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        for collection_name in ["employee", "store", "location", "rank", "shift"] {
+            db.collection::<Document>(collection_name)
+                .update_many(
+                    doc! {"version": {"$exists": false}},
+                    doc! {"$set": {"version": FIRST_VERSION}},
+                    None,
+                )
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Deleted entries give up their name, so that a new entry can take it. The
+/// index is dropped first, because one with the same name and keys but
+/// without a filter cannot be replaced in place.
+fn unique_live_names(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        for collection_name in ["rank", "store"] {
+            let col: Collection<Document> = db.collection(collection_name);
+            let options: IndexOptions = IndexOptions::builder()
+                .name(Some(String::from("name_unique")))
+                .unique(Some(true))
+                .partial_filter_expression(Some(doc! {"deleted_at": null}))
+                .build();
+
+            col.drop_index("name_unique", None).await?;
+            col.create_index(
+                IndexModel::builder()
+                    .keys(doc! {"name": 1})
+                    .options(options)
+                    .build(),
+                None,
+            )
+            .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Employees without a rank and stores without a location used to hold an
+/// empty string, which every filter had to tell apart from an ID.
+fn null_missing_references(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        for (collection_name, field) in [("employee", "rank_id"), ("store", "location_id")] {
+            db.collection::<Document>(collection_name)
+                .update_many(doc! {field: ""}, doc! {"$set": {field: null}}, None)
+                .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Shifts and absences are hidden while `deleted_owners` names one of their
+/// owners. The marks are kept up to date on delete and restore; this sets
+/// them for the owners deleted before.
+fn mark_deleted_owners(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let owners: [(&str, &str, &[&str]); 2] = [
+            ("employee", "employee_id", &["shift", "absence"]),
+            ("store", "store_id", &["shift"]),
+        ];
+        for (owner_collection, field, collection_names) in owners {
+            let deleted: Vec<Document> = db
+                .collection::<Document>(owner_collection)
+                .find(doc! {"deleted_at": {"$ne": null}}, None)
+                .await?
+                .try_collect()
+                .await?;
+            let owner_ids: Vec<String> = deleted
+                .iter()
+                .filter_map(|owner| owner.get_object_id("_id").ok())
+                .map(|id| id.to_hex())
+                .collect();
+            for owner_id in &owner_ids {
+                for collection_name in collection_names {
+                    db.collection::<Document>(collection_name)
+                        .update_many(
+                            doc! {field: owner_id},
+                            doc! {"$addToSet": {"deleted_owners": owner_id}},
+                            None,
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Absences got versions after the other entries; see `initial_versions`.
+fn initial_absence_versions(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        db.collection::<Document>("absence")
+            .update_many(
+                doc! {"version": {"$exists": false}},
+                doc! {"$set": {"version": FIRST_VERSION}},
+                None,
+            )
+            .await?;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_increase_strictly() {
+        assert!(MIGRATIONS
+            .windows(2)
+            .all(|pair| pair[0].version < pair[1].version));
+        assert!(MIGRATIONS
+            .first()
+            .is_some_and(|migration| migration.version == 1));
+    }
+
+    #[test]
+    fn tells_held_claims_from_expired_ones() {
+        let now: DateTime = DateTime::now();
+        let earlier: DateTime = DateTime::from_millis(now.timestamp_millis() - 1000);
+
+        assert_eq!(
+            claim_state(&doc! {"started_at": earlier, "applied_at": earlier}, now),
+            ClaimState::Applied
+        );
+        assert_eq!(
+            claim_state(
+                &doc! {"started_at": earlier, "expires_at": expires_after(now)},
+                now
+            ),
+            ClaimState::Held
+        );
+        assert_eq!(
+            claim_state(&doc! {"started_at": earlier, "expires_at": earlier}, now),
+            ClaimState::Expired
+        );
+    }
+
+    #[test]
+    fn expires_claims_without_expiry_after_their_start() {
+        let now: DateTime = DateTime::now();
+        let long_ago: DateTime =
+            DateTime::from_millis(now.timestamp_millis() - 2 * CLAIM_TTL.as_millis() as i64);
+
+        assert_eq!(
+            claim_state(&doc! {"started_at": now}, now),
+            ClaimState::Held
+        );
+        assert_eq!(
+            claim_state(&doc! {"started_at": long_ago}, now),
+            ClaimState::Expired
+        );
+        assert_eq!(claim_state(&doc! {}, now), ClaimState::Expired);
+    }
+}
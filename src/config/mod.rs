@@ -1 +1,17 @@
-pub mod mongo;
\ No newline at end of file
+pub mod audit;
+pub mod auth;
+pub mod change_stream;
+pub mod error;
+pub mod event_bus;
+pub mod integrity;
+pub mod memory;
+pub mod migration;
+pub mod mongo;
+pub mod mongo_config;
+pub mod mongo_filter;
+pub mod pagination;
+pub mod postgres;
+pub mod postgres_filter;
+pub mod repository;
+pub mod retention;
+pub mod validation;
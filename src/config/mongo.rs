@@ -1,303 +1,1627 @@
+use crate::config::{
+    error::RepositoryError,
+    integrity::{DeletePolicies, OnDelete},
+    migration::{self, Migration},
+    mongo_config::MongoConfig,
+    mongo_filter::{
+        absence_filter, all_of, audit_filter, employee_filter, employee_sort, location_filter,
+        location_sort, rank_filter, rank_sort, store_filter, store_sort, SortKey,
+    },
+    pagination::{Page, PageRequest},
+    repository::{
+        absent_statuses, employee_patch, location_patch, new_absence, new_employee, new_location,
+        new_rank, new_shift, new_store, rank_patch, shift_patch, store_patch, today,
+        validate_restored_employee, validate_restored_store, AbsenceDecision, AbsenceRepository,
+        AuditRepository, Deletion, EmployeePatch, EmployeeRepository, LocationPatch,
+        LocationRepository, PurgeReport, PurgeRepository, RankPatch, RankRepository, ShiftPatch,
+        ShiftRepository, StatusHistoryRepository, StorePatch, StoreRepository, TimeRange,
+    },
+    validation::ValidationMode,
+};
+use crate::schema::project_schema::{
+    Absence, AbsenceFilter, AbsenceState, AuditEntry, AuditFilter, CreateEmployee, CreateLocation,
+    CreateRank, CreateShift, CreateStore, DeleteShift, Employee, EmployeeFilter, EmployeeOrderBy,
+    Location, LocationFilter, LocationOrderBy, Rank, RankFilter, RankOrderBy, RequestAbsence,
+    Shift, Status, StatusTransition, Store, StoreFilter, StoreOrderBy, UpdateEmployee,
+    UpdateLocation, UpdateRank, UpdateShift, UpdateStore, FIRST_VERSION,
+};
+use async_graphql::MaybeUndefined;
+use async_trait::async_trait;
+use chrono::NaiveDate;
 use dotenv::dotenv;
-use std::{env, io::Error};
+use futures_util::TryStreamExt;
 use mongodb::{
-    bson::{doc, oid::ObjectId, Document},
-    sync::{Client, Collection, Database, Cursor},
-    results::{InsertOneResult}};
-use crate::schema::project_schema::{CreateEmployee, DeleteEmployee, Employee, UpdateEmployee, Status, Store, CreateStore, Location, Rank, CreateLocation, CreateRank};
+    bson::{doc, from_bson, oid::ObjectId, to_bson, Bson, DateTime, Document},
+    change_stream::{
+        event::{ChangeStreamEvent, ResumeToken},
+        ChangeStream,
+    },
+    options::{
+        ChangeStreamOptions, ClientOptions, FindOneAndUpdateOptions, FindOptions,
+        FullDocumentBeforeChangeType, FullDocumentType, ReturnDocument, UpdateModifications,
+        UpdateOptions,
+    },
+    results::InsertOneResult,
+    Client, Collection, Cursor, Database,
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 
+/// Where the change-stream bridge remembers how far it got, one document
+/// per watched collection.
+const RESUME_TOKEN_COLLECTION: &str = "change_stream_token";
+
+#[derive(Clone)]
 pub struct MongoDB {
     db: Database,
+    policies: DeletePolicies,
 }
 
 impl MongoDB {
-    pub fn init() -> Self {
+    pub async fn init() -> Self {
         dotenv().ok();
-        let uri = match env::var("MONGO_URI"){
-            Ok(v) => v.to_string(),
-            Err(_) => format!("Error while loading environment file!"),
-        };
+        let config = MongoConfig::load()
+            .unwrap_or_else(|error| panic!("Invalid MongoDB configuration: {}", error));
+        let policies = DeletePolicies::from_env()
+            .unwrap_or_else(|error| panic!("Invalid on-delete policy configuration: {}", error));
+
+        let db = MongoDB::connect(&config, policies)
+            .await
+            .unwrap_or_else(|error| panic!("Cannot start without MongoDB: {}", error));
+        if config.migrate_on_start {
+            db.migrate()
+                .await
+                .unwrap_or_else(|error| panic!("Cannot migrate the database: {}", error));
+        }
+        db
+    }
 
-        let client = Client::with_uri_str(uri).unwrap();
-        let db = client.database("praktikum");
-        MongoDB { db }
+    /// Connects and pings the server, so that an unreachable or misconfigured
+    /// database stops the launch instead of failing the first request.
+    pub async fn connect(config: &MongoConfig, policies: DeletePolicies) -> Result<Self, String> {
+        let options: ClientOptions = config
+            .client_options()
+            .await
+            .map_err(|error| format!("invalid connection string: {}", error))?;
+        let hosts: Vec<String> = options.hosts.iter().map(|host| host.to_string()).collect();
+        let client: Client = Client::with_options(options)
+            .map_err(|error| format!("invalid client options: {}", error))?;
+
+        let db: Database = client.database(&config.database);
+        db.run_command(doc! {"ping": 1}, None)
+            .await
+            .map_err(|error| {
+                format!(
+                    "no answer from {} for database '{}': {}",
+                    hosts.join(", "),
+                    config.database,
+                    error
+                )
+            })?;
+
+        Ok(MongoDB { db, policies })
+    }
+
+    /// Applies pending migrations, returning the ones that ran.
+    pub async fn migrate(&self) -> Result<Vec<&'static Migration>, String> {
+        migration::migrate(&self.db).await
     }
 
     fn column_helper<T>(data_source: &Self, collection_name: &str) -> Collection<T> {
         data_source.db.collection(collection_name)
     }
 
-    /*
-     * Employee Repository
-     */
-    pub fn delete_employee(&self, delete_entry: DeleteEmployee) -> Result<Employee, Error> {
-        let obj_id: ObjectId = ObjectId::parse_str(&delete_entry.id).unwrap();
-        let col: Collection<Employee> =  MongoDB::column_helper::<Employee>(&self, "employee");
-        let filter: Document  = doc! {"_id": obj_id};
-
-        let _data = col.delete_one(filter, None)
-            .ok()
-            .expect(&*format!("Error while deleting requested Employee with ID '{}'", obj_id));
-
-        Ok(Employee{id: None, first_name: String::from(""), last_name: String::from(""), status: None, stores: None, rank_id: None })
-    }
-
-    pub fn update_employee(&self, update_entry: UpdateEmployee) -> Result<Employee, Error> {
-        let obj_id: ObjectId = ObjectId::parse_str(&update_entry.id).unwrap();
-        let fetch_filter: Document  = doc! {"_id": obj_id};
-
-        let col: Collection<Employee> =  MongoDB::column_helper::<Employee>(&self, "employee");
-        let employee_result: Employee = col
-            .find_one(fetch_filter, None)
-            .ok()
-            .expect(&*format!("Error while fetching requested Employee with ID '{}'", obj_id))
-            .unwrap();
-
-        let validated_rank = if update_entry.rank_id == None {employee_result.rank_id.unwrap()} else {
-            let opt_rank = self.get_single_rank(&update_entry.rank_id.unwrap()).unwrap();
-            if opt_rank.is_some() {
-                opt_rank.unwrap().id.unwrap().to_string()
-            }else {
-                employee_result.rank_id.unwrap().clone()
-            }
+    fn parse_id(id: &str) -> Result<ObjectId, RepositoryError> {
+        ObjectId::parse_str(id).map_err(|_| RepositoryError::InvalidId(String::from(id)))
+    }
+
+    /// Entries marked deleted are left out.
+    async fn find_by_ids<T>(
+        &self,
+        collection_name: &str,
+        ids: &[String],
+    ) -> Result<Vec<T>, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let obj_ids: Vec<ObjectId> = ids
+            .iter()
+            .filter_map(|id| ObjectId::parse_str(id).ok())
+            .collect();
+        if obj_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
+        let filter: Document = doc! {"_id": {"$in": obj_ids}, "deleted_at": null};
+        let cursor: Cursor<T> = col.find(filter, None).await?;
+
+        let doc_vec: Vec<T> = cursor.try_collect().await?;
+
+        Ok(doc_vec)
+    }
+
+    fn sort_document(sort: Option<SortKey>, reverse: bool) -> Document {
+        let ascending: bool = sort.is_none_or(|key| key.ascending) != reverse;
+        let direction: i32 = if ascending { 1 } else { -1 };
+
+        match sort {
+            Some(key) => doc! {key.field: direction, "_id": direction},
+            None => doc! {"_id": direction},
+        }
+    }
+
+    async fn find_all<T>(
+        &self,
+        collection_name: &str,
+        filter: Document,
+        sort: Option<SortKey>,
+    ) -> Result<Vec<T>, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
+        let options: FindOptions = FindOptions::builder()
+            .sort(MongoDB::sort_document(sort, false))
+            .build();
+        let cursor: Cursor<T> = col.find(filter, options).await?;
+
+        let doc_vec: Vec<T> = cursor.try_collect().await?;
+
+        Ok(doc_vec)
+    }
+
+    /// Builds the condition selecting every document that comes after (or
+    /// before) the cursor document in the given sort order. Missing and null
+    /// values sort before every other value, but `$gt` and `$lt` never match
+    /// them, so they are selected explicitly.
+    async fn cursor_condition(
+        &self,
+        collection_name: &str,
+        cursor: &str,
+        sort: Option<SortKey>,
+        after: bool,
+    ) -> Result<Document, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(cursor)?;
+        let operator: &str = if sort.is_none_or(|key| key.ascending) == after {
+            "$gt"
+        } else {
+            "$lt"
+        };
+
+        let key: SortKey = match sort {
+            Some(key) => key,
+            None => return Ok(doc! {"_id": {operator: obj_id}}),
         };
 
-        let first_name: String = if update_entry.first_name == None {employee_result.first_name} else { update_entry.first_name.clone().unwrap() };
-        let last_name: String= if update_entry.last_name == None {employee_result.last_name} else { update_entry.last_name.clone().unwrap() };
-        let status: String = if update_entry.status == None {Status::None.to_string()} else { update_entry.status.unwrap().to_string() };
-        let validated_stores: Vec<String> = if update_entry.stores == None {vec![]} else { self.validate_store_vec(&update_entry.stores.unwrap()) };
+        let col: Collection<Document> = MongoDB::column_helper::<Document>(self, collection_name);
+        let cursor_doc: Document =
+            col.find_one(doc! {"_id": obj_id}, None)
+                .await?
+                .ok_or_else(|| {
+                    RepositoryError::invalid_field(
+                        if after { "after" } else { "before" },
+                        format!("cursor '{}' does not point to an existing entry", cursor),
+                    )
+                })?;
+        let value: Bson = cursor_doc.get(key.field).cloned().unwrap_or(Bson::Null);
+        let ascending: bool = operator == "$gt";
+
+        let conditions: Vec<Document> = match value {
+            Bson::Null if ascending => vec![
+                doc! {key.field: {"$ne": null}},
+                doc! {key.field: null, "_id": {operator: obj_id}},
+            ],
+            Bson::Null => vec![doc! {key.field: null, "_id": {operator: obj_id}}],
+            value if ascending => vec![
+                doc! {key.field: {operator: value.clone()}},
+                doc! {key.field: value, "_id": {operator: obj_id}},
+            ],
+            value => vec![
+                doc! {key.field: {operator: value.clone()}},
+                doc! {key.field: null},
+                doc! {key.field: value, "_id": {operator: obj_id}},
+            ],
+        };
+
+        Ok(doc! {"$or": conditions})
+    }
+
+    async fn find_page<T>(
+        &self,
+        collection_name: &str,
+        filter: Document,
+        sort: Option<SortKey>,
+        request: &PageRequest,
+    ) -> Result<Page<T>, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
 
-        let update_filter: Document  = doc! {"_id": obj_id};
-        let update: Document  = doc! {"$set": {"first_name": String::from(&first_name), "last_name": String::from(&last_name), "stores": &validated_stores, "rank_id": &validated_rank, "status": status}};
+        let mut conditions: Vec<Document> = vec![filter.clone()];
+        if let Some(after) = &request.after {
+            conditions.push(
+                self.cursor_condition(collection_name, after, sort, true)
+                    .await?,
+            );
+        }
+        if let Some(before) = &request.before {
+            conditions.push(
+                self.cursor_condition(collection_name, before, sort, false)
+                    .await?,
+            );
+        }
 
-        col.update_one(update_filter, update,None).ok().expect(&*format!("Error while updating requested Employee with ID '{}", obj_id));
+        let options: FindOptions = FindOptions::builder()
+            .sort(MongoDB::sort_document(sort, request.is_backward()))
+            .limit(request.size() as i64 + 1)
+            .build();
+        let cursor: Cursor<T> = col.find(all_of(conditions), options).await?;
+        let doc_vec: Vec<T> = cursor.try_collect().await?;
 
-        let updated_employee = self.get_single_employee(&update_entry.id);
+        let total_count: u64 = col.count_documents(filter, None).await?;
 
-        Ok(updated_employee.unwrap())
+        Ok(Page::from_overfetch(doc_vec, request, total_count))
     }
 
-    pub fn create_employee(&self, new_entry: CreateEmployee) -> Result<Employee, Error> {
-        let col: Collection<Employee> = MongoDB::column_helper::<Employee>(&self, "employee");
-        let validated_stores: Vec<String> = self.validate_store_vec(&new_entry.stores.unwrap());
-        let validated_rank: String =  self.validate_rank(&new_entry.rank_id);
+    async fn update_by_id<T>(
+        &self,
+        collection_name: &str,
+        entity: &'static str,
+        id: &str,
+        expected: Option<i32>,
+        set: Document,
+    ) -> Result<T, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
 
-        let mut new_doc = Employee{
-            id: None,
-            first_name: new_entry.first_name.clone(),
-            last_name: new_entry.last_name.clone(),
-            status: Option::from(if new_entry.status == None { Status::None } else { new_entry.status.unwrap() }),
-            stores: Option::from(validated_stores),
-            rank_id: Option::from(validated_rank),
+        let options: FindOneAndUpdateOptions = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match col
+            .find_one_and_update(
+                MongoDB::versioned(obj_id, expected),
+                UpdateModifications::Pipeline(MongoDB::next_version(set, Vec::new())),
+                options,
+            )
+            .await?
+        {
+            Some(doc) => Ok(doc),
+            None => Err(self
+                .update_missed(collection_name, entity, id, expected)
+                .await),
+        }
+    }
+
+    /// Filters a live entry, pinned to the expected version when one is given.
+    fn versioned(obj_id: ObjectId, expected: Option<i32>) -> Document {
+        let mut filter: Document = doc! {"_id": obj_id, "deleted_at": null};
+        if let Some(version) = expected {
+            filter.insert("version", MongoDB::version_condition(version));
+        }
+        filter
+    }
+
+    /// Sets and unsets the given fields and moves the entry to its next
+    /// version, counting an entry without a version as the first one like
+    /// `version_condition` does. Values are wrapped in `$literal` so a user
+    /// string starting with `$` is never read as a field path.
+    fn next_version(set: Document, unset: Vec<&str>) -> Vec<Document> {
+        let mut stage: Document = doc! {
+            "version": {"$add": [{"$ifNull": ["$version", FIRST_VERSION]}, 1]},
         };
+        for (field, value) in set {
+            stage.insert(field, doc! {"$literal": value});
+        }
+        let mut pipeline: Vec<Document> = vec![doc! {"$set": stage}];
+        if !unset.is_empty() {
+            pipeline.push(doc! {"$unset": unset});
+        }
 
-        let data: InsertOneResult = col.insert_one(&new_doc, None)
-            .ok()
-            .expect("Error while creating new employee.");
-        new_doc.id =  data.inserted_id.as_object_id();
+        pipeline
+    }
 
-        Ok(new_doc)
+    /// Matches entries at the given version. Entries without one read as the
+    /// first version, so they match it too, whether or not the migration
+    /// that sets it has run yet.
+    fn version_condition(version: i32) -> Bson {
+        match version {
+            FIRST_VERSION => Bson::Document(doc! {"$in": [FIRST_VERSION, Bson::Null]}),
+            _ => Bson::Int32(version),
+        }
     }
 
-    pub fn get_all_employees(&self) -> Result<Vec<Employee>, Error> {
-        let col: Collection<Employee> = MongoDB::column_helper(&self, "employee");
-        let cursor: Cursor<Employee>= col.find(None, None)
-            .ok()
-            .expect("Error while fetching list of employees.");
+    /// Explains why a versioned update matched nothing: either the entry is
+    /// gone or it has moved past the expected version.
+    async fn update_missed(
+        &self,
+        collection_name: &str,
+        entity: &'static str,
+        id: &str,
+        expected: Option<i32>,
+    ) -> RepositoryError {
+        let col: Collection<Document> = MongoDB::column_helper::<Document>(self, collection_name);
+        let current: Option<Document> = match MongoDB::parse_id(id) {
+            Ok(obj_id) => match col
+                .find_one(doc! {"_id": obj_id, "deleted_at": null}, None)
+                .await
+            {
+                Ok(current) => current,
+                Err(error) => return error.into(),
+            },
+            Err(error) => return error,
+        };
 
-        let employee_vec: Vec<Employee> = cursor
-            .map(|doc| doc.unwrap())
-            .collect();
+        match (current, expected) {
+            (Some(current), Some(expected)) => RepositoryError::Conflict {
+                entity,
+                id: id.to_string(),
+                expected,
+                actual: current.get_i32("version").unwrap_or(FIRST_VERSION),
+            },
+            _ => RepositoryError::not_found(entity, id),
+        }
+    }
 
-        Ok(employee_vec)
+    async fn delete_by_id<T>(
+        &self,
+        collection_name: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<T, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
+        let filter: Document = doc! {"_id": obj_id};
+
+        col.find_one_and_delete(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found(entity, id))
     }
 
-    pub fn get_single_employee(&self, id: &String) -> Result<Employee, Error> {
-        let obj_id: ObjectId = ObjectId::parse_str(id).unwrap();
-        let filter: Document  = doc! {"_id": obj_id};
-        let col: Collection<Employee> =  MongoDB::column_helper::<Employee>(&self, "employee");
+    /// Marks a live entry deleted.
+    async fn mark_deleted<T>(
+        &self,
+        collection_name: &str,
+        entity: &'static str,
+        deletion: &Deletion,
+    ) -> Result<T, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let obj_id: ObjectId = MongoDB::parse_id(&deletion.id)?;
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
+        let options: FindOneAndUpdateOptions = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        col.find_one_and_update(
+            doc! {"_id": obj_id, "deleted_at": null},
+            MongoDB::deletion_update(deletion),
+            options,
+        )
+        .await?
+        .ok_or_else(|| RepositoryError::not_found(entity, &deletion.id))
+    }
+
+    fn deletion_update(deletion: &Deletion) -> Document {
+        doc! {"$set": {"deleted_at": deletion.deleted_at, "deleted_by": deletion.deleted_by.clone()}}
+    }
 
-        let opt_employee: Option<Employee> = col
-            .find_one(filter, None)
-            .ok()
-            .expect(&*format!("Error while fetching requested employee with ID '{}'", obj_id));
+    async fn find_deleted<T>(
+        &self,
+        collection_name: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<T, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
 
-        Ok(opt_employee.unwrap())
+        col.find_one(doc! {"_id": obj_id, "deleted_at": {"$ne": null}}, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    async fn restore_by_id<T>(
+        &self,
+        collection_name: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<T, RepositoryError>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let col: Collection<T> = MongoDB::column_helper::<T>(self, collection_name);
+        let options: FindOneAndUpdateOptions = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        col.find_one_and_update(
+            doc! {"_id": obj_id, "deleted_at": {"$ne": null}},
+            doc! {"$unset": {"deleted_at": "", "deleted_by": ""}},
+            options,
+        )
+        .await?
+        .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    async fn dependent_ids(
+        &self,
+        collection_name: &str,
+        filter: Document,
+    ) -> Result<Vec<String>, RepositoryError> {
+        let col: Collection<Document> = MongoDB::column_helper::<Document>(self, collection_name);
+        let options: FindOptions = FindOptions::builder().projection(doc! {"_id": 1}).build();
+        let cursor: Cursor<Document> = col.find(filter, options).await?;
+
+        let doc_vec: Vec<Document> = cursor.try_collect().await?;
+
+        Ok(doc_vec
+            .iter()
+            .filter_map(|dependent| dependent.get_object_id("_id").ok())
+            .map(|id| id.to_string())
+            .collect())
+    }
+
+    /// Refuses to delete stores live employees are assigned to under
+    /// `Restrict`. The other policies leave the assignments for the purge.
+    async fn check_assignments(&self, store_ids: &[String]) -> Result<(), RepositoryError> {
+        if self.policies.employee_store == OnDelete::Restrict {
+            let dependents: Vec<String> = self
+                .dependent_ids(
+                    "employee",
+                    doc! {"stores": {"$in": store_ids}, "deleted_at": null},
+                )
+                .await?;
+            if !dependents.is_empty() {
+                return Err(RepositoryError::HasDependents {
+                    entity: "store",
+                    id: store_ids.join(", "),
+                    dependent_entity: "employee",
+                    dependents,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Narrows a shift or absence filter to the entries none of whose owners
+    /// are marked deleted. Shifts and absences stay with a deleted employee
+    /// or store until it is purged, hidden like it.
+    fn with_live_owners(filter: Document) -> Document {
+        all_of(vec![filter, doc! {"deleted_owners.0": {"$exists": false}}])
+    }
+
+    /// Keeps the `deleted_owners` of the shifts and absences owned through
+    /// `field` by one of the given employees or stores in step with their
+    /// owners: adds the IDs when the owners are deleted and takes them out
+    /// again when they are restored.
+    async fn mark_owned(
+        &self,
+        field: &str,
+        owner_ids: &[String],
+        deleted: bool,
+    ) -> Result<(), RepositoryError> {
+        let update: Document = match deleted {
+            true => doc! {"$addToSet": {"deleted_owners": {"$each": owner_ids}}},
+            false => doc! {"$pull": {"deleted_owners": {"$in": owner_ids}}},
+        };
+        let collection_names: &[&str] = match field {
+            "employee_id" => &["shift", "absence"],
+            _ => &["shift"],
+        };
+        for collection_name in collection_names {
+            MongoDB::column_helper::<Document>(self, collection_name)
+                .update_many(doc! {field: {"$in": owner_ids}}, update.clone(), None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// The employee filter, with the absences approved for today looked up
+    /// first when it goes by status.
+    async fn employee_conditions(
+        &self,
+        filter: &EmployeeFilter,
+    ) -> Result<Document, RepositoryError> {
+        let absent: HashMap<String, Status> = match filter.status_in {
+            Some(_) => {
+                let day: String = today().to_string();
+                absent_statuses(
+                    &self
+                        .find_absences(doc! {
+                            "state": AbsenceState::Approved.as_str(),
+                            "starts_on": {"$lte": &day},
+                            "ends_on": {"$gte": &day},
+                        })
+                        .await?,
+                )
+            }
+            None => HashMap::new(),
+        };
+
+        Ok(employee_filter(filter, &absent))
+    }
+
+    /// Deletes the shifts of the given employees or stores once those are
+    /// purged, whatever the delete policies say.
+    async fn drop_shifts(&self, field: &str, ids: &[String]) -> Result<(), RepositoryError> {
+        let col: Collection<Shift> = MongoDB::column_helper::<Shift>(self, "shift");
+        col.delete_many(doc! {field: {"$in": ids}}, None).await?;
+
+        Ok(())
+    }
+
+    /// Deletes the absences of the given employees once those are purged.
+    async fn drop_absences(&self, employee_ids: &[String]) -> Result<(), RepositoryError> {
+        let col: Collection<Absence> = MongoDB::column_helper::<Absence>(self, "absence");
+        col.delete_many(doc! {"employee_id": {"$in": employee_ids}}, None)
+            .await?;
+
+        Ok(())
     }
 
     /*
-     * Store Repository
+     * Change Streams
      */
-    pub fn create_store(&self, new_entry: CreateStore) -> Result<Store, Error> {
-        let col: Collection<Store> = MongoDB::column_helper::<Store>(&self, "store");
-        let validated_location = self.validate_location(&new_entry.location_id);
-
-        let mut new_doc = Store{
-            id: None,
-            name: new_entry.name.clone(),
-            location_id: String::from("")
+    /// Opens a change stream on a collection, resuming after `resume_after`
+    /// when given. Updates carry the document as it is now; updates, replaces
+    /// and deletes carry its pre-image where the collection records them
+    /// (`changeStreamPreAndPostImages`, MongoDB 6.0 and later).
+    pub async fn watch<T>(
+        &self,
+        collection_name: &str,
+        resume_after: Option<ResumeToken>,
+    ) -> mongodb::error::Result<ChangeStream<ChangeStreamEvent<T>>>
+    where
+        T: DeserializeOwned + Unpin + Send + Sync,
+    {
+        let options: ChangeStreamOptions = ChangeStreamOptions::builder()
+            .full_document(Some(FullDocumentType::UpdateLookup))
+            .full_document_before_change(Some(FullDocumentBeforeChangeType::WhenAvailable))
+            .resume_after(resume_after)
+            .build();
+
+        MongoDB::column_helper::<T>(self, collection_name)
+            .watch(None, options)
+            .await
+    }
+
+    /// Resume tokens are stored per watcher, so replicas sharing a database
+    /// each resume from their own position.
+    fn resume_token_id(instance: &str, collection_name: &str) -> Document {
+        doc! {"instance": instance, "collection": collection_name}
+    }
+
+    /// The stored resume token of a watcher. A token that no longer decodes
+    /// is logged and dropped, and the watcher starts from the current time.
+    pub async fn load_resume_token(
+        &self,
+        instance: &str,
+        collection_name: &str,
+    ) -> mongodb::error::Result<Option<ResumeToken>> {
+        let col: Collection<Document> =
+            MongoDB::column_helper::<Document>(self, RESUME_TOKEN_COLLECTION);
+        let id: Document = MongoDB::resume_token_id(instance, collection_name);
+        let stored: Option<Document> = col.find_one(doc! {"_id": id}, None).await?;
+
+        let token: Bson = match stored.and_then(|stored| stored.get("token").cloned()) {
+            Some(token) => token,
+            None => return Ok(None),
         };
+        match from_bson(token) {
+            Ok(token) => Ok(Some(token)),
+            Err(error) => {
+                log::warn!(
+                    "Dropping the unreadable resume token of '{}' for instance '{}': {}",
+                    collection_name,
+                    instance,
+                    error
+                );
+                self.clear_resume_token(instance, collection_name).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    pub async fn save_resume_token(
+        &self,
+        instance: &str,
+        collection_name: &str,
+        token: &ResumeToken,
+    ) -> mongodb::error::Result<()> {
+        let col: Collection<Document> =
+            MongoDB::column_helper::<Document>(self, RESUME_TOKEN_COLLECTION);
+        let id: Document = MongoDB::resume_token_id(instance, collection_name);
+        let token: Bson = to_bson(token)?;
+        let options: UpdateOptions = UpdateOptions::builder().upsert(true).build();
+        col.update_one(doc! {"_id": id}, doc! {"$set": {"token": token}}, options)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_resume_token(
+        &self,
+        instance: &str,
+        collection_name: &str,
+    ) -> mongodb::error::Result<()> {
+        let col: Collection<Document> =
+            MongoDB::column_helper::<Document>(self, RESUME_TOKEN_COLLECTION);
+        let id: Document = MongoDB::resume_token_id(instance, collection_name);
+        col.delete_one(doc! {"_id": id}, None).await?;
 
-        let data: InsertOneResult = col.insert_one(&new_doc, None)
-            .ok()
-            .expect("Error while creating new store.");
-        new_doc.id =  data.inserted_id.as_object_id();
+        Ok(())
+    }
+}
+
+/*
+ * Employee Repository
+ */
+#[async_trait]
+impl EmployeeRepository for MongoDB {
+    async fn create_employee(
+        &self,
+        new_entry: CreateEmployee,
+        mode: ValidationMode,
+    ) -> Result<Employee, RepositoryError> {
+        let col: Collection<Employee> = MongoDB::column_helper::<Employee>(self, "employee");
+        let mut new_doc: Employee = new_employee(self, new_entry, mode).await?;
+
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
 
         Ok(new_doc)
     }
 
-    pub fn get_all_stores(&self) -> Result<Vec<Store>, Error> {
-        let col: Collection<Store> = MongoDB::column_helper(&self, "store");
-        let cursor: Cursor<Store>= col.find(None, None)
-            .ok()
-            .expect("Error while fetching list of stores.");
+    async fn update_employee(
+        &self,
+        update_entry: UpdateEmployee,
+        mode: ValidationMode,
+    ) -> Result<(Employee, Employee), RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(&update_entry.id)?;
+        let id: String = update_entry.id.clone();
+        let col: Collection<Employee> = MongoDB::column_helper::<Employee>(self, "employee");
+        let patch: EmployeePatch = employee_patch(self, update_entry, mode).await?;
+        let expected: Option<i32> = patch.version;
+        // The write returns the document as it found it; the pipeline below
+        // does to it what `EmployeePatch::apply` does, which gives the result.
+        let applied: EmployeePatch = patch.clone();
 
-        let store_vec: Vec<Store> = cursor
-            .map(|doc| {
-                doc.unwrap()
-            })
-            .collect();
+        let mut set: Document = Document::new();
+        let mut unset: Vec<&str> = Vec::new();
+        if let Some(first_name) = patch.first_name {
+            set.insert("first_name", first_name);
+        }
+        if let Some(last_name) = patch.last_name {
+            set.insert("last_name", last_name);
+        }
+        match patch.status {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => unset.push("status"),
+            MaybeUndefined::Value(status) => {
+                set.insert("status", status.as_str());
+            }
+        }
+        match patch.rank_id {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => unset.push("rank_id"),
+            MaybeUndefined::Value(rank_id) => {
+                set.insert("rank_id", rank_id);
+            }
+        }
+        match patch.stores {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => unset.push("stores"),
+            MaybeUndefined::Value(stores) => {
+                set.insert("stores", stores);
+            }
+        }
+
+        let mut pipeline: Vec<Document> = MongoDB::next_version(set, unset);
+        // Store edits run as a pipeline stage of the same write: removals
+        // first, then additions for stores not assigned yet, so the whole
+        // update matches or misses the guard filter as one.
+        if !patch.add_stores.is_empty() || !patch.remove_stores.is_empty() {
+            let mut add_stores: Vec<String> = Vec::new();
+            for store_id in patch.add_stores {
+                if !add_stores.contains(&store_id) {
+                    add_stores.push(store_id);
+                }
+            }
+            pipeline.push(doc! {"$set": {"stores": {"$let": {
+                "vars": {"kept": {"$filter": {
+                    "input": {"$ifNull": ["$stores", []]},
+                    "cond": {"$not": [{"$in": ["$$this", {"$literal": patch.remove_stores}]}]},
+                }}},
+                "in": {"$concatArrays": ["$$kept", {"$filter": {
+                    "input": {"$literal": add_stores},
+                    "cond": {"$not": [{"$in": ["$$this", "$$kept"]}]},
+                }}]},
+            }}}});
+        }
+
+        let options: FindOneAndUpdateOptions = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+        match col
+            .find_one_and_update(
+                MongoDB::versioned(obj_id, expected),
+                UpdateModifications::Pipeline(pipeline),
+                options,
+            )
+            .await?
+        {
+            Some(previous) => {
+                let mut employee: Employee = previous.clone();
+                applied.apply(&mut employee);
+
+                Ok((previous, employee))
+            }
+            None => Err(self
+                .update_missed("employee", "employee", &id, expected)
+                .await),
+        }
+    }
+
+    async fn delete_employee(&self, deletion: Deletion) -> Result<Employee, RepositoryError> {
+        let deleted: Employee = self.mark_deleted("employee", "employee", &deletion).await?;
+        self.mark_owned("employee_id", std::slice::from_ref(&deletion.id), true)
+            .await?;
+
+        Ok(deleted)
+    }
+
+    async fn restore_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        let deleted: Employee = self.get_deleted_employee(id).await?;
+        validate_restored_employee(self, &self.policies, &deleted).await?;
+
+        let restored: Employee = self
+            .restore_by_id("employee", "deleted employee", id)
+            .await?;
+        self.mark_owned("employee_id", &[String::from(id)], false)
+            .await?;
+
+        Ok(restored)
+    }
+
+    async fn get_all_employees(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        self.find_all(
+            "employee",
+            self.employee_conditions(filter).await?,
+            employee_sort(order_by),
+        )
+        .await
+    }
+
+    async fn get_employees_by_stores(
+        &self,
+        store_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let col: Collection<Employee> = MongoDB::column_helper(self, "employee");
+        let filter: Document = doc! {"stores": {"$in": store_ids}, "deleted_at": null};
+        let cursor: Cursor<Employee> = col.find(filter, None).await?;
+
+        let employee_vec: Vec<Employee> = cursor.try_collect().await?;
+
+        Ok(employee_vec)
+    }
+
+    async fn get_employees_by_ranks(
+        &self,
+        rank_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let col: Collection<Employee> = MongoDB::column_helper(self, "employee");
+        let filter: Document = doc! {"rank_id": {"$in": rank_ids}, "deleted_at": null};
+        let cursor: Cursor<Employee> = col.find(filter, None).await?;
+
+        let employee_vec: Vec<Employee> = cursor.try_collect().await?;
+
+        Ok(employee_vec)
+    }
+
+    async fn get_employees_by_ids(&self, ids: &[String]) -> Result<Vec<Employee>, RepositoryError> {
+        self.find_by_ids("employee", ids).await
+    }
+
+    async fn get_employee_page(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Employee>, RepositoryError> {
+        self.find_page(
+            "employee",
+            self.employee_conditions(filter).await?,
+            employee_sort(order_by),
+            request,
+        )
+        .await
+    }
+
+    async fn get_single_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let filter: Document = doc! {"_id": obj_id, "deleted_at": null};
+        let col: Collection<Employee> = MongoDB::column_helper::<Employee>(self, "employee");
+
+        col.find_one(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("employee", id))
+    }
+
+    async fn get_deleted_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        self.find_deleted("employee", "deleted employee", id).await
+    }
+}
+
+/*
+ * Store Repository
+ */
+#[async_trait]
+impl StoreRepository for MongoDB {
+    async fn create_store(
+        &self,
+        new_entry: CreateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError> {
+        let col: Collection<Store> = MongoDB::column_helper::<Store>(self, "store");
+        let mut new_doc: Store = new_store(self, new_entry, mode).await?;
+
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
+
+        Ok(new_doc)
+    }
+
+    async fn update_store(
+        &self,
+        update_entry: UpdateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: StorePatch = store_patch(self, update_entry, mode).await?;
+
+        let mut set: Document = Document::new();
+        if let Some(name) = patch.name {
+            set.insert("name", name);
+        }
+        if let Some(location_id) = patch.location_id {
+            set.insert("location_id", location_id);
+        }
+
+        self.update_by_id("store", "store", &id, patch.version, set)
+            .await
+    }
+
+    async fn delete_store(&self, deletion: Deletion) -> Result<Store, RepositoryError> {
+        self.get_single_store(&deletion.id).await?;
+        self.check_assignments(std::slice::from_ref(&deletion.id))
+            .await?;
+        let deleted: Store = self.mark_deleted("store", "store", &deletion).await?;
+        self.mark_owned("store_id", std::slice::from_ref(&deletion.id), true)
+            .await?;
+
+        Ok(deleted)
+    }
+
+    async fn restore_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        let deleted: Store = self.get_deleted_store(id).await?;
+        validate_restored_store(self, &self.policies, &deleted).await?;
+
+        let restored: Store = self.restore_by_id("store", "deleted store", id).await?;
+        self.mark_owned("store_id", &[String::from(id)], false)
+            .await?;
+
+        Ok(restored)
+    }
+
+    async fn get_all_stores(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+    ) -> Result<Vec<Store>, RepositoryError> {
+        self.find_all("store", store_filter(filter), store_sort(order_by))
+            .await
+    }
+
+    async fn get_stores_by_locations(
+        &self,
+        location_ids: &[String],
+    ) -> Result<Vec<Store>, RepositoryError> {
+        let col: Collection<Store> = MongoDB::column_helper(self, "store");
+        let filter: Document = doc! {"location_id": {"$in": location_ids}, "deleted_at": null};
+        let cursor: Cursor<Store> = col.find(filter, None).await?;
+
+        let store_vec: Vec<Store> = cursor.try_collect().await?;
 
         Ok(store_vec)
     }
 
-    pub fn get_single_store(&self, id: &String) -> Result<Option<Store>, Error> {
-        let obj_id: ObjectId = ObjectId::parse_str(id).expect("Ungültige ID");
-        let filter: Document  = doc! {"_id": obj_id};
-        let col: Collection<Store> =  MongoDB::column_helper::<Store>(&self, "store");
+    async fn get_stores_by_ids(&self, ids: &[String]) -> Result<Vec<Store>, RepositoryError> {
+        self.find_by_ids("store", ids).await
+    }
 
-        let opt_store: Option<Store> = col
-            .find_one(filter, None)
-            .ok()
-            .expect(&*format!("Error while fetching requested store with ID '{}'", obj_id));
+    async fn get_store_page(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Store>, RepositoryError> {
+        self.find_page("store", store_filter(filter), store_sort(order_by), request)
+            .await
+    }
+
+    async fn get_single_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let filter: Document = doc! {"_id": obj_id, "deleted_at": null};
+        let col: Collection<Store> = MongoDB::column_helper::<Store>(self, "store");
 
-        Ok(opt_store)
+        col.find_one(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("store", id))
     }
 
-    pub fn validate_store_vec(&self, store_vec: &Vec<String>) -> Vec<String> {
-        let mut valid_store_vec: Vec<String> = Vec::new();
+    async fn get_deleted_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        self.find_deleted("store", "deleted store", id).await
+    }
+}
+
+/*
+ * Location Repository
+ */
+#[async_trait]
+impl LocationRepository for MongoDB {
+    async fn create_location(
+        &self,
+        new_entry: CreateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError> {
+        let col: Collection<Location> = MongoDB::column_helper::<Location>(self, "location");
+        let mut new_doc: Location = new_location(new_entry, mode)?;
 
-        for store_id in store_vec.clone().iter(){
-            let fetched_store: Option<Store> = self.get_single_store(&store_id).unwrap();
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
 
-            if fetched_store.is_some() {
-                valid_store_vec.push(String::from(store_id));
+        Ok(new_doc)
+    }
+
+    async fn update_location(
+        &self,
+        update_entry: UpdateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: LocationPatch = location_patch(update_entry, mode)?;
+
+        let mut set: Document = Document::new();
+        if let Some(country) = patch.country {
+            set.insert("country", country);
+        }
+        if let Some(state) = patch.state {
+            set.insert("state", state);
+        }
+
+        self.update_by_id("location", "location", &id, patch.version, set)
+            .await
+    }
+
+    async fn delete_location(&self, deletion: Deletion) -> Result<Location, RepositoryError> {
+        self.get_single_location(&deletion.id).await?;
+        let col: Collection<Store> = MongoDB::column_helper::<Store>(self, "store");
+        let live: Document = doc! {"location_id": &deletion.id, "deleted_at": null};
+
+        match self.policies.store_location {
+            OnDelete::Restrict => {
+                let dependents: Vec<String> = self.dependent_ids("store", live).await?;
+                if !dependents.is_empty() {
+                    return Err(RepositoryError::HasDependents {
+                        entity: "location",
+                        id: deletion.id,
+                        dependent_entity: "store",
+                        dependents,
+                    });
+                }
             }
+            OnDelete::Cascade => {
+                let store_ids: Vec<String> = self.dependent_ids("store", live.clone()).await?;
+                if !store_ids.is_empty() {
+                    self.check_assignments(&store_ids).await?;
+                    col.update_many(live, MongoDB::deletion_update(&deletion), None)
+                        .await?;
+                    self.mark_owned("store_id", &store_ids, true).await?;
+                }
+            }
+            OnDelete::Nullify => {}
         }
 
-        valid_store_vec
+        self.mark_deleted("location", "location", &deletion).await
     }
 
-    /*
-     * Location Repository
-     */
-    pub fn create_location(&self, new_entry: CreateLocation) -> Result<Location, Error> {
-        let col: Collection<Location> = MongoDB::column_helper::<Location>(&self, "location");
-        let mut new_doc = Location{
-            id: None,
-            country: new_entry.country.clone(),
-            state: new_entry.state.clone()
+    /// Stores deleted along with the location, carrying the same stamp, come
+    /// back with it.
+    async fn restore_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        let deleted: Location = self.get_deleted_location(id).await?;
+        let col: Collection<Store> = MongoDB::column_helper::<Store>(self, "store");
+        let cascaded: Document = doc! {
+            "location_id": id,
+            "deleted_at": deleted.deleted_at,
+            "deleted_by": deleted.deleted_by.clone(),
         };
+        let stores: Vec<Store> = col
+            .find(cascaded.clone(), None)
+            .await?
+            .try_collect()
+            .await?;
+        if !stores.is_empty() {
+            let names: Vec<&str> = stores.iter().map(|store| store.name.as_str()).collect();
+            if let Some(taken) = col
+                .find_one(doc! {"name": {"$in": &names}, "deleted_at": null}, None)
+                .await?
+            {
+                return Err(RepositoryError::Duplicate(format!(
+                    "a store named '{}' already exists",
+                    taken.name
+                )));
+            }
+            col.update_many(
+                cascaded,
+                doc! {"$unset": {"deleted_at": "", "deleted_by": ""}},
+                None,
+            )
+            .await?;
+            let store_ids: Vec<String> = stores
+                .iter()
+                .filter_map(|store| store.id.map(|id| id.to_string()))
+                .collect();
+            self.mark_owned("store_id", &store_ids, false).await?;
+        }
+
+        self.restore_by_id("location", "deleted location", id).await
+    }
+
+    async fn get_all_locations(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+    ) -> Result<Vec<Location>, RepositoryError> {
+        self.find_all("location", location_filter(filter), location_sort(order_by))
+            .await
+    }
+
+    async fn get_locations_by_ids(&self, ids: &[String]) -> Result<Vec<Location>, RepositoryError> {
+        self.find_by_ids("location", ids).await
+    }
+
+    async fn get_location_page(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Location>, RepositoryError> {
+        self.find_page(
+            "location",
+            location_filter(filter),
+            location_sort(order_by),
+            request,
+        )
+        .await
+    }
+
+    async fn get_single_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let filter: Document = doc! {"_id": obj_id, "deleted_at": null};
+        let col: Collection<Location> = MongoDB::column_helper::<Location>(self, "location");
 
-        let data: InsertOneResult = col.insert_one(&new_doc, None)
-            .ok()
-            .expect("Error while creating new location.");
-        new_doc.id =  data.inserted_id.as_object_id();
+        col.find_one(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("location", id))
+    }
+
+    async fn get_deleted_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        self.find_deleted("location", "deleted location", id).await
+    }
+}
+
+/*
+ * Rank Repository
+ */
+#[async_trait]
+impl RankRepository for MongoDB {
+    async fn create_rank(
+        &self,
+        new_entry: CreateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError> {
+        let col: Collection<Rank> = MongoDB::column_helper::<Rank>(self, "rank");
+        let mut new_doc: Rank = new_rank(new_entry, mode)?;
+
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
 
         Ok(new_doc)
     }
 
-    pub fn get_all_locations(&self) -> Result<Vec<Location>, Error> {
-        let col: Collection<Location> = MongoDB::column_helper(&self, "location");
-        let cursor: Cursor<Location>= col.find(None, None)
-            .ok()
-            .expect("Error while fetching list of stores.");
+    async fn update_rank(
+        &self,
+        update_entry: UpdateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: RankPatch = rank_patch(update_entry, mode)?;
 
-        let location_vec: Vec<Location> = cursor
-            .map(|doc| {
-                doc.unwrap()
-            })
-            .collect();
+        let mut set: Document = Document::new();
+        if let Some(name) = patch.name {
+            set.insert("name", name);
+        }
+        if let Some(description) = patch.description {
+            set.insert("description", description);
+        }
+        if let Some(permissions) = patch.permissions {
+            set.insert(
+                "permissions",
+                to_bson(&permissions)
+                    .map_err(|error| RepositoryError::Backend(error.to_string()))?,
+            );
+        }
 
-        Ok(location_vec)
+        self.update_by_id("rank", "rank", &id, patch.version, set)
+            .await
     }
 
-    pub fn get_single_location(&self, id: &String) -> Result<Option<Location>, Error> {
-        let obj_id: ObjectId = ObjectId::parse_str(id).expect("Ungültige ID");
-        let filter: Document  = doc! {"_id": obj_id};
-        let col: Collection<Location> =  MongoDB::column_helper::<Location>(&self, "location");
+    async fn delete_rank(&self, deletion: Deletion) -> Result<Rank, RepositoryError> {
+        self.get_single_rank(&deletion.id).await?;
+        if self.policies.employee_rank == OnDelete::Restrict {
+            let live: Document = doc! {"rank_id": &deletion.id, "deleted_at": null};
+            let dependents: Vec<String> = self.dependent_ids("employee", live).await?;
+            if !dependents.is_empty() {
+                return Err(RepositoryError::HasDependents {
+                    entity: "rank",
+                    id: deletion.id,
+                    dependent_entity: "employee",
+                    dependents,
+                });
+            }
+        }
+
+        self.mark_deleted("rank", "rank", &deletion).await
+    }
+
+    async fn restore_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        self.restore_by_id("rank", "deleted rank", id).await
+    }
 
-        let opt_location: Option<Location> = col
-            .find_one(filter, None)
-            .ok()
-            .expect(&*format!("Error while fetching requested location with ID '{}'", obj_id));
+    async fn get_all_ranks(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+    ) -> Result<Vec<Rank>, RepositoryError> {
+        self.find_all("rank", rank_filter(filter), rank_sort(order_by))
+            .await
+    }
 
-        Ok(opt_location)
+    async fn get_ranks_by_ids(&self, ids: &[String]) -> Result<Vec<Rank>, RepositoryError> {
+        self.find_by_ids("rank", ids).await
     }
 
-    pub fn validate_location(&self, location_id: &String) -> Option<Location> {
-        let mut valid_location_id: String = location_id.clone();
+    async fn get_rank_page(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Rank>, RepositoryError> {
+        self.find_page("rank", rank_filter(filter), rank_sort(order_by), request)
+            .await
+    }
 
-        let fetched_location: Option<Location> = self.get_single_location(location_id).unwrap();
-        //if fetched_location.is_none() { valid_location_id = String::from("")}
+    async fn get_single_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let filter: Document = doc! {"_id": obj_id, "deleted_at": null};
+        let col: Collection<Rank> = MongoDB::column_helper::<Rank>(self, "rank");
 
-        fetched_location
-        //valid_location_id
+        col.find_one(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("rank", id))
     }
 
-    /*
-     * Rank Repository
-     */
-    pub fn create_rank(&self, new_entry: CreateRank) -> Result<Rank, Error> {
-        let col: Collection<Rank> = MongoDB::column_helper::<Rank>(&self, "rank");
-        let mut new_doc = Rank{
-            id: None,
-            name: new_entry.name,
-            description: new_entry.description
+    async fn get_deleted_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        self.find_deleted("rank", "deleted rank", id).await
+    }
+}
+
+/*
+ * Shift Repository
+ */
+impl MongoDB {
+    async fn find_shifts(
+        &self,
+        field: &str,
+        ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        let mut filter: Document = doc! {field: {"$in": ids}};
+        if let Some(from) = range.from {
+            filter.insert("ends_at", doc! {"$gt": from});
+        }
+        if let Some(to) = range.to {
+            filter.insert("starts_at", doc! {"$lt": to});
+        }
+        let options: FindOptions = FindOptions::builder()
+            .sort(doc! {"starts_at": 1, "_id": 1})
+            .build();
+        let cursor: Cursor<Shift> = MongoDB::column_helper::<Shift>(self, "shift")
+            .find(MongoDB::with_live_owners(filter), options)
+            .await?;
+
+        let shift_vec: Vec<Shift> = cursor.try_collect().await?;
+
+        Ok(shift_vec)
+    }
+}
+
+#[async_trait]
+impl ShiftRepository for MongoDB {
+    async fn create_shift(&self, new_entry: CreateShift) -> Result<Shift, RepositoryError> {
+        let col: Collection<Shift> = MongoDB::column_helper::<Shift>(self, "shift");
+        let mut new_doc: Shift = new_shift(self, new_entry).await?;
+
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
+
+        Ok(new_doc)
+    }
+
+    async fn update_shift(&self, update_entry: UpdateShift) -> Result<Shift, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(&update_entry.id)?;
+        let id: String = update_entry.id.clone();
+        let col: Collection<Shift> = MongoDB::column_helper::<Shift>(self, "shift");
+        let patch: ShiftPatch = shift_patch(self, update_entry).await?;
+
+        let mut set: Document = Document::new();
+        let mut unset: Vec<&str> = Vec::new();
+        if let Some(employee_id) = patch.employee_id {
+            set.insert("employee_id", employee_id);
+        }
+        if let Some(store_id) = patch.store_id {
+            set.insert("store_id", store_id);
+        }
+        if let Some(starts_at) = patch.starts_at {
+            set.insert("starts_at", starts_at);
+        }
+        if let Some(ends_at) = patch.ends_at {
+            set.insert("ends_at", ends_at);
+        }
+        match patch.required_rank_id {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => unset.push("required_rank_id"),
+            MaybeUndefined::Value(rank_id) => {
+                set.insert("required_rank_id", rank_id);
+            }
+        }
+
+        if col
+            .update_one(
+                MongoDB::versioned(obj_id, patch.version),
+                UpdateModifications::Pipeline(MongoDB::next_version(set, unset)),
+                None,
+            )
+            .await?
+            .matched_count
+            == 0
+        {
+            return Err(self
+                .update_missed("shift", "shift", &id, patch.version)
+                .await);
+        }
+
+        self.get_single_shift(&id).await
+    }
+
+    async fn delete_shift(&self, delete_entry: DeleteShift) -> Result<Shift, RepositoryError> {
+        self.delete_by_id("shift", "shift", &delete_entry.id).await
+    }
+
+    async fn get_shifts_by_store(
+        &self,
+        store_id: &str,
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        self.find_shifts("store_id", &[String::from(store_id)], range)
+            .await
+    }
+
+    async fn get_shifts_by_employees(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        self.find_shifts("employee_id", employee_ids, range).await
+    }
+
+    async fn get_single_shift(&self, id: &str) -> Result<Shift, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let col: Collection<Shift> = MongoDB::column_helper::<Shift>(self, "shift");
+        let filter: Document = MongoDB::with_live_owners(doc! {"_id": obj_id});
+
+        col.find_one(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("shift", id))
+    }
+}
+
+/*
+ * Absence Repository
+ */
+impl MongoDB {
+    async fn find_absences(&self, filter: Document) -> Result<Vec<Absence>, RepositoryError> {
+        let options: FindOptions = FindOptions::builder()
+            .sort(doc! {"starts_on": 1, "_id": 1})
+            .build();
+        let cursor: Cursor<Absence> = MongoDB::column_helper::<Absence>(self, "absence")
+            .find(MongoDB::with_live_owners(filter), options)
+            .await?;
+
+        let absence_vec: Vec<Absence> = cursor.try_collect().await?;
+
+        Ok(absence_vec)
+    }
+}
+
+#[async_trait]
+impl AbsenceRepository for MongoDB {
+    async fn request_absence(&self, new_entry: RequestAbsence) -> Result<Absence, RepositoryError> {
+        let col: Collection<Absence> = MongoDB::column_helper::<Absence>(self, "absence");
+        let mut new_doc: Absence = new_absence(self, new_entry).await?;
+
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
+
+        Ok(new_doc)
+    }
+
+    async fn decide_absence(&self, decision: AbsenceDecision) -> Result<Absence, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(&decision.id)?;
+        let col: Collection<Absence> = MongoDB::column_helper::<Absence>(self, "absence");
+        let mut absence: Absence = self.get_single_absence(&decision.id).await?;
+        decision.check(&absence)?;
+
+        let filter: Document = doc! {
+            "_id": obj_id,
+            "state": absence.state.as_str(),
+            "version": MongoDB::version_condition(absence.version),
         };
+        let set: Document = doc! {"state": decision.state.as_str(), "decided_by": decision.decided_by.clone(), "decided_at": decision.decided_at, "version": absence.version + 1};
+        let updated: bool = col
+            .update_one(filter, doc! {"$set": set}, None)
+            .await?
+            .matched_count
+            > 0;
+        if !updated {
+            if decision.version.is_some() {
+                return Err(self
+                    .update_missed("absence", "absence", &decision.id, decision.version)
+                    .await);
+            }
+            return Err(RepositoryError::invalid_field(
+                "state",
+                "the absence was decided on by someone else in the meantime",
+            ));
+        }
+        decision.apply(&mut absence);
+
+        Ok(absence)
+    }
+
+    async fn get_absences(&self, filter: &AbsenceFilter) -> Result<Vec<Absence>, RepositoryError> {
+        self.find_absences(absence_filter(filter)).await
+    }
+
+    async fn get_absences_by_employees(
+        &self,
+        employee_ids: &[String],
+    ) -> Result<Vec<Absence>, RepositoryError> {
+        self.find_absences(doc! {"employee_id": {"$in": employee_ids}})
+            .await
+    }
 
-        let data: InsertOneResult = col.insert_one(&new_doc, None)
-            .ok()
-            .expect("Error while creating new rank.");
-        new_doc.id =  data.inserted_id.as_object_id();
+    async fn get_approved_absences_on(
+        &self,
+        employee_ids: &[String],
+        day: NaiveDate,
+    ) -> Result<Vec<Absence>, RepositoryError> {
+        let day: String = day.to_string();
+        self.find_absences(doc! {
+            "employee_id": {"$in": employee_ids},
+            "state": AbsenceState::Approved.as_str(),
+            "starts_on": {"$lte": &day},
+            "ends_on": {"$gte": &day},
+        })
+        .await
+    }
+
+    async fn get_single_absence(&self, id: &str) -> Result<Absence, RepositoryError> {
+        let obj_id: ObjectId = MongoDB::parse_id(id)?;
+        let col: Collection<Absence> = MongoDB::column_helper::<Absence>(self, "absence");
+        let filter: Document = MongoDB::with_live_owners(doc! {"_id": obj_id});
+
+        col.find_one(filter, None)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("absence", id))
+    }
+}
+
+/*
+ * Status History Repository
+ */
+#[async_trait]
+impl StatusHistoryRepository for MongoDB {
+    async fn record_status_transition(
+        &self,
+        transition: StatusTransition,
+    ) -> Result<StatusTransition, RepositoryError> {
+        let col: Collection<StatusTransition> =
+            MongoDB::column_helper::<StatusTransition>(self, "status_transition");
+        let mut new_doc: StatusTransition = transition;
+
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
 
         Ok(new_doc)
     }
 
-    pub fn get_all_ranks(&self) -> Result<Vec<Rank>, Error> {
-        let col: Collection<Rank> = MongoDB::column_helper(&self, "rank");
-        let cursor: Cursor<Rank>= col.find(None, None)
-            .ok()
-            .expect("Error while fetching list of ranks.");
+    async fn get_status_history(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<StatusTransition>, RepositoryError> {
+        let mut changed_at: Document = Document::new();
+        if let Some(from) = range.from {
+            changed_at.insert("$gte", from);
+        }
+        if let Some(to) = range.to {
+            changed_at.insert("$lt", to);
+        }
+        let mut filter: Document = doc! {"employee_id": {"$in": employee_ids}};
+        if !changed_at.is_empty() {
+            filter.insert("changed_at", changed_at);
+        }
+        let options: FindOptions = FindOptions::builder()
+            .sort(doc! {"changed_at": 1, "_id": 1})
+            .build();
+        let cursor: Cursor<StatusTransition> =
+            MongoDB::column_helper::<StatusTransition>(self, "status_transition")
+                .find(filter, options)
+                .await?;
 
-        let rank_vec: Vec<Rank> = cursor
-            .map(|doc| {
-                doc.unwrap()
-            })
-            .collect();
+        let transition_vec: Vec<StatusTransition> = cursor.try_collect().await?;
 
-        Ok(rank_vec)
+        Ok(transition_vec)
     }
+}
 
-    pub fn get_single_rank(&self, id: &String) -> Result<Option<Rank>, Error> {
-        let obj_id: ObjectId = ObjectId::parse_str(id).expect("Ungültige ID");
-        let filter: Document  = doc! {"_id": obj_id};
-        let col: Collection<Rank> =  MongoDB::column_helper::<Rank>(&self, "rank");
+/*
+ * Audit Repository
+ */
+#[async_trait]
+impl AuditRepository for MongoDB {
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, RepositoryError> {
+        let col: Collection<AuditEntry> = MongoDB::column_helper::<AuditEntry>(self, "audit_entry");
+        let mut new_doc: AuditEntry = entry;
 
-        let opt_rank: Option<Rank> = col
-            .find_one(filter, None)
-            .ok()
-            .expect(&*format!("Error while fetching requested rank with ID '{}'", obj_id));
+        let data: InsertOneResult = col.insert_one(&new_doc, None).await?;
+        new_doc.id = data.inserted_id.as_object_id();
 
-        Ok(opt_rank)
+        Ok(new_doc)
     }
 
-    pub fn validate_rank(&self, rank_id: &String) -> String {
-        let mut valid_rank_id: String = rank_id.clone();
+    async fn get_audit_page(
+        &self,
+        filter: &AuditFilter,
+        request: &PageRequest,
+    ) -> Result<Page<AuditEntry>, RepositoryError> {
+        self.find_page("audit_entry", audit_filter(filter), None, request)
+            .await
+    }
+}
 
-        let fetched_rank: Option<Rank> = self.get_single_rank(rank_id).unwrap();
-        if fetched_rank.is_none() { valid_rank_id = String::from("")}
+/*
+ * Purge Repository
+ */
+impl MongoDB {
+    /// Removes the entries of a collection marked deleted before the given
+    /// time and returns their IDs.
+    async fn purge(
+        &self,
+        collection_name: &str,
+        deleted_before: DateTime,
+    ) -> Result<Vec<String>, RepositoryError> {
+        let filter: Document = doc! {"deleted_at": {"$lt": deleted_before}};
+        let ids: Vec<String> = self.dependent_ids(collection_name, filter.clone()).await?;
+        if !ids.is_empty() {
+            MongoDB::column_helper::<Document>(self, collection_name)
+                .delete_many(filter, None)
+                .await?;
+        }
 
-        valid_rank_id
+        Ok(ids)
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl PurgeRepository for MongoDB {
+    async fn purge_deleted(
+        &self,
+        deleted_before: DateTime,
+    ) -> Result<PurgeReport, RepositoryError> {
+        let employees: Collection<Document> = MongoDB::column_helper::<Document>(self, "employee");
+        let employee_ids: Vec<String> = self.purge("employee", deleted_before).await?;
+        if !employee_ids.is_empty() {
+            self.drop_shifts("employee_id", &employee_ids).await?;
+            self.drop_absences(&employee_ids).await?;
+        }
+
+        let store_ids: Vec<String> = self.purge("store", deleted_before).await?;
+        if !store_ids.is_empty() {
+            self.drop_shifts("store_id", &store_ids).await?;
+            employees
+                .update_many(
+                    doc! {"stores": {"$in": &store_ids}},
+                    doc! {"$pull": {"stores": {"$in": &store_ids}}},
+                    None,
+                )
+                .await?;
+        }
+
+        let rank_ids: Vec<String> = self.purge("rank", deleted_before).await?;
+        if !rank_ids.is_empty() {
+            employees
+                .update_many(
+                    doc! {"rank_id": {"$in": &rank_ids}},
+                    doc! {"$set": {"rank_id": Bson::Null}},
+                    None,
+                )
+                .await?;
+            MongoDB::column_helper::<Document>(self, "shift")
+                .update_many(
+                    doc! {"required_rank_id": {"$in": &rank_ids}},
+                    doc! {"$set": {"required_rank_id": Bson::Null}},
+                    None,
+                )
+                .await?;
+        }
+
+        let location_ids: Vec<String> = self.purge("location", deleted_before).await?;
+        if !location_ids.is_empty() {
+            MongoDB::column_helper::<Document>(self, "store")
+                .update_many(
+                    doc! {"location_id": {"$in": &location_ids}},
+                    doc! {"$set": {"location_id": Bson::Null}},
+                    None,
+                )
+                .await?;
+        }
+
+        Ok(PurgeReport {
+            employees: employee_ids.len() as u64,
+            stores: store_ids.len() as u64,
+            locations: location_ids.len() as u64,
+            ranks: rank_ids.len() as u64,
+        })
+    }
+}
@@ -0,0 +1,222 @@
+use mongodb::options::{ClientOptions, Tls, TlsOptions};
+use rocket::figment::{
+    providers::{Env, Format, Serialized, Toml},
+    Figment,
+};
+use serde::{Deserialize, Serialize};
+use std::{env, path::PathBuf, time::Duration};
+
+pub const DEFAULT_DATABASE: &str = "praktikum";
+
+/// Connection settings for the MongoDB backend. Later sources override
+/// earlier ones:
+///
+/// 1. the defaults below,
+/// 2. the `mongo` table of the active profile in `Rocket.toml`,
+/// 3. the TOML file named by `MONGO_CONFIG_FILE`,
+/// 4. `MONGO_*` environment variables (`MONGO_URI`, `MONGO_DATABASE`,
+///    `MONGO_MAX_POOL_SIZE`, ...), with `__` separating nested keys as in
+///    `MONGO_TLS__CA_FILE`.
+///
+/// Pool size and timeouts left unset keep the driver's defaults, or
+/// whatever the URI sets. Pending migrations are applied at startup unless
+/// `migrate_on_start` is turned off.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MongoConfig {
+    pub uri: String,
+    pub database: String,
+    pub app_name: Option<String>,
+    pub min_pool_size: Option<u32>,
+    pub max_pool_size: Option<u32>,
+    pub connect_timeout_ms: Option<u64>,
+    pub server_selection_timeout_ms: Option<u64>,
+    pub tls: Option<MongoTlsConfig>,
+    pub migrate_on_start: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MongoTlsConfig {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub ca_file: Option<PathBuf>,
+    pub cert_key_file: Option<PathBuf>,
+    #[serde(default)]
+    pub allow_invalid_certificates: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for MongoConfig {
+    fn default() -> Self {
+        MongoConfig {
+            uri: String::new(),
+            database: String::from(DEFAULT_DATABASE),
+            app_name: None,
+            min_pool_size: None,
+            max_pool_size: None,
+            connect_timeout_ms: None,
+            server_selection_timeout_ms: None,
+            tls: None,
+            migrate_on_start: true,
+        }
+    }
+}
+
+impl MongoConfig {
+    pub fn figment() -> Figment {
+        let mut figment: Figment = Figment::from(Serialized::defaults(MongoConfig::default()))
+            .merge(rocket::Config::figment().focus("mongo"));
+        if let Ok(path) = env::var("MONGO_CONFIG_FILE") {
+            figment = figment.merge(Toml::file(path));
+        }
+
+        figment.merge(Env::prefixed("MONGO_").split("__"))
+    }
+
+    pub fn load() -> Result<Self, String> {
+        MongoConfig::from_figment(&MongoConfig::figment())
+    }
+
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        let config: MongoConfig = figment.extract().map_err(|error| error.to_string())?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.uri.trim().is_empty() {
+            return Err(String::from("no connection string given, set MONGO_URI or `uri` in the `mongo` table of Rocket.toml"));
+        }
+        if !self.uri.starts_with("mongodb://") && !self.uri.starts_with("mongodb+srv://") {
+            return Err(String::from(
+                "the connection string must start with 'mongodb://' or 'mongodb+srv://'",
+            ));
+        }
+        if self.database.trim().is_empty() {
+            return Err(String::from("the database name must not be empty"));
+        }
+        if let (Some(min), Some(max)) = (self.min_pool_size, self.max_pool_size) {
+            if min > max {
+                return Err(format!(
+                    "min_pool_size ({}) must not exceed max_pool_size ({})",
+                    min, max
+                ));
+            }
+        }
+        if self.max_pool_size == Some(0) {
+            return Err(String::from("max_pool_size must be at least 1"));
+        }
+        for (field, timeout) in [
+            ("connect_timeout_ms", self.connect_timeout_ms),
+            (
+                "server_selection_timeout_ms",
+                self.server_selection_timeout_ms,
+            ),
+        ] {
+            if timeout == Some(0) {
+                return Err(format!("{} must be greater than 0", field));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the connection string and applies every setting given on top
+    /// of it.
+    pub async fn client_options(&self) -> mongodb::error::Result<ClientOptions> {
+        let mut options: ClientOptions = ClientOptions::parse(&self.uri).await?;
+
+        if self.app_name.is_some() {
+            options.app_name = self.app_name.clone();
+        }
+        if self.min_pool_size.is_some() {
+            options.min_pool_size = self.min_pool_size;
+        }
+        if self.max_pool_size.is_some() {
+            options.max_pool_size = self.max_pool_size;
+        }
+        if let Some(timeout) = self.connect_timeout_ms {
+            options.connect_timeout = Some(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.server_selection_timeout_ms {
+            options.server_selection_timeout = Some(Duration::from_millis(timeout));
+        }
+        if let Some(tls) = &self.tls {
+            options.tls = Some(if tls.enabled {
+                Tls::Enabled(
+                    TlsOptions::builder()
+                        .ca_file_path(tls.ca_file.clone())
+                        .cert_key_file_path(tls.cert_key_file.clone())
+                        .allow_invalid_certificates(Some(tls.allow_invalid_certificates))
+                        .build(),
+                )
+            } else {
+                Tls::Disabled
+            });
+        }
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Result<MongoConfig, String> {
+        let figment: Figment =
+            Figment::from(Serialized::defaults(MongoConfig::default())).merge(Toml::string(toml));
+
+        MongoConfig::from_figment(&figment)
+    }
+
+    #[test]
+    fn defaults_to_praktikum_database() {
+        let config: MongoConfig = config(r#"uri = "mongodb://localhost:27017""#).unwrap();
+
+        assert_eq!(config.database, DEFAULT_DATABASE);
+        assert!(config.tls.is_none());
+    }
+
+    #[test]
+    fn requires_connection_string() {
+        assert!(config("").err().unwrap().contains("MONGO_URI"));
+        assert!(config(r#"uri = "localhost:27017""#).is_err());
+    }
+
+    #[test]
+    fn rejects_inverted_pool_bounds() {
+        let error: String =
+            config("uri = \"mongodb://localhost\"\nmin_pool_size = 10\nmax_pool_size = 5")
+                .err()
+                .unwrap();
+
+        assert!(error.contains("min_pool_size"));
+    }
+
+    #[rocket::async_test]
+    async fn applies_settings_over_uri() {
+        let config: MongoConfig = config(
+            r#"
+            uri = "mongodb://localhost:27017/?appName=uri&maxPoolSize=3"
+            app_name = "rocket_ql"
+            connect_timeout_ms = 2500
+
+            [tls]
+            ca_file = "/etc/ssl/mongo.pem"
+        "#,
+        )
+        .unwrap();
+        let options: ClientOptions = config.client_options().await.unwrap();
+
+        assert_eq!(options.app_name.as_deref(), Some("rocket_ql"));
+        assert_eq!(options.max_pool_size, Some(3));
+        assert_eq!(options.connect_timeout, Some(Duration::from_millis(2500)));
+        assert!(
+            matches!(options.tls, Some(Tls::Enabled(ref tls)) if tls.ca_file_path == Some(PathBuf::from("/etc/ssl/mongo.pem")))
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::schema::project_schema::{
+    AbsenceFilter, AbsenceState, AuditFilter, EmployeeFilter, EmployeeOrderBy, EmployeeOrderField,
+    LocationFilter, LocationOrderBy, LocationOrderField, OrderDirection, RankFilter, RankOrderBy,
+    RankOrderField, Status, StoreFilter, StoreOrderBy, StoreOrderField,
+};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use std::collections::HashMap;
+
+/// The field a listing is ordered by. `_id` is always appended as a
+/// tie-breaker in the same direction, so that the order is total and can be
+/// resumed from a cursor.
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    pub field: &'static str,
+    pub ascending: bool,
+}
+
+impl SortKey {
+    fn new(field: &'static str, direction: OrderDirection) -> Self {
+        SortKey {
+            field,
+            ascending: direction == OrderDirection::Asc,
+        }
+    }
+}
+
+/// Combines the given conditions with `$and`, skipping empty documents.
+pub fn all_of(conditions: Vec<Document>) -> Document {
+    let mut conditions: Vec<Document> = conditions
+        .into_iter()
+        .filter(|condition| !condition.is_empty())
+        .collect();
+
+    match conditions.len() {
+        0 => doc! {},
+        1 => conditions.remove(0),
+        _ => doc! {"$and": conditions},
+    }
+}
+
+/// Leaves out entries marked deleted unless they were asked for.
+fn exclude_deleted(conditions: &mut Vec<Document>, include_deleted: bool) {
+    if !include_deleted {
+        conditions.push(doc! {"deleted_at": null});
+    }
+}
+
+fn escape_regex(input: &str) -> String {
+    let mut escaped: String = String::with_capacity(input.len());
+    for c in input.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn name_matches(pattern: String) -> Document {
+    doc! {"$or": [
+        {"first_name": {"$regex": &pattern, "$options": "i"}},
+        {"last_name": {"$regex": &pattern, "$options": "i"}},
+    ]}
+}
+
+/*
+ * Employee Filters
+ */
+/// `absent` holds the statuses approved absences give today, which take
+/// precedence over the stored status like they do in the `status` resolver.
+pub fn employee_filter(filter: &EmployeeFilter, absent: &HashMap<String, Status>) -> Document {
+    let mut conditions: Vec<Document> = Vec::new();
+    exclude_deleted(&mut conditions, filter.include_deleted);
+
+    if let Some(status_in) = &filter.status_in {
+        let status_vec: Vec<&str> = status_in.iter().map(Status::as_str).collect();
+        let absent_ids = |all: bool| -> Vec<ObjectId> {
+            absent
+                .iter()
+                .filter(|(_, status)| all || status_in.contains(status))
+                .filter_map(|(id, _)| ObjectId::parse_str(id).ok())
+                .collect()
+        };
+        conditions.push(doc! {"$or": [
+            {"_id": {"$in": absent_ids(false)}},
+            {"_id": {"$nin": absent_ids(true)}, "status": {"$in": status_vec}},
+        ]});
+    }
+    if let Some(store_id) = &filter.store_id {
+        conditions.push(doc! {"stores": store_id});
+    }
+    if let Some(rank_id) = &filter.rank_id {
+        conditions.push(doc! {"rank_id": rank_id});
+    }
+    if let Some(name_prefix) = &filter.name_prefix {
+        conditions.push(name_matches(format!("^{}", escape_regex(name_prefix))));
+    }
+    if let Some(name_contains) = &filter.name_contains {
+        conditions.push(name_matches(escape_regex(name_contains)));
+    }
+
+    all_of(conditions)
+}
+
+pub fn employee_sort(order_by: Option<&EmployeeOrderBy>) -> Option<SortKey> {
+    order_by.map(|order_by| {
+        SortKey::new(
+            match order_by.field {
+                EmployeeOrderField::FirstName => "first_name",
+                EmployeeOrderField::LastName => "last_name",
+                EmployeeOrderField::Status => "status",
+            },
+            order_by.direction,
+        )
+    })
+}
+
+/*
+ * Store Filters
+ */
+pub fn store_filter(filter: &StoreFilter) -> Document {
+    let mut conditions: Vec<Document> = Vec::new();
+    exclude_deleted(&mut conditions, filter.include_deleted);
+
+    if let Some(location_id) = &filter.location_id {
+        conditions.push(doc! {"location_id": location_id});
+    }
+    if let Some(name) = &filter.name {
+        conditions.push(doc! {"name": name});
+    }
+
+    all_of(conditions)
+}
+
+pub fn store_sort(order_by: Option<&StoreOrderBy>) -> Option<SortKey> {
+    order_by.map(|order_by| {
+        SortKey::new(
+            match order_by.field {
+                StoreOrderField::Name => "name",
+                StoreOrderField::LocationId => "location_id",
+            },
+            order_by.direction,
+        )
+    })
+}
+
+/*
+ * Location Filters
+ */
+pub fn location_filter(filter: &LocationFilter) -> Document {
+    let mut conditions: Vec<Document> = Vec::new();
+    exclude_deleted(&mut conditions, filter.include_deleted);
+
+    if let Some(country) = &filter.country {
+        conditions.push(doc! {"country": country});
+    }
+    if let Some(state) = &filter.state {
+        conditions.push(doc! {"state": state});
+    }
+
+    all_of(conditions)
+}
+
+pub fn location_sort(order_by: Option<&LocationOrderBy>) -> Option<SortKey> {
+    order_by.map(|order_by| {
+        SortKey::new(
+            match order_by.field {
+                LocationOrderField::Country => "country",
+                LocationOrderField::State => "state",
+            },
+            order_by.direction,
+        )
+    })
+}
+
+/*
+ * Rank Filters
+ */
+pub fn rank_filter(filter: &RankFilter) -> Document {
+    let mut conditions: Vec<Document> = Vec::new();
+    exclude_deleted(&mut conditions, filter.include_deleted);
+
+    if let Some(name) = &filter.name {
+        conditions.push(doc! {"name": name});
+    }
+
+    all_of(conditions)
+}
+
+pub fn rank_sort(order_by: Option<&RankOrderBy>) -> Option<SortKey> {
+    order_by.map(|order_by| {
+        SortKey::new(
+            match order_by.field {
+                RankOrderField::Name => "name",
+            },
+            order_by.direction,
+        )
+    })
+}
+
+/*
+ * Absence Filters
+ */
+/// Days are stored as `YYYY-MM-DD` strings, which compare like the dates.
+pub fn absence_filter(filter: &AbsenceFilter) -> Document {
+    let mut conditions: Vec<Document> = Vec::new();
+
+    if let Some(employee_id) = &filter.employee_id {
+        conditions.push(doc! {"employee_id": employee_id});
+    }
+    if let Some(state_in) = &filter.state_in {
+        let state_vec: Vec<&str> = state_in.iter().map(AbsenceState::as_str).collect();
+        conditions.push(doc! {"state": {"$in": state_vec}});
+    }
+    if let Some(from) = &filter.from {
+        conditions.push(doc! {"ends_on": {"$gte": from.to_string()}});
+    }
+    if let Some(to) = &filter.to {
+        conditions.push(doc! {"starts_on": {"$lte": to.to_string()}});
+    }
+
+    all_of(conditions)
+}
+
+/*
+ * Audit Filters
+ */
+pub fn audit_filter(filter: &AuditFilter) -> Document {
+    let mut conditions: Vec<Document> = Vec::new();
+
+    for (field, value) in [
+        ("actor", &filter.actor),
+        ("operation", &filter.operation),
+        ("entity_type", &filter.entity_type),
+        ("entity_id", &filter.entity_id),
+    ] {
+        if let Some(value) = value {
+            conditions.push(doc! {field: value});
+        }
+    }
+    if let Some(from) = filter.from {
+        conditions.push(doc! {"at": {"$gte": from}});
+    }
+    if let Some(to) = filter.to {
+        conditions.push(doc! {"at": {"$lt": to}});
+    }
+
+    all_of(conditions)
+}
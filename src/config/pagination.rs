@@ -0,0 +1,89 @@
+use crate::config::{error::RepositoryError, validation::Validator};
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// A window over an `_id`-ordered collection, described by Relay-style
+/// cursors (the hex representation of the bounding document IDs).
+#[derive(Debug, Clone, Default)]
+pub struct PageRequest {
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub first: Option<usize>,
+    pub last: Option<usize>,
+}
+
+impl PageRequest {
+    pub fn new(
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Self, RepositoryError> {
+        let mut validator: Validator = Validator::default();
+        for (field, size) in [("first", first), ("last", last)] {
+            if size.is_some_and(|size| size > MAX_PAGE_SIZE) {
+                validator.add(field, format!("must not exceed {}", MAX_PAGE_SIZE));
+            }
+        }
+        validator.finish()?;
+
+        Ok(PageRequest {
+            after,
+            before,
+            first,
+            last,
+        })
+    }
+
+    /// Paging backwards is only done when the client asks for the `last`
+    /// entries without also bounding the page from the front.
+    pub fn is_backward(&self) -> bool {
+        self.last.is_some() && self.first.is_none()
+    }
+
+    pub fn size(&self) -> usize {
+        if self.is_backward() {
+            self.last
+        } else {
+            self.first
+        }
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_previous_page: bool,
+    pub has_next_page: bool,
+    pub total_count: u64,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from a query that fetched one document more than
+    /// requested, which tells us whether another page follows in the
+    /// direction of travel.
+    pub fn from_overfetch(mut items: Vec<T>, request: &PageRequest, total_count: u64) -> Self {
+        let size: usize = request.size();
+        let has_more: bool = items.len() > size;
+        items.truncate(size);
+
+        if request.is_backward() {
+            items.reverse();
+            Page {
+                items,
+                has_previous_page: has_more,
+                has_next_page: request.before.is_some(),
+                total_count,
+            }
+        } else {
+            Page {
+                items,
+                has_previous_page: request.after.is_some(),
+                has_next_page: has_more,
+                total_count,
+            }
+        }
+    }
+}
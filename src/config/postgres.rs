@@ -0,0 +1,1847 @@
+use crate::config::{
+    error::RepositoryError,
+    integrity::{DeletePolicies, OnDelete},
+    mongo_filter::{employee_sort, location_sort, rank_sort, store_sort, SortKey},
+    pagination::{Page, PageRequest},
+    postgres_filter::{
+        absence_filter, audit_filter, employee_filter, location_filter, push_order, rank_filter,
+        sort_expression, store_filter, SqlQuery,
+    },
+    repository::{
+        check_version, employee_patch, location_patch, new_absence, new_employee, new_location,
+        new_rank, new_shift, new_store, rank_patch, shift_patch, store_patch,
+        validate_restored_employee, validate_restored_store, AbsenceDecision, AbsenceRepository,
+        AuditRepository, Deletion, EmployeePatch, EmployeeRepository, LocationPatch,
+        LocationRepository, PurgeReport, PurgeRepository, RankPatch, RankRepository, ShiftPatch,
+        ShiftRepository, StatusHistoryRepository, StorePatch, StoreRepository, TimeRange,
+    },
+    validation::ValidationMode,
+};
+use crate::schema::project_schema::{
+    Absence, AbsenceFilter, AbsenceState, AuditEntry, AuditFilter, CreateEmployee, CreateLocation,
+    CreateRank, CreateShift, CreateStore, DeleteShift, Employee, EmployeeFilter, EmployeeOrderBy,
+    Location, LocationFilter, LocationOrderBy, Rank, RankFilter, RankOrderBy, RequestAbsence,
+    Shift, StatusTransition, Store, StoreFilter, StoreOrderBy, UpdateEmployee, UpdateLocation,
+    UpdateRank, UpdateShift, UpdateStore,
+};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use mongodb::bson::{from_bson, oid::ObjectId, to_bson, Bson, DateTime, Document};
+use rocket::serde::json::{serde_json, Value as Json};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{
+    postgres::{PgPool, PgPoolOptions, PgRow},
+    types::chrono::{self, NaiveDate, Utc},
+    Executor, FromRow, Postgres, QueryBuilder, Transaction,
+};
+use std::{collections::HashMap, env};
+
+/// IDs are generated as ObjectIds and stored in their hex form, so that
+/// cursors and references look the same on every backend. Store and rank names are
+/// unique among the entries that are not deleted; indexes built before that
+/// covered every row and are rebuilt.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS locations (
+    id CHAR(24) PRIMARY KEY,
+    country TEXT NOT NULL,
+    state TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS stores (
+    id CHAR(24) PRIMARY KEY,
+    name TEXT NOT NULL,
+    location_id CHAR(24) REFERENCES locations (id)
+);
+
+CREATE TABLE IF NOT EXISTS ranks (
+    id CHAR(24) PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    permissions TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE IF NOT EXISTS employees (
+    id CHAR(24) PRIMARY KEY,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    status TEXT,
+    rank_id CHAR(24) REFERENCES ranks (id)
+);
+
+CREATE TABLE IF NOT EXISTS employee_stores (
+    employee_id CHAR(24) NOT NULL REFERENCES employees (id) ON DELETE CASCADE,
+    store_id CHAR(24) NOT NULL REFERENCES stores (id),
+    position INTEGER NOT NULL,
+    PRIMARY KEY (employee_id, store_id)
+);
+
+CREATE TABLE IF NOT EXISTS shifts (
+    id CHAR(24) PRIMARY KEY,
+    employee_id CHAR(24) NOT NULL REFERENCES employees (id) ON DELETE CASCADE,
+    store_id CHAR(24) NOT NULL REFERENCES stores (id) ON DELETE CASCADE,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    required_rank_id CHAR(24) REFERENCES ranks (id) ON DELETE SET NULL,
+    CHECK (ends_at > starts_at)
+);
+
+CREATE TABLE IF NOT EXISTS absences (
+    id CHAR(24) PRIMARY KEY,
+    employee_id CHAR(24) NOT NULL REFERENCES employees (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    reason TEXT,
+    state TEXT NOT NULL,
+    requested_at TIMESTAMPTZ NOT NULL,
+    decided_by TEXT,
+    decided_at TIMESTAMPTZ,
+    CHECK (ends_on >= starts_on)
+);
+
+CREATE TABLE IF NOT EXISTS status_transitions (
+    id CHAR(24) PRIMARY KEY,
+    employee_id CHAR(24) NOT NULL,
+    previous_status TEXT,
+    status TEXT,
+    changed_at TIMESTAMPTZ NOT NULL,
+    changed_by TEXT
+);
+
+CREATE TABLE IF NOT EXISTS audit_entries (
+    id CHAR(24) PRIMARY KEY,
+    actor TEXT,
+    operation TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    at TIMESTAMPTZ NOT NULL,
+    diff TEXT NOT NULL,
+    client_ip TEXT
+);
+
+ALTER TABLE employees ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ, ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+ALTER TABLE stores ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ, ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+ALTER TABLE locations ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ, ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+ALTER TABLE ranks ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ, ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+
+ALTER TABLE employees ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE stores ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE locations ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE ranks ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE shifts ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE absences ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+
+DO $$
+DECLARE
+    stale TEXT;
+BEGIN
+    FOR stale IN
+        SELECT pg_index.indexrelid::regclass::text FROM pg_index
+        JOIN pg_class index_class ON index_class.oid = pg_index.indexrelid
+        WHERE index_class.relname IN ('stores_name_unique', 'ranks_name_unique')
+            AND index_class.relnamespace = current_schema()::regnamespace
+            AND pg_index.indpred IS NULL
+    LOOP
+        EXECUTE format('DROP INDEX %s', stale);
+    END LOOP;
+END $$;
+CREATE UNIQUE INDEX IF NOT EXISTS stores_name_unique ON stores (name) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS ranks_name_unique ON ranks (name) WHERE deleted_at IS NULL;
+CREATE INDEX IF NOT EXISTS stores_location_id ON stores (location_id);
+CREATE INDEX IF NOT EXISTS employees_status ON employees (status);
+CREATE INDEX IF NOT EXISTS employees_rank_id ON employees (rank_id);
+CREATE INDEX IF NOT EXISTS employee_stores_store_id ON employee_stores (store_id);
+CREATE INDEX IF NOT EXISTS shifts_employee_id ON shifts (employee_id, starts_at);
+CREATE INDEX IF NOT EXISTS shifts_store_id ON shifts (store_id, starts_at);
+CREATE INDEX IF NOT EXISTS absences_employee_id ON absences (employee_id, starts_on);
+CREATE INDEX IF NOT EXISTS status_transitions_employee_id ON status_transitions (employee_id, changed_at);
+CREATE INDEX IF NOT EXISTS audit_entries_entity ON audit_entries (entity_type, entity_id);
+CREATE INDEX IF NOT EXISTS audit_entries_actor ON audit_entries (actor);
+"#;
+
+/// Tables whose rows are marked deleted instead of being removed.
+const SOFT_DELETED: [&str; 4] = ["employees", "stores", "locations", "ranks"];
+
+#[derive(FromRow)]
+struct EmployeeRow {
+    id: String,
+    first_name: String,
+    last_name: String,
+    status: Option<String>,
+    rank_id: Option<String>,
+    version: i32,
+    deleted_at: Option<chrono::DateTime<Utc>>,
+    deleted_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct StoreRow {
+    id: String,
+    name: String,
+    location_id: Option<String>,
+    version: i32,
+    deleted_at: Option<chrono::DateTime<Utc>>,
+    deleted_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct LocationRow {
+    id: String,
+    country: String,
+    state: String,
+    version: i32,
+    deleted_at: Option<chrono::DateTime<Utc>>,
+    deleted_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct RankRow {
+    id: String,
+    name: String,
+    description: Option<String>,
+    permissions: Vec<String>,
+    version: i32,
+    deleted_at: Option<chrono::DateTime<Utc>>,
+    deleted_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct ShiftRow {
+    id: String,
+    employee_id: String,
+    store_id: String,
+    starts_at: chrono::DateTime<Utc>,
+    ends_at: chrono::DateTime<Utc>,
+    required_rank_id: Option<String>,
+    version: i32,
+}
+
+#[derive(FromRow)]
+struct AbsenceRow {
+    id: String,
+    employee_id: String,
+    kind: String,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    reason: Option<String>,
+    state: String,
+    requested_at: chrono::DateTime<Utc>,
+    decided_by: Option<String>,
+    decided_at: Option<chrono::DateTime<Utc>>,
+    version: i32,
+}
+
+#[derive(FromRow)]
+struct StatusTransitionRow {
+    id: String,
+    employee_id: String,
+    previous_status: Option<String>,
+    status: Option<String>,
+    changed_at: chrono::DateTime<Utc>,
+    changed_by: Option<String>,
+}
+
+/// `diff` holds the document as relaxed extended JSON.
+#[derive(FromRow)]
+struct AuditRow {
+    id: String,
+    actor: Option<String>,
+    operation: String,
+    entity_type: String,
+    entity_id: String,
+    at: chrono::DateTime<Utc>,
+    diff: String,
+    client_ip: Option<String>,
+}
+
+/// Enums are stored under their serialized names, as in MongoDB.
+fn encode<T: Serialize>(value: &T) -> String {
+    match to_bson(value) {
+        Ok(Bson::String(text)) => text,
+        _ => String::new(),
+    }
+}
+
+fn decode<T: DeserializeOwned>(text: String) -> Result<T, RepositoryError> {
+    from_bson(Bson::String(text)).map_err(|error| RepositoryError::Backend(error.to_string()))
+}
+
+impl EmployeeRow {
+    fn into_employee(self, stores: Vec<String>) -> Result<Employee, RepositoryError> {
+        Ok(Employee {
+            id: ObjectId::parse_str(&self.id).ok(),
+            first_name: self.first_name,
+            last_name: self.last_name,
+            status: self.status.map(decode).transpose()?,
+            stores: Some(stores),
+            rank_id: self.rank_id,
+            version: self.version,
+            deleted_at: self.deleted_at.map(DateTime::from_chrono),
+            deleted_by: self.deleted_by,
+        })
+    }
+}
+
+impl From<StoreRow> for Store {
+    fn from(row: StoreRow) -> Self {
+        Store {
+            id: ObjectId::parse_str(&row.id).ok(),
+            name: row.name,
+            location_id: row.location_id,
+            version: row.version,
+            deleted_at: row.deleted_at.map(DateTime::from_chrono),
+            deleted_by: row.deleted_by,
+        }
+    }
+}
+
+impl From<LocationRow> for Location {
+    fn from(row: LocationRow) -> Self {
+        Location {
+            id: ObjectId::parse_str(&row.id).ok(),
+            country: row.country,
+            state: row.state,
+            version: row.version,
+            deleted_at: row.deleted_at.map(DateTime::from_chrono),
+            deleted_by: row.deleted_by,
+        }
+    }
+}
+
+impl TryFrom<RankRow> for Rank {
+    type Error = RepositoryError;
+
+    fn try_from(row: RankRow) -> Result<Self, RepositoryError> {
+        Ok(Rank {
+            id: ObjectId::parse_str(&row.id).ok(),
+            name: row.name,
+            description: row.description,
+            permissions: row
+                .permissions
+                .into_iter()
+                .map(decode)
+                .collect::<Result<_, _>>()?,
+            version: row.version,
+            deleted_at: row.deleted_at.map(DateTime::from_chrono),
+            deleted_by: row.deleted_by,
+        })
+    }
+}
+
+impl From<ShiftRow> for Shift {
+    fn from(row: ShiftRow) -> Self {
+        Shift {
+            id: ObjectId::parse_str(&row.id).ok(),
+            employee_id: row.employee_id,
+            store_id: row.store_id,
+            starts_at: DateTime::from_chrono(row.starts_at),
+            ends_at: DateTime::from_chrono(row.ends_at),
+            required_rank_id: row.required_rank_id,
+            version: row.version,
+        }
+    }
+}
+
+impl TryFrom<AbsenceRow> for Absence {
+    type Error = RepositoryError;
+
+    fn try_from(row: AbsenceRow) -> Result<Self, RepositoryError> {
+        Ok(Absence {
+            id: ObjectId::parse_str(&row.id).ok(),
+            employee_id: row.employee_id,
+            kind: decode(row.kind)?,
+            starts_on: row.starts_on,
+            ends_on: row.ends_on,
+            reason: row.reason,
+            state: decode(row.state)?,
+            requested_at: DateTime::from_chrono(row.requested_at),
+            decided_by: row.decided_by,
+            decided_at: row.decided_at.map(DateTime::from_chrono),
+            version: row.version,
+        })
+    }
+}
+
+impl TryFrom<StatusTransitionRow> for StatusTransition {
+    type Error = RepositoryError;
+
+    fn try_from(row: StatusTransitionRow) -> Result<Self, RepositoryError> {
+        Ok(StatusTransition {
+            id: ObjectId::parse_str(&row.id).ok(),
+            employee_id: row.employee_id,
+            previous_status: row.previous_status.map(decode).transpose()?,
+            status: row.status.map(decode).transpose()?,
+            changed_at: DateTime::from_chrono(row.changed_at),
+            changed_by: row.changed_by,
+        })
+    }
+}
+
+impl TryFrom<AuditRow> for AuditEntry {
+    type Error = RepositoryError;
+
+    fn try_from(row: AuditRow) -> Result<Self, RepositoryError> {
+        let diff: Document = serde_json::from_str::<Json>(&row.diff)
+            .ok()
+            .and_then(|json| Bson::try_from(json).ok())
+            .and_then(|bson| bson.as_document().cloned())
+            .ok_or_else(|| {
+                RepositoryError::Backend(format!("audit entry {} has a malformed diff", row.id))
+            })?;
+
+        Ok(AuditEntry {
+            id: ObjectId::parse_str(&row.id).ok(),
+            actor: row.actor,
+            operation: row.operation,
+            entity_type: row.entity_type,
+            entity_id: row.entity_id,
+            at: DateTime::from_chrono(row.at),
+            diff,
+            client_ip: row.client_ip,
+        })
+    }
+}
+
+pub struct PostgresDB {
+    pool: PgPool,
+    policies: DeletePolicies,
+}
+
+impl PostgresDB {
+    pub async fn init() -> Self {
+        dotenv().ok();
+        let uri = env::var("POSTGRES_URI").unwrap_or_else(|_| {
+            panic!("POSTGRES_URI must be set to use the postgres storage backend")
+        });
+
+        let pool = PgPoolOptions::new()
+            .connect(&uri)
+            .await
+            .unwrap_or_else(|error| panic!("Cannot connect to Postgres: {}", error));
+        let policies = DeletePolicies::from_env()
+            .unwrap_or_else(|error| panic!("Invalid on-delete policy configuration: {}", error));
+        let db = PostgresDB::new(pool, policies);
+        db.create_tables()
+            .await
+            .unwrap_or_else(|error| panic!("Cannot create the Postgres tables: {}", error));
+        db
+    }
+
+    pub fn new(pool: PgPool, policies: DeletePolicies) -> Self {
+        PostgresDB { pool, policies }
+    }
+
+    pub async fn create_tables(&self) -> Result<(), sqlx::Error> {
+        self.pool.execute(SCHEMA).await?;
+
+        Ok(())
+    }
+
+    fn parse_id(id: &str) -> Result<String, RepositoryError> {
+        ObjectId::parse_str(id)
+            .map(|obj_id| obj_id.to_hex())
+            .map_err(|_| RepositoryError::InvalidId(String::from(id)))
+    }
+
+    /// The condition that leaves out rows marked deleted, for the tables
+    /// that have them. Shifts and absences stay with their deleted employee
+    /// or store until it is purged, and are left out like it.
+    fn live(table: &str) -> &'static str {
+        match table {
+            "shifts" => " AND employee_id IN (SELECT id FROM employees WHERE deleted_at IS NULL) AND store_id IN (SELECT id FROM stores WHERE deleted_at IS NULL)",
+            "absences" => " AND employee_id IN (SELECT id FROM employees WHERE deleted_at IS NULL)",
+            _ if SOFT_DELETED.contains(&table) => " AND deleted_at IS NULL",
+            _ => "",
+        }
+    }
+
+    async fn find_by_ids<R>(&self, table: &str, ids: &[String]) -> Result<Vec<R>, RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let valid_ids: Vec<String> = ids
+            .iter()
+            .filter_map(|id| PostgresDB::parse_id(id).ok())
+            .collect();
+        if valid_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let row_vec: Vec<R> = sqlx::query_as(&format!(
+            "SELECT * FROM {} WHERE id = ANY($1){}",
+            table,
+            PostgresDB::live(table)
+        ))
+        .bind(valid_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(row_vec)
+    }
+
+    async fn find_one<R>(
+        &self,
+        table: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<R, RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let valid_id: String = PostgresDB::parse_id(id)?;
+
+        sqlx::query_as(&format!(
+            "SELECT * FROM {} WHERE id = $1{}",
+            table,
+            PostgresDB::live(table)
+        ))
+        .bind(valid_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    /// Fetches a row for an update, locking it until the transaction ends.
+    async fn lock_one<R>(
+        tx: &mut Transaction<'_, Postgres>,
+        table: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<R, RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let valid_id: String = PostgresDB::parse_id(id)?;
+
+        sqlx::query_as(&format!(
+            "SELECT * FROM {} WHERE id = $1{} FOR UPDATE",
+            table,
+            PostgresDB::live(table)
+        ))
+        .bind(valid_id)
+        .fetch_optional(tx)
+        .await?
+        .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    /// Marks a row deleted. The row has been locked by the caller.
+    async fn mark_deleted(
+        tx: &mut Transaction<'_, Postgres>,
+        table: &str,
+        id: &str,
+        deletion: &Deletion,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(&format!(
+            "UPDATE {} SET deleted_at = $2, deleted_by = $3 WHERE id = $1",
+            table
+        ))
+        .bind(id)
+        .bind(deletion.deleted_at.to_chrono())
+        .bind(&deletion.deleted_by)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the live rows matching a condition on `$1` deleted and returns
+    /// their IDs.
+    async fn mark_dependents_deleted(
+        tx: &mut Transaction<'_, Postgres>,
+        table: &str,
+        condition: &str,
+        id: &str,
+        deletion: &Deletion,
+    ) -> Result<Vec<String>, RepositoryError> {
+        let dependents: Vec<String> = sqlx::query_scalar(&format!(
+            "UPDATE {} SET deleted_at = $2, deleted_by = $3 WHERE {} AND deleted_at IS NULL RETURNING id", table, condition))
+            .bind(id)
+            .bind(deletion.deleted_at.to_chrono())
+            .bind(&deletion.deleted_by)
+            .fetch_all(&mut *tx).await?;
+
+        Ok(dependents)
+    }
+
+    async fn find_deleted<R>(
+        &self,
+        table: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<R, RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let valid_id: String = PostgresDB::parse_id(id)?;
+
+        sqlx::query_as(&format!(
+            "SELECT * FROM {} WHERE id = $1 AND deleted_at IS NOT NULL",
+            table
+        ))
+        .bind(valid_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    async fn restore_row<R>(
+        &self,
+        table: &str,
+        entity: &'static str,
+        id: &str,
+    ) -> Result<R, RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let valid_id: String = PostgresDB::parse_id(id)?;
+
+        sqlx::query_as(&format!("UPDATE {} SET deleted_at = NULL, deleted_by = NULL WHERE id = $1 AND deleted_at IS NOT NULL RETURNING *", table))
+            .bind(valid_id)
+            .fetch_optional(&self.pool).await?
+            .ok_or_else(|| RepositoryError::not_found(entity, id))
+    }
+
+    fn select(table: &str, filter: impl Fn(&mut SqlQuery)) -> SqlQuery {
+        let mut query: SqlQuery = QueryBuilder::new(format!("SELECT * FROM {} WHERE TRUE", table));
+        filter(&mut query);
+        query
+    }
+
+    async fn find_all<R>(
+        &self,
+        table: &str,
+        filter: impl Fn(&mut SqlQuery),
+        sort: Option<SortKey>,
+    ) -> Result<Vec<R>, RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let mut query: SqlQuery = PostgresDB::select(table, filter);
+        push_order(&mut query, sort, false);
+
+        let row_vec: Vec<R> = query.build_query_as().fetch_all(&self.pool).await?;
+
+        Ok(row_vec)
+    }
+
+    /// Appends the condition selecting every row that comes after (or
+    /// before) the cursor row in the given sort order.
+    async fn push_cursor_condition(
+        &self,
+        query: &mut SqlQuery,
+        table: &str,
+        cursor: &str,
+        sort: Option<SortKey>,
+        after: bool,
+    ) -> Result<(), RepositoryError> {
+        let cursor_id: String = PostgresDB::parse_id(cursor)?;
+        let operator: &str = if sort.is_none_or(|key| key.ascending) == after {
+            ">"
+        } else {
+            "<"
+        };
+
+        let key: SortKey = match sort {
+            Some(key) => key,
+            None => {
+                query
+                    .push(format!(" AND id {} ", operator))
+                    .push_bind(cursor_id);
+                return Ok(());
+            }
+        };
+
+        let value: String = sqlx::query_scalar(&format!(
+            "SELECT {} FROM {} WHERE id = $1",
+            sort_expression(key),
+            table
+        ))
+        .bind(&cursor_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            RepositoryError::invalid_field(
+                if after { "after" } else { "before" },
+                format!("cursor '{}' does not point to an existing entry", cursor),
+            )
+        })?;
+
+        query
+            .push(format!(
+                " AND ({}, id) {} (",
+                sort_expression(key),
+                operator
+            ))
+            .push_bind(value)
+            .push(", ")
+            .push_bind(cursor_id)
+            .push(")");
+
+        Ok(())
+    }
+
+    async fn find_page<R>(
+        &self,
+        table: &str,
+        filter: impl Fn(&mut SqlQuery),
+        sort: Option<SortKey>,
+        request: &PageRequest,
+    ) -> Result<(Vec<R>, u64), RepositoryError>
+    where
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let mut query: SqlQuery = PostgresDB::select(table, &filter);
+        if let Some(after) = &request.after {
+            self.push_cursor_condition(&mut query, table, after, sort, true)
+                .await?;
+        }
+        if let Some(before) = &request.before {
+            self.push_cursor_condition(&mut query, table, before, sort, false)
+                .await?;
+        }
+        push_order(&mut query, sort, request.is_backward());
+        query.push(" LIMIT ").push_bind(request.size() as i64 + 1);
+        let row_vec: Vec<R> = query.build_query_as().fetch_all(&self.pool).await?;
+
+        let mut count: SqlQuery =
+            QueryBuilder::new(format!("SELECT COUNT(*) FROM {} WHERE TRUE", table));
+        filter(&mut count);
+        let (total_count,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        Ok((row_vec, total_count as u64))
+    }
+
+    async fn dependent_ids<'e, E>(
+        executor: E,
+        query: &str,
+        id: &str,
+    ) -> Result<Vec<String>, RepositoryError>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let dependents: Vec<String> = sqlx::query_scalar(query)
+            .bind(id)
+            .fetch_all(executor)
+            .await?;
+
+        Ok(dependents)
+    }
+
+    /// The stores of each of the given employees, in the order they were
+    /// assigned.
+    async fn store_assignments<'e, E>(
+        executor: E,
+        employee_ids: &[String],
+    ) -> Result<HashMap<String, Vec<String>>, RepositoryError>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let assignment_vec: Vec<(String, String)> = sqlx::query_as(
+            "SELECT employee_id, store_id FROM employee_stores WHERE employee_id = ANY($1) ORDER BY employee_id, position")
+            .bind(employee_ids)
+            .fetch_all(executor).await?;
+
+        let mut assignments: HashMap<String, Vec<String>> = HashMap::new();
+        for (employee_id, store_id) in assignment_vec {
+            assignments.entry(employee_id).or_default().push(store_id);
+        }
+
+        Ok(assignments)
+    }
+
+    async fn with_stores(
+        &self,
+        row_vec: Vec<EmployeeRow>,
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let employee_ids: Vec<String> = row_vec.iter().map(|row| row.id.clone()).collect();
+        let mut assignments: HashMap<String, Vec<String>> =
+            PostgresDB::store_assignments(&self.pool, &employee_ids).await?;
+
+        row_vec
+            .into_iter()
+            .map(|row| {
+                let stores: Vec<String> = assignments.remove(&row.id).unwrap_or_default();
+                row.into_employee(stores)
+            })
+            .collect()
+    }
+
+    async fn lock_employee(
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+    ) -> Result<Employee, RepositoryError> {
+        let row: EmployeeRow = PostgresDB::lock_one(tx, "employees", "employee", id).await?;
+        let mut assignments: HashMap<String, Vec<String>> =
+            PostgresDB::store_assignments(&mut *tx, std::slice::from_ref(&row.id)).await?;
+        let stores: Vec<String> = assignments.remove(&row.id).unwrap_or_default();
+
+        row.into_employee(stores)
+    }
+
+    async fn assign_stores(
+        tx: &mut Transaction<'_, Postgres>,
+        employee_id: &str,
+        stores: &[String],
+    ) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM employee_stores WHERE employee_id = $1")
+            .bind(employee_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (position, store_id) in stores.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO employee_stores (employee_id, store_id, position) VALUES ($1, $2, $3)",
+            )
+            .bind(employee_id)
+            .bind(store_id)
+            .bind(position as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Refuses to delete stores live employees are assigned to under
+    /// `Restrict`. The other policies leave the assignments for the purge.
+    async fn check_assignments(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        store_ids: &[String],
+    ) -> Result<(), RepositoryError> {
+        if self.policies.employee_store == OnDelete::Restrict {
+            let dependents: Vec<String> = sqlx::query_scalar(
+                "SELECT DISTINCT employee_id FROM employee_stores JOIN employees ON employees.id = employee_id WHERE store_id = ANY($1) AND deleted_at IS NULL ORDER BY employee_id")
+                .bind(store_ids)
+                .fetch_all(&mut *tx).await?;
+            if !dependents.is_empty() {
+                return Err(RepositoryError::HasDependents {
+                    entity: "store",
+                    id: store_ids.join(", "),
+                    dependent_entity: "employee",
+                    dependents,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/*
+ * Employee Repository
+ */
+#[async_trait]
+impl EmployeeRepository for PostgresDB {
+    async fn create_employee(
+        &self,
+        new_entry: CreateEmployee,
+        mode: ValidationMode,
+    ) -> Result<Employee, RepositoryError> {
+        let mut new_doc: Employee = new_employee(self, new_entry, mode).await?;
+        let obj_id: ObjectId = ObjectId::new();
+
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        sqlx::query("INSERT INTO employees (id, first_name, last_name, status, rank_id) VALUES ($1, $2, $3, $4, $5)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.first_name)
+            .bind(&new_doc.last_name)
+            .bind(new_doc.status.as_ref().map(encode))
+            .bind(new_doc.rank_id.as_deref())
+            .execute(&mut tx).await?;
+        PostgresDB::assign_stores(
+            &mut tx,
+            &obj_id.to_hex(),
+            new_doc.stores.as_deref().unwrap_or_default(),
+        )
+        .await?;
+        tx.commit().await?;
+
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn update_employee(
+        &self,
+        update_entry: UpdateEmployee,
+        mode: ValidationMode,
+    ) -> Result<(Employee, Employee), RepositoryError> {
+        let id: String = PostgresDB::parse_id(&update_entry.id)?;
+        let patch: EmployeePatch = employee_patch(self, update_entry, mode).await?;
+
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let previous: Employee = PostgresDB::lock_employee(&mut tx, &id).await?;
+        check_version("employee", &id, patch.version, previous.version)?;
+        let mut employee: Employee = previous.clone();
+        // A stored status this version does not know reads as `Unknown`; it
+        // is only overwritten when the patch sets a new one.
+        let sets_status: bool = !patch.status.is_undefined();
+        patch.apply(&mut employee);
+
+        let mut query: SqlQuery = QueryBuilder::new("UPDATE employees SET first_name = ");
+        query
+            .push_bind(employee.first_name.clone())
+            .push(", last_name = ")
+            .push_bind(employee.last_name.clone())
+            .push(", rank_id = ")
+            .push_bind(employee.rank_id.clone())
+            .push(", version = ")
+            .push_bind(employee.version);
+        if sets_status {
+            query
+                .push(", status = ")
+                .push_bind(employee.status.as_ref().map(encode));
+        }
+        query.push(" WHERE id = ").push_bind(id.clone());
+        query.build().execute(&mut tx).await?;
+        let stores: Vec<String> = employee.stores.take().unwrap_or_default();
+        PostgresDB::assign_stores(&mut tx, &id, &stores).await?;
+        tx.commit().await?;
+
+        employee.stores = Some(stores);
+
+        Ok((previous, employee))
+    }
+
+    async fn delete_employee(&self, deletion: Deletion) -> Result<Employee, RepositoryError> {
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut employee: Employee = PostgresDB::lock_employee(&mut tx, &deletion.id).await?;
+        let id: String = PostgresDB::parse_id(&deletion.id)?;
+        PostgresDB::mark_deleted(&mut tx, "employees", &id, &deletion).await?;
+        tx.commit().await?;
+        deletion.apply(&mut employee);
+
+        Ok(employee)
+    }
+
+    async fn restore_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        let deleted: Employee = self.get_deleted_employee(id).await?;
+        validate_restored_employee(self, &self.policies, &deleted).await?;
+
+        let row: EmployeeRow = self
+            .restore_row("employees", "deleted employee", id)
+            .await?;
+        self.with_stores(vec![row])
+            .await
+            .map(|mut employee_vec| employee_vec.remove(0))
+    }
+
+    async fn get_all_employees(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let row_vec: Vec<EmployeeRow> = self
+            .find_all(
+                "employees",
+                |query| employee_filter(query, filter),
+                employee_sort(order_by),
+            )
+            .await?;
+
+        self.with_stores(row_vec).await
+    }
+
+    async fn get_employees_by_stores(
+        &self,
+        store_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let row_vec: Vec<EmployeeRow> = sqlx::query_as(
+            "SELECT * FROM employees WHERE id IN (SELECT employee_id FROM employee_stores WHERE store_id = ANY($1)) AND deleted_at IS NULL ORDER BY id")
+            .bind(store_ids)
+            .fetch_all(&self.pool).await?;
+
+        self.with_stores(row_vec).await
+    }
+
+    async fn get_employees_by_ranks(
+        &self,
+        rank_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError> {
+        let row_vec: Vec<EmployeeRow> = sqlx::query_as(
+            "SELECT * FROM employees WHERE rank_id = ANY($1) AND deleted_at IS NULL ORDER BY id",
+        )
+        .bind(rank_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.with_stores(row_vec).await
+    }
+
+    async fn get_employees_by_ids(&self, ids: &[String]) -> Result<Vec<Employee>, RepositoryError> {
+        let row_vec: Vec<EmployeeRow> = self.find_by_ids("employees", ids).await?;
+
+        self.with_stores(row_vec).await
+    }
+
+    async fn get_employee_page(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Employee>, RepositoryError> {
+        let (row_vec, total_count): (Vec<EmployeeRow>, u64) = self
+            .find_page(
+                "employees",
+                |query| employee_filter(query, filter),
+                employee_sort(order_by),
+                request,
+            )
+            .await?;
+
+        Ok(Page::from_overfetch(
+            self.with_stores(row_vec).await?,
+            request,
+            total_count,
+        ))
+    }
+
+    async fn get_single_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        let row: EmployeeRow = self.find_one("employees", "employee", id).await?;
+
+        self.with_stores(vec![row])
+            .await
+            .map(|mut employee_vec| employee_vec.remove(0))
+    }
+
+    async fn get_deleted_employee(&self, id: &str) -> Result<Employee, RepositoryError> {
+        let row: EmployeeRow = self
+            .find_deleted("employees", "deleted employee", id)
+            .await?;
+
+        self.with_stores(vec![row])
+            .await
+            .map(|mut employee_vec| employee_vec.remove(0))
+    }
+}
+
+/*
+ * Store Repository
+ */
+#[async_trait]
+impl StoreRepository for PostgresDB {
+    async fn create_store(
+        &self,
+        new_entry: CreateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError> {
+        let mut new_doc: Store = new_store(self, new_entry, mode).await?;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query("INSERT INTO stores (id, name, location_id) VALUES ($1, $2, $3)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.name)
+            .bind(new_doc.location_id.as_deref())
+            .execute(&self.pool)
+            .await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn update_store(
+        &self,
+        update_entry: UpdateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: StorePatch = store_patch(self, update_entry, mode).await?;
+
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut store: Store = PostgresDB::lock_one::<StoreRow>(&mut tx, "stores", "store", &id)
+            .await?
+            .into();
+        check_version("store", &id, patch.version, store.version)?;
+        patch.apply(&mut store);
+
+        sqlx::query("UPDATE stores SET name = $2, location_id = $3, version = $4 WHERE id = $1")
+            .bind(PostgresDB::parse_id(&id)?)
+            .bind(&store.name)
+            .bind(store.location_id.as_deref())
+            .bind(store.version)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(store)
+    }
+
+    async fn delete_store(&self, deletion: Deletion) -> Result<Store, RepositoryError> {
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut store: Store =
+            PostgresDB::lock_one::<StoreRow>(&mut tx, "stores", "store", &deletion.id)
+                .await?
+                .into();
+        let id: String = PostgresDB::parse_id(&deletion.id)?;
+        self.check_assignments(&mut tx, std::slice::from_ref(&id))
+            .await?;
+
+        PostgresDB::mark_deleted(&mut tx, "stores", &id, &deletion).await?;
+        tx.commit().await?;
+        deletion.apply(&mut store);
+
+        Ok(store)
+    }
+
+    async fn restore_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        let deleted: Store = self.get_deleted_store(id).await?;
+        validate_restored_store(self, &self.policies, &deleted).await?;
+
+        self.restore_row::<StoreRow>("stores", "deleted store", id)
+            .await
+            .map(Store::from)
+    }
+
+    async fn get_all_stores(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+    ) -> Result<Vec<Store>, RepositoryError> {
+        let row_vec: Vec<StoreRow> = self
+            .find_all(
+                "stores",
+                |query| store_filter(query, filter),
+                store_sort(order_by),
+            )
+            .await?;
+
+        Ok(row_vec.into_iter().map(Store::from).collect())
+    }
+
+    async fn get_stores_by_locations(
+        &self,
+        location_ids: &[String],
+    ) -> Result<Vec<Store>, RepositoryError> {
+        let row_vec: Vec<StoreRow> = sqlx::query_as(
+            "SELECT * FROM stores WHERE location_id = ANY($1) AND deleted_at IS NULL ORDER BY id",
+        )
+        .bind(location_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(row_vec.into_iter().map(Store::from).collect())
+    }
+
+    async fn get_stores_by_ids(&self, ids: &[String]) -> Result<Vec<Store>, RepositoryError> {
+        let row_vec: Vec<StoreRow> = self.find_by_ids("stores", ids).await?;
+
+        Ok(row_vec.into_iter().map(Store::from).collect())
+    }
+
+    async fn get_store_page(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Store>, RepositoryError> {
+        let (row_vec, total_count): (Vec<StoreRow>, u64) = self
+            .find_page(
+                "stores",
+                |query| store_filter(query, filter),
+                store_sort(order_by),
+                request,
+            )
+            .await?;
+
+        Ok(Page::from_overfetch(
+            row_vec.into_iter().map(Store::from).collect(),
+            request,
+            total_count,
+        ))
+    }
+
+    async fn get_single_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        self.find_one::<StoreRow>("stores", "store", id)
+            .await
+            .map(Store::from)
+    }
+
+    async fn get_deleted_store(&self, id: &str) -> Result<Store, RepositoryError> {
+        self.find_deleted::<StoreRow>("stores", "deleted store", id)
+            .await
+            .map(Store::from)
+    }
+}
+
+/*
+ * Location Repository
+ */
+#[async_trait]
+impl LocationRepository for PostgresDB {
+    async fn create_location(
+        &self,
+        new_entry: CreateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError> {
+        let mut new_doc: Location = new_location(new_entry, mode)?;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query("INSERT INTO locations (id, country, state) VALUES ($1, $2, $3)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.country)
+            .bind(&new_doc.state)
+            .execute(&self.pool)
+            .await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn update_location(
+        &self,
+        update_entry: UpdateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: LocationPatch = location_patch(update_entry, mode)?;
+
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut location: Location =
+            PostgresDB::lock_one::<LocationRow>(&mut tx, "locations", "location", &id)
+                .await?
+                .into();
+        check_version("location", &id, patch.version, location.version)?;
+        patch.apply(&mut location);
+
+        sqlx::query("UPDATE locations SET country = $2, state = $3, version = $4 WHERE id = $1")
+            .bind(PostgresDB::parse_id(&id)?)
+            .bind(&location.country)
+            .bind(&location.state)
+            .bind(location.version)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(location)
+    }
+
+    async fn delete_location(&self, deletion: Deletion) -> Result<Location, RepositoryError> {
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut location: Location =
+            PostgresDB::lock_one::<LocationRow>(&mut tx, "locations", "location", &deletion.id)
+                .await?
+                .into();
+        let id: String = PostgresDB::parse_id(&deletion.id)?;
+
+        match self.policies.store_location {
+            OnDelete::Restrict => {
+                let dependents: Vec<String> = PostgresDB::dependent_ids(&mut tx, "SELECT id FROM stores WHERE location_id = $1 AND deleted_at IS NULL ORDER BY id", &id).await?;
+                if !dependents.is_empty() {
+                    return Err(RepositoryError::HasDependents {
+                        entity: "location",
+                        id: deletion.id,
+                        dependent_entity: "store",
+                        dependents,
+                    });
+                }
+            }
+            OnDelete::Cascade => {
+                let store_ids: Vec<String> = PostgresDB::dependent_ids(&mut tx, "SELECT id FROM stores WHERE location_id = $1 AND deleted_at IS NULL ORDER BY id", &id).await?;
+                if !store_ids.is_empty() {
+                    self.check_assignments(&mut tx, &store_ids).await?;
+                    PostgresDB::mark_dependents_deleted(
+                        &mut tx,
+                        "stores",
+                        "location_id = $1",
+                        &id,
+                        &deletion,
+                    )
+                    .await?;
+                }
+            }
+            OnDelete::Nullify => {}
+        }
+
+        PostgresDB::mark_deleted(&mut tx, "locations", &id, &deletion).await?;
+        tx.commit().await?;
+        deletion.apply(&mut location);
+
+        Ok(location)
+    }
+
+    /// Stores deleted along with the location, carrying the same stamp, come
+    /// back with it.
+    async fn restore_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        let valid_id: String = PostgresDB::parse_id(id)?;
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        sqlx::query(
+            "UPDATE stores SET deleted_at = NULL, deleted_by = NULL FROM locations WHERE locations.id = $1 AND locations.deleted_at IS NOT NULL AND stores.location_id = locations.id AND stores.deleted_at = locations.deleted_at AND stores.deleted_by IS NOT DISTINCT FROM locations.deleted_by")
+            .bind(&valid_id)
+            .execute(&mut tx)
+            .await?;
+        let location: LocationRow = sqlx::query_as(
+            "UPDATE locations SET deleted_at = NULL, deleted_by = NULL WHERE id = $1 AND deleted_at IS NOT NULL RETURNING *")
+            .bind(&valid_id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("deleted location", id))?;
+        tx.commit().await?;
+
+        Ok(location.into())
+    }
+
+    async fn get_all_locations(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+    ) -> Result<Vec<Location>, RepositoryError> {
+        let row_vec: Vec<LocationRow> = self
+            .find_all(
+                "locations",
+                |query| location_filter(query, filter),
+                location_sort(order_by),
+            )
+            .await?;
+
+        Ok(row_vec.into_iter().map(Location::from).collect())
+    }
+
+    async fn get_locations_by_ids(&self, ids: &[String]) -> Result<Vec<Location>, RepositoryError> {
+        let row_vec: Vec<LocationRow> = self.find_by_ids("locations", ids).await?;
+
+        Ok(row_vec.into_iter().map(Location::from).collect())
+    }
+
+    async fn get_location_page(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Location>, RepositoryError> {
+        let (row_vec, total_count): (Vec<LocationRow>, u64) = self
+            .find_page(
+                "locations",
+                |query| location_filter(query, filter),
+                location_sort(order_by),
+                request,
+            )
+            .await?;
+
+        Ok(Page::from_overfetch(
+            row_vec.into_iter().map(Location::from).collect(),
+            request,
+            total_count,
+        ))
+    }
+
+    async fn get_single_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        self.find_one::<LocationRow>("locations", "location", id)
+            .await
+            .map(Location::from)
+    }
+
+    async fn get_deleted_location(&self, id: &str) -> Result<Location, RepositoryError> {
+        self.find_deleted::<LocationRow>("locations", "deleted location", id)
+            .await
+            .map(Location::from)
+    }
+}
+
+/*
+ * Rank Repository
+ */
+#[async_trait]
+impl RankRepository for PostgresDB {
+    async fn create_rank(
+        &self,
+        new_entry: CreateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError> {
+        let mut new_doc: Rank = new_rank(new_entry, mode)?;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query(
+            "INSERT INTO ranks (id, name, description, permissions) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(obj_id.to_hex())
+        .bind(&new_doc.name)
+        .bind(&new_doc.description)
+        .bind(
+            new_doc
+                .permissions
+                .iter()
+                .map(encode)
+                .collect::<Vec<String>>(),
+        )
+        .execute(&self.pool)
+        .await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn update_rank(
+        &self,
+        update_entry: UpdateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: RankPatch = rank_patch(update_entry, mode)?;
+
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut rank: Rank = PostgresDB::lock_one::<RankRow>(&mut tx, "ranks", "rank", &id)
+            .await?
+            .try_into()?;
+        check_version("rank", &id, patch.version, rank.version)?;
+        patch.apply(&mut rank);
+
+        sqlx::query("UPDATE ranks SET name = $2, description = $3, permissions = $4, version = $5 WHERE id = $1")
+            .bind(PostgresDB::parse_id(&id)?)
+            .bind(&rank.name)
+            .bind(&rank.description)
+            .bind(rank.permissions.iter().map(encode).collect::<Vec<String>>())
+            .bind(rank.version)
+            .execute(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(rank)
+    }
+
+    async fn delete_rank(&self, deletion: Deletion) -> Result<Rank, RepositoryError> {
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut rank: Rank =
+            PostgresDB::lock_one::<RankRow>(&mut tx, "ranks", "rank", &deletion.id)
+                .await?
+                .try_into()?;
+        let id: String = PostgresDB::parse_id(&deletion.id)?;
+
+        if self.policies.employee_rank == OnDelete::Restrict {
+            let dependents: Vec<String> = PostgresDB::dependent_ids(
+                &mut tx,
+                "SELECT id FROM employees WHERE rank_id = $1 AND deleted_at IS NULL ORDER BY id",
+                &id,
+            )
+            .await?;
+            if !dependents.is_empty() {
+                return Err(RepositoryError::HasDependents {
+                    entity: "rank",
+                    id: deletion.id,
+                    dependent_entity: "employee",
+                    dependents,
+                });
+            }
+        }
+
+        PostgresDB::mark_deleted(&mut tx, "ranks", &id, &deletion).await?;
+        tx.commit().await?;
+        deletion.apply(&mut rank);
+
+        Ok(rank)
+    }
+
+    async fn restore_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        self.restore_row::<RankRow>("ranks", "deleted rank", id)
+            .await?
+            .try_into()
+    }
+
+    async fn get_all_ranks(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+    ) -> Result<Vec<Rank>, RepositoryError> {
+        let row_vec: Vec<RankRow> = self
+            .find_all(
+                "ranks",
+                |query| rank_filter(query, filter),
+                rank_sort(order_by),
+            )
+            .await?;
+
+        row_vec.into_iter().map(Rank::try_from).collect()
+    }
+
+    async fn get_ranks_by_ids(&self, ids: &[String]) -> Result<Vec<Rank>, RepositoryError> {
+        let row_vec: Vec<RankRow> = self.find_by_ids("ranks", ids).await?;
+
+        row_vec.into_iter().map(Rank::try_from).collect()
+    }
+
+    async fn get_rank_page(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Rank>, RepositoryError> {
+        let (row_vec, total_count): (Vec<RankRow>, u64) = self
+            .find_page(
+                "ranks",
+                |query| rank_filter(query, filter),
+                rank_sort(order_by),
+                request,
+            )
+            .await?;
+        let rank_vec: Vec<Rank> = row_vec
+            .into_iter()
+            .map(Rank::try_from)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Page::from_overfetch(rank_vec, request, total_count))
+    }
+
+    async fn get_single_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        self.find_one::<RankRow>("ranks", "rank", id)
+            .await?
+            .try_into()
+    }
+
+    async fn get_deleted_rank(&self, id: &str) -> Result<Rank, RepositoryError> {
+        self.find_deleted::<RankRow>("ranks", "deleted rank", id)
+            .await?
+            .try_into()
+    }
+}
+
+/*
+ * Shift Repository
+ */
+impl PostgresDB {
+    async fn find_shifts(
+        &self,
+        column: &str,
+        ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        let row_vec: Vec<ShiftRow> = sqlx::query_as(&format!(
+            "SELECT * FROM shifts WHERE {} = ANY($1) AND ($2::TIMESTAMPTZ IS NULL OR ends_at > $2) AND ($3::TIMESTAMPTZ IS NULL OR starts_at < $3){} ORDER BY starts_at, id",
+            column, PostgresDB::live("shifts")))
+            .bind(ids)
+            .bind(range.from.map(DateTime::to_chrono))
+            .bind(range.to.map(DateTime::to_chrono))
+            .fetch_all(&self.pool).await?;
+
+        Ok(row_vec.into_iter().map(Shift::from).collect())
+    }
+}
+
+#[async_trait]
+impl ShiftRepository for PostgresDB {
+    async fn create_shift(&self, new_entry: CreateShift) -> Result<Shift, RepositoryError> {
+        let mut new_doc: Shift = new_shift(self, new_entry).await?;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query("INSERT INTO shifts (id, employee_id, store_id, starts_at, ends_at, required_rank_id) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.employee_id)
+            .bind(&new_doc.store_id)
+            .bind(new_doc.starts_at.to_chrono())
+            .bind(new_doc.ends_at.to_chrono())
+            .bind(&new_doc.required_rank_id)
+            .execute(&self.pool).await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn update_shift(&self, update_entry: UpdateShift) -> Result<Shift, RepositoryError> {
+        let id: String = update_entry.id.clone();
+        let patch: ShiftPatch = shift_patch(self, update_entry).await?;
+
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut shift: Shift = PostgresDB::lock_one::<ShiftRow>(&mut tx, "shifts", "shift", &id)
+            .await?
+            .into();
+        check_version("shift", &id, patch.version, shift.version)?;
+        patch.apply(&mut shift);
+
+        sqlx::query("UPDATE shifts SET employee_id = $2, store_id = $3, starts_at = $4, ends_at = $5, required_rank_id = $6, version = $7 WHERE id = $1")
+            .bind(PostgresDB::parse_id(&id)?)
+            .bind(&shift.employee_id)
+            .bind(&shift.store_id)
+            .bind(shift.starts_at.to_chrono())
+            .bind(shift.ends_at.to_chrono())
+            .bind(&shift.required_rank_id)
+            .bind(shift.version)
+            .execute(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(shift)
+    }
+
+    async fn delete_shift(&self, delete_entry: DeleteShift) -> Result<Shift, RepositoryError> {
+        let row: ShiftRow = sqlx::query_as("DELETE FROM shifts WHERE id = $1 RETURNING *")
+            .bind(PostgresDB::parse_id(&delete_entry.id)?)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| RepositoryError::not_found("shift", &delete_entry.id))?;
+
+        Ok(row.into())
+    }
+
+    async fn get_shifts_by_store(
+        &self,
+        store_id: &str,
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        self.find_shifts("store_id", &[String::from(store_id)], range)
+            .await
+    }
+
+    async fn get_shifts_by_employees(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError> {
+        self.find_shifts("employee_id", employee_ids, range).await
+    }
+
+    async fn get_single_shift(&self, id: &str) -> Result<Shift, RepositoryError> {
+        self.find_one::<ShiftRow>("shifts", "shift", id)
+            .await
+            .map(Shift::from)
+    }
+}
+
+/*
+ * Absence Repository
+ */
+#[async_trait]
+impl AbsenceRepository for PostgresDB {
+    async fn request_absence(&self, new_entry: RequestAbsence) -> Result<Absence, RepositoryError> {
+        let mut new_doc: Absence = new_absence(self, new_entry).await?;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query("INSERT INTO absences (id, employee_id, kind, starts_on, ends_on, reason, state, requested_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.employee_id)
+            .bind(encode(&new_doc.kind))
+            .bind(new_doc.starts_on)
+            .bind(new_doc.ends_on)
+            .bind(&new_doc.reason)
+            .bind(new_doc.state.as_str())
+            .bind(new_doc.requested_at.to_chrono())
+            .execute(&self.pool).await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn decide_absence(&self, decision: AbsenceDecision) -> Result<Absence, RepositoryError> {
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let mut absence: Absence =
+            PostgresDB::lock_one::<AbsenceRow>(&mut tx, "absences", "absence", &decision.id)
+                .await?
+                .try_into()?;
+        decision.check(&absence)?;
+
+        sqlx::query(
+            "UPDATE absences SET state = $2, decided_by = $3, decided_at = $4, version = $5 WHERE id = $1",
+        )
+        .bind(PostgresDB::parse_id(&decision.id)?)
+        .bind(decision.state.as_str())
+        .bind(&decision.decided_by)
+        .bind(decision.decided_at.to_chrono())
+        .bind(absence.version + 1)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        decision.apply(&mut absence);
+
+        Ok(absence)
+    }
+
+    async fn get_absences(&self, filter: &AbsenceFilter) -> Result<Vec<Absence>, RepositoryError> {
+        let mut query: SqlQuery =
+            PostgresDB::select("absences", |query| absence_filter(query, filter));
+        query.push(PostgresDB::live("absences"));
+        query.push(" ORDER BY starts_on, id");
+        let row_vec: Vec<AbsenceRow> = query.build_query_as().fetch_all(&self.pool).await?;
+
+        row_vec.into_iter().map(Absence::try_from).collect()
+    }
+
+    async fn get_absences_by_employees(
+        &self,
+        employee_ids: &[String],
+    ) -> Result<Vec<Absence>, RepositoryError> {
+        let row_vec: Vec<AbsenceRow> = sqlx::query_as(&format!(
+            "SELECT * FROM absences WHERE employee_id = ANY($1){} ORDER BY starts_on, id",
+            PostgresDB::live("absences")
+        ))
+        .bind(employee_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        row_vec.into_iter().map(Absence::try_from).collect()
+    }
+
+    async fn get_approved_absences_on(
+        &self,
+        employee_ids: &[String],
+        day: NaiveDate,
+    ) -> Result<Vec<Absence>, RepositoryError> {
+        let row_vec: Vec<AbsenceRow> = sqlx::query_as(&format!(
+            "SELECT * FROM absences WHERE employee_id = ANY($1) AND state = $2 AND starts_on <= $3 AND ends_on >= $3{} ORDER BY starts_on, id",
+            PostgresDB::live("absences")))
+            .bind(employee_ids)
+            .bind(AbsenceState::Approved.as_str())
+            .bind(day)
+            .fetch_all(&self.pool).await?;
+
+        row_vec.into_iter().map(Absence::try_from).collect()
+    }
+
+    async fn get_single_absence(&self, id: &str) -> Result<Absence, RepositoryError> {
+        self.find_one::<AbsenceRow>("absences", "absence", id)
+            .await?
+            .try_into()
+    }
+}
+
+/*
+ * Status History Repository
+ */
+#[async_trait]
+impl StatusHistoryRepository for PostgresDB {
+    async fn record_status_transition(
+        &self,
+        transition: StatusTransition,
+    ) -> Result<StatusTransition, RepositoryError> {
+        let mut new_doc: StatusTransition = transition;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query("INSERT INTO status_transitions (id, employee_id, previous_status, status, changed_at, changed_by) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.employee_id)
+            .bind(new_doc.previous_status.as_ref().map(encode))
+            .bind(new_doc.status.as_ref().map(encode))
+            .bind(new_doc.changed_at.to_chrono())
+            .bind(&new_doc.changed_by)
+            .execute(&self.pool).await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn get_status_history(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<StatusTransition>, RepositoryError> {
+        let row_vec: Vec<StatusTransitionRow> = sqlx::query_as(
+            "SELECT * FROM status_transitions WHERE employee_id = ANY($1) AND ($2::TIMESTAMPTZ IS NULL OR changed_at >= $2) AND ($3::TIMESTAMPTZ IS NULL OR changed_at < $3) ORDER BY changed_at, id")
+            .bind(employee_ids)
+            .bind(range.from.map(DateTime::to_chrono))
+            .bind(range.to.map(DateTime::to_chrono))
+            .fetch_all(&self.pool).await?;
+
+        row_vec
+            .into_iter()
+            .map(StatusTransition::try_from)
+            .collect()
+    }
+}
+
+/*
+ * Audit Repository
+ */
+#[async_trait]
+impl AuditRepository for PostgresDB {
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, RepositoryError> {
+        let mut new_doc: AuditEntry = entry;
+        let obj_id: ObjectId = ObjectId::new();
+
+        sqlx::query("INSERT INTO audit_entries (id, actor, operation, entity_type, entity_id, at, diff, client_ip) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+            .bind(obj_id.to_hex())
+            .bind(&new_doc.actor)
+            .bind(&new_doc.operation)
+            .bind(&new_doc.entity_type)
+            .bind(&new_doc.entity_id)
+            .bind(new_doc.at.to_chrono())
+            .bind(Bson::Document(new_doc.diff.clone()).into_relaxed_extjson().to_string())
+            .bind(&new_doc.client_ip)
+            .execute(&self.pool).await?;
+        new_doc.id = Some(obj_id);
+
+        Ok(new_doc)
+    }
+
+    async fn get_audit_page(
+        &self,
+        filter: &AuditFilter,
+        request: &PageRequest,
+    ) -> Result<Page<AuditEntry>, RepositoryError> {
+        let (row_vec, total_count): (Vec<AuditRow>, u64) = self
+            .find_page(
+                "audit_entries",
+                |query| audit_filter(query, filter),
+                None,
+                request,
+            )
+            .await?;
+        let entry_vec: Vec<AuditEntry> = row_vec
+            .into_iter()
+            .map(AuditEntry::try_from)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Page::from_overfetch(entry_vec, request, total_count))
+    }
+}
+
+/*
+ * Purge Repository
+ */
+impl PostgresDB {
+    /// Removes the rows of a table marked deleted before the cutoff. The
+    /// references other rows hold to them are cleared with `detach` first.
+    async fn purge(
+        tx: &mut Transaction<'_, Postgres>,
+        table: &str,
+        detach: Option<&str>,
+        cutoff: chrono::DateTime<Utc>,
+    ) -> Result<u64, RepositoryError> {
+        let expired: String = format!("SELECT id FROM {} WHERE deleted_at < $1", table);
+        if let Some(detach) = detach {
+            sqlx::query(&format!("{} IN ({})", detach, expired))
+                .bind(cutoff)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let purged = sqlx::query(&format!("DELETE FROM {} WHERE deleted_at < $1", table))
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(purged.rows_affected())
+    }
+}
+
+#[async_trait]
+impl PurgeRepository for PostgresDB {
+    async fn purge_deleted(
+        &self,
+        deleted_before: DateTime,
+    ) -> Result<PurgeReport, RepositoryError> {
+        let cutoff: chrono::DateTime<Utc> = deleted_before.to_chrono();
+        let mut tx: Transaction<'_, Postgres> = self.pool.begin().await?;
+        let report: PurgeReport = PurgeReport {
+            employees: PostgresDB::purge(&mut tx, "employees", None, cutoff).await?,
+            stores: PostgresDB::purge(
+                &mut tx,
+                "stores",
+                Some("DELETE FROM employee_stores WHERE store_id"),
+                cutoff,
+            )
+            .await?,
+            ranks: PostgresDB::purge(
+                &mut tx,
+                "ranks",
+                Some("UPDATE employees SET rank_id = NULL WHERE rank_id"),
+                cutoff,
+            )
+            .await?,
+            locations: PostgresDB::purge(
+                &mut tx,
+                "locations",
+                Some("UPDATE stores SET location_id = NULL WHERE location_id"),
+                cutoff,
+            )
+            .await?,
+        };
+        tx.commit().await?;
+
+        Ok(report)
+    }
+}
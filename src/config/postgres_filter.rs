@@ -0,0 +1,187 @@
+use crate::config::{mongo_filter::SortKey, repository::today};
+use crate::schema::project_schema::{
+    AbsenceFilter, AbsenceState, AuditFilter, EmployeeFilter, LocationFilter, RankFilter, Status,
+    StoreFilter,
+};
+use chrono::NaiveDate;
+use sqlx::{Postgres, QueryBuilder};
+
+/// A query under construction. Filters append `AND` conditions, so the query
+/// must already end in a `WHERE` clause.
+pub type SqlQuery = QueryBuilder<'static, Postgres>;
+
+/// The expression a listing is ordered by. Missing values sort as the empty
+/// string and text is compared bytewise, which matches how MongoDB orders
+/// the same documents.
+pub fn sort_expression(key: SortKey) -> String {
+    format!("COALESCE({}, '') COLLATE \"C\"", key.field)
+}
+
+/// Appends `ORDER BY` for the given sort, with `id` as the tie-breaker in the
+/// same direction. Paging backwards walks the order in reverse.
+pub fn push_order(query: &mut SqlQuery, sort: Option<SortKey>, backward: bool) {
+    let direction: &str = if sort.is_none_or(|key| key.ascending) != backward {
+        "ASC"
+    } else {
+        "DESC"
+    };
+
+    match sort {
+        Some(key) => query.push(format!(
+            " ORDER BY {} {}, id {}",
+            sort_expression(key),
+            direction,
+            direction
+        )),
+        None => query.push(format!(" ORDER BY id {}", direction)),
+    };
+}
+
+fn escape_like(input: &str) -> String {
+    let mut escaped: String = String::with_capacity(input.len());
+    for c in input.chars() {
+        if "\\%_".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Leaves out rows marked deleted unless they were asked for.
+fn exclude_deleted(query: &mut SqlQuery, include_deleted: bool) {
+    if !include_deleted {
+        query.push(" AND deleted_at IS NULL");
+    }
+}
+
+fn name_matches(query: &mut SqlQuery, pattern: String) {
+    query
+        .push(" AND (first_name ILIKE ")
+        .push_bind(pattern.clone())
+        .push(" OR last_name ILIKE ")
+        .push_bind(pattern)
+        .push(")");
+}
+
+/*
+ * Employee Filters
+ */
+pub fn employee_filter(query: &mut SqlQuery, filter: &EmployeeFilter) {
+    exclude_deleted(query, filter.include_deleted);
+    if let Some(status_in) = &filter.status_in {
+        let status_vec: Vec<&str> = status_in.iter().map(Status::as_str).collect();
+        let day: NaiveDate = today();
+        query
+            .push(" AND COALESCE((SELECT kind FROM absences WHERE employee_id = employees.id AND state = ")
+            .push_bind(AbsenceState::Approved.as_str())
+            .push(" AND starts_on <= ")
+            .push_bind(day)
+            .push(" AND ends_on >= ")
+            .push_bind(day)
+            .push(" LIMIT 1), status) = ANY(")
+            .push_bind(status_vec)
+            .push(")");
+    }
+    if let Some(store_id) = &filter.store_id {
+        query.push(" AND EXISTS (SELECT 1 FROM employee_stores WHERE employee_id = employees.id AND store_id = ")
+            .push_bind(store_id.clone())
+            .push(")");
+    }
+    if let Some(rank_id) = &filter.rank_id {
+        query.push(" AND rank_id = ").push_bind(rank_id.clone());
+    }
+    if let Some(name_prefix) = &filter.name_prefix {
+        name_matches(query, format!("{}%", escape_like(name_prefix)));
+    }
+    if let Some(name_contains) = &filter.name_contains {
+        name_matches(query, format!("%{}%", escape_like(name_contains)));
+    }
+}
+
+/*
+ * Store Filters
+ */
+pub fn store_filter(query: &mut SqlQuery, filter: &StoreFilter) {
+    exclude_deleted(query, filter.include_deleted);
+    if let Some(location_id) = &filter.location_id {
+        query
+            .push(" AND location_id = ")
+            .push_bind(location_id.clone());
+    }
+    if let Some(name) = &filter.name {
+        query.push(" AND name = ").push_bind(name.clone());
+    }
+}
+
+/*
+ * Location Filters
+ */
+pub fn location_filter(query: &mut SqlQuery, filter: &LocationFilter) {
+    exclude_deleted(query, filter.include_deleted);
+    if let Some(country) = &filter.country {
+        query.push(" AND country = ").push_bind(country.clone());
+    }
+    if let Some(state) = &filter.state {
+        query.push(" AND state = ").push_bind(state.clone());
+    }
+}
+
+/*
+ * Rank Filters
+ */
+pub fn rank_filter(query: &mut SqlQuery, filter: &RankFilter) {
+    exclude_deleted(query, filter.include_deleted);
+    if let Some(name) = &filter.name {
+        query.push(" AND name = ").push_bind(name.clone());
+    }
+}
+
+/*
+ * Absence Filters
+ */
+pub fn absence_filter(query: &mut SqlQuery, filter: &AbsenceFilter) {
+    if let Some(employee_id) = &filter.employee_id {
+        query
+            .push(" AND employee_id = ")
+            .push_bind(employee_id.clone());
+    }
+    if let Some(state_in) = &filter.state_in {
+        let state_vec: Vec<&str> = state_in.iter().map(AbsenceState::as_str).collect();
+        query
+            .push(" AND state = ANY(")
+            .push_bind(state_vec)
+            .push(")");
+    }
+    if let Some(from) = filter.from {
+        query.push(" AND ends_on >= ").push_bind(from);
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND starts_on <= ").push_bind(to);
+    }
+}
+
+/*
+ * Audit Filters
+ */
+pub fn audit_filter(query: &mut SqlQuery, filter: &AuditFilter) {
+    for (column, value) in [
+        ("actor", &filter.actor),
+        ("operation", &filter.operation),
+        ("entity_type", &filter.entity_type),
+        ("entity_id", &filter.entity_id),
+    ] {
+        if let Some(value) = value {
+            query
+                .push(format!(" AND {} = ", column))
+                .push_bind(value.clone());
+        }
+    }
+    if let Some(from) = filter.from {
+        query.push(" AND at >= ").push_bind(from.to_chrono());
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND at < ").push_bind(to.to_chrono());
+    }
+}
@@ -0,0 +1,1442 @@
+use crate::config::{
+    error::{optional, RepositoryError},
+    integrity::{DeletePolicies, OnDelete},
+    pagination::{Page, PageRequest},
+    validation::{ValidationMode, Validator},
+};
+use crate::schema::project_schema::{
+    Absence, AbsenceFilter, AbsenceState, AuditEntry, AuditFilter, CreateEmployee, CreateLocation,
+    CreateRank, CreateShift, CreateStore, DecideAbsence, DeleteShift, Employee, EmployeeFilter,
+    EmployeeOrderBy, Location, LocationFilter, LocationOrderBy, Permission, Rank, RankFilter,
+    RankOrderBy, RequestAbsence, Shift, Status, StatusTransition, Store, StoreFilter, StoreOrderBy,
+    TimeInStatus, UpdateEmployee, UpdateLocation, UpdateRank, UpdateShift, UpdateStore,
+    FIRST_VERSION,
+};
+use async_graphql::MaybeUndefined;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use mongodb::bson::DateTime;
+use std::{collections::HashMap, env, sync::Arc};
+
+/*
+ * Repository Traits
+ *
+ * Deleting an employee, store, location or rank only marks it deleted.
+ * Marked entries are left out of every lookup and listing, except listings
+ * that ask for them, until they are restored or purged.
+ */
+#[async_trait]
+pub trait EmployeeRepository: Send + Sync {
+    async fn create_employee(
+        &self,
+        new_entry: CreateEmployee,
+        mode: ValidationMode,
+    ) -> Result<Employee, RepositoryError>;
+    /// Returns the employee as it was right before the update and as the
+    /// update left it, both taken from the write itself, so that no other
+    /// update can slip in between.
+    async fn update_employee(
+        &self,
+        update_entry: UpdateEmployee,
+        mode: ValidationMode,
+    ) -> Result<(Employee, Employee), RepositoryError>;
+    async fn delete_employee(&self, deletion: Deletion) -> Result<Employee, RepositoryError>;
+    async fn restore_employee(&self, id: &str) -> Result<Employee, RepositoryError>;
+    async fn get_all_employees(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+    ) -> Result<Vec<Employee>, RepositoryError>;
+    /// The employees assigned to any of the given stores.
+    async fn get_employees_by_stores(
+        &self,
+        store_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError>;
+    /// The employees holding any of the given ranks.
+    async fn get_employees_by_ranks(
+        &self,
+        rank_ids: &[String],
+    ) -> Result<Vec<Employee>, RepositoryError>;
+    async fn get_employees_by_ids(&self, ids: &[String]) -> Result<Vec<Employee>, RepositoryError>;
+    async fn get_employee_page(
+        &self,
+        filter: &EmployeeFilter,
+        order_by: Option<&EmployeeOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Employee>, RepositoryError>;
+    async fn get_single_employee(&self, id: &str) -> Result<Employee, RepositoryError>;
+    /// An employee marked deleted, as it is until it is restored or purged.
+    async fn get_deleted_employee(&self, id: &str) -> Result<Employee, RepositoryError>;
+}
+
+#[async_trait]
+pub trait StoreRepository: Send + Sync {
+    async fn create_store(
+        &self,
+        new_entry: CreateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError>;
+    async fn update_store(
+        &self,
+        update_entry: UpdateStore,
+        mode: ValidationMode,
+    ) -> Result<Store, RepositoryError>;
+    async fn delete_store(&self, deletion: Deletion) -> Result<Store, RepositoryError>;
+    async fn restore_store(&self, id: &str) -> Result<Store, RepositoryError>;
+    async fn get_all_stores(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+    ) -> Result<Vec<Store>, RepositoryError>;
+    /// The stores at any of the given locations.
+    async fn get_stores_by_locations(
+        &self,
+        location_ids: &[String],
+    ) -> Result<Vec<Store>, RepositoryError>;
+    async fn get_stores_by_ids(&self, ids: &[String]) -> Result<Vec<Store>, RepositoryError>;
+    async fn get_store_page(
+        &self,
+        filter: &StoreFilter,
+        order_by: Option<&StoreOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Store>, RepositoryError>;
+    async fn get_single_store(&self, id: &str) -> Result<Store, RepositoryError>;
+    async fn get_deleted_store(&self, id: &str) -> Result<Store, RepositoryError>;
+}
+
+#[async_trait]
+pub trait LocationRepository: Send + Sync {
+    async fn create_location(
+        &self,
+        new_entry: CreateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError>;
+    async fn update_location(
+        &self,
+        update_entry: UpdateLocation,
+        mode: ValidationMode,
+    ) -> Result<Location, RepositoryError>;
+    async fn delete_location(&self, deletion: Deletion) -> Result<Location, RepositoryError>;
+    async fn restore_location(&self, id: &str) -> Result<Location, RepositoryError>;
+    async fn get_all_locations(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+    ) -> Result<Vec<Location>, RepositoryError>;
+    async fn get_locations_by_ids(&self, ids: &[String]) -> Result<Vec<Location>, RepositoryError>;
+    async fn get_location_page(
+        &self,
+        filter: &LocationFilter,
+        order_by: Option<&LocationOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Location>, RepositoryError>;
+    async fn get_single_location(&self, id: &str) -> Result<Location, RepositoryError>;
+    async fn get_deleted_location(&self, id: &str) -> Result<Location, RepositoryError>;
+}
+
+#[async_trait]
+pub trait RankRepository: Send + Sync {
+    async fn create_rank(
+        &self,
+        new_entry: CreateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError>;
+    async fn update_rank(
+        &self,
+        update_entry: UpdateRank,
+        mode: ValidationMode,
+    ) -> Result<Rank, RepositoryError>;
+    async fn delete_rank(&self, deletion: Deletion) -> Result<Rank, RepositoryError>;
+    async fn restore_rank(&self, id: &str) -> Result<Rank, RepositoryError>;
+    async fn get_all_ranks(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+    ) -> Result<Vec<Rank>, RepositoryError>;
+    async fn get_ranks_by_ids(&self, ids: &[String]) -> Result<Vec<Rank>, RepositoryError>;
+    async fn get_rank_page(
+        &self,
+        filter: &RankFilter,
+        order_by: Option<&RankOrderBy>,
+        request: &PageRequest,
+    ) -> Result<Page<Rank>, RepositoryError>;
+    async fn get_single_rank(&self, id: &str) -> Result<Rank, RepositoryError>;
+    async fn get_deleted_rank(&self, id: &str) -> Result<Rank, RepositoryError>;
+}
+
+/// Shift listings only hold shifts overlapping the range, ordered by start.
+#[async_trait]
+pub trait ShiftRepository: Send + Sync {
+    async fn create_shift(&self, new_entry: CreateShift) -> Result<Shift, RepositoryError>;
+    async fn update_shift(&self, update_entry: UpdateShift) -> Result<Shift, RepositoryError>;
+    async fn delete_shift(&self, delete_entry: DeleteShift) -> Result<Shift, RepositoryError>;
+    async fn get_shifts_by_store(
+        &self,
+        store_id: &str,
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError>;
+    async fn get_shifts_by_employees(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<Shift>, RepositoryError>;
+    async fn get_single_shift(&self, id: &str) -> Result<Shift, RepositoryError>;
+}
+
+/// Absence listings are ordered by their first day.
+#[async_trait]
+pub trait AbsenceRepository: Send + Sync {
+    async fn request_absence(&self, new_entry: RequestAbsence) -> Result<Absence, RepositoryError>;
+    async fn decide_absence(&self, decision: AbsenceDecision) -> Result<Absence, RepositoryError>;
+    async fn get_absences(&self, filter: &AbsenceFilter) -> Result<Vec<Absence>, RepositoryError>;
+    /// Every absence of the given employees.
+    async fn get_absences_by_employees(
+        &self,
+        employee_ids: &[String],
+    ) -> Result<Vec<Absence>, RepositoryError>;
+    /// The approved absences of the given employees that cover `day`.
+    async fn get_approved_absences_on(
+        &self,
+        employee_ids: &[String],
+        day: NaiveDate,
+    ) -> Result<Vec<Absence>, RepositoryError>;
+    async fn get_single_absence(&self, id: &str) -> Result<Absence, RepositoryError>;
+}
+
+/// Status history is ordered by the time of the change.
+#[async_trait]
+pub trait StatusHistoryRepository: Send + Sync {
+    async fn record_status_transition(
+        &self,
+        transition: StatusTransition,
+    ) -> Result<StatusTransition, RepositoryError>;
+    /// The transitions of the given employees made within the range.
+    async fn get_status_history(
+        &self,
+        employee_ids: &[String],
+        range: TimeRange,
+    ) -> Result<Vec<StatusTransition>, RepositoryError>;
+}
+
+/// The audit log is append-only and ordered by ID, which follows the order
+/// the entries were written in.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, RepositoryError>;
+    async fn get_audit_page(
+        &self,
+        filter: &AuditFilter,
+        request: &PageRequest,
+    ) -> Result<Page<AuditEntry>, RepositoryError>;
+}
+
+/// Removes entries that were marked deleted before `deleted_before` for good.
+/// References other deleted entries still hold to them are cleared, and the
+/// shifts and absences of purged employees and stores go with them.
+#[async_trait]
+pub trait PurgeRepository: Send + Sync {
+    async fn purge_deleted(&self, deleted_before: DateTime)
+        -> Result<PurgeReport, RepositoryError>;
+}
+
+/// How many entries of each kind a purge removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub employees: u64,
+    pub stores: u64,
+    pub locations: u64,
+    pub ranks: u64,
+}
+
+/// A time window for shift and status history listings. An open end reaches
+/// as far as the entries do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TimeRange {
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+}
+
+impl TimeRange {
+    /// Whether a shift overlaps the range. Shifts are half-open, so one
+    /// ending exactly where the range starts does not.
+    pub fn overlaps(&self, shift: &Shift) -> bool {
+        self.from.is_none_or(|from| shift.ends_at > from)
+            && self.to.is_none_or(|to| shift.starts_at < to)
+    }
+
+    /// Whether a point in time lies within the range, `to` excluded.
+    pub fn contains(&self, at: DateTime) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at < to)
+    }
+}
+
+/// The repositories the resolvers work against, handed to the schema as
+/// `data`. Every backend implements all of the traits, so they usually share
+/// a single instance.
+#[derive(Clone)]
+pub struct Repositories {
+    pub employees: Arc<dyn EmployeeRepository>,
+    pub stores: Arc<dyn StoreRepository>,
+    pub locations: Arc<dyn LocationRepository>,
+    pub ranks: Arc<dyn RankRepository>,
+    pub shifts: Arc<dyn ShiftRepository>,
+    pub absences: Arc<dyn AbsenceRepository>,
+    pub status_history: Arc<dyn StatusHistoryRepository>,
+    pub audit: Arc<dyn AuditRepository>,
+    pub purge: Arc<dyn PurgeRepository>,
+}
+
+impl Repositories {
+    pub fn new<B>(backend: B) -> Self
+    where
+        B: EmployeeRepository
+            + StoreRepository
+            + LocationRepository
+            + RankRepository
+            + ShiftRepository
+            + AbsenceRepository
+            + StatusHistoryRepository
+            + AuditRepository
+            + PurgeRepository
+            + 'static,
+    {
+        let backend: Arc<B> = Arc::new(backend);
+        Repositories {
+            employees: backend.clone(),
+            stores: backend.clone(),
+            locations: backend.clone(),
+            ranks: backend.clone(),
+            shifts: backend.clone(),
+            absences: backend.clone(),
+            status_history: backend.clone(),
+            audit: backend.clone(),
+            purge: backend,
+        }
+    }
+}
+
+/// The storage the repositories are served from, set through
+/// `STORAGE_BACKEND`. The in-memory backend loses everything on shutdown and
+/// is meant for demos and tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    Mongo,
+    Postgres,
+    Memory,
+}
+
+impl StorageBackend {
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("STORAGE_BACKEND") {
+            Ok(value) => match value.to_ascii_lowercase().as_str() {
+                "mongo" | "" => Ok(StorageBackend::Mongo),
+                "postgres" => Ok(StorageBackend::Postgres),
+                "memory" => Ok(StorageBackend::Memory),
+                _ => Err(format!(
+                    "STORAGE_BACKEND must be 'mongo', 'postgres' or 'memory', got '{}'",
+                    value
+                )),
+            },
+            Err(_) => Ok(StorageBackend::Mongo),
+        }
+    }
+}
+
+/*
+ * Reference Validation
+ */
+pub async fn validate_store_vec<R>(
+    repository: &R,
+    validator: &mut Validator,
+    field: &str,
+    store_vec: &[String],
+) -> Result<Vec<String>, RepositoryError>
+where
+    R: StoreRepository + ?Sized,
+{
+    let known_ids: Vec<String> = repository
+        .get_stores_by_ids(store_vec)
+        .await?
+        .into_iter()
+        .filter_map(|store| store.id.map(|id| id.to_string()))
+        .collect();
+
+    let mut valid_store_vec: Vec<String> = Vec::new();
+    for (index, store_id) in store_vec.iter().enumerate() {
+        let item_field: String = format!("{}[{}]", field, index);
+        if !validator.object_id(&item_field, store_id) {
+            continue;
+        }
+
+        if known_ids.contains(store_id) {
+            valid_store_vec.push(String::from(store_id));
+        } else {
+            validator.unknown_reference(&item_field, "store", store_id);
+        }
+    }
+
+    Ok(valid_store_vec)
+}
+
+pub async fn validate_location<R>(
+    repository: &R,
+    validator: &mut Validator,
+    field: &str,
+    location_id: &str,
+) -> Result<Option<String>, RepositoryError>
+where
+    R: LocationRepository + ?Sized,
+{
+    if !validator.object_id(field, location_id) {
+        return Ok(None);
+    }
+
+    match optional(repository.get_single_location(location_id).await)? {
+        Some(_) => Ok(Some(String::from(location_id))),
+        None => {
+            validator.unknown_reference(field, "location", location_id);
+            Ok(None)
+        }
+    }
+}
+
+pub async fn validate_rank<R>(
+    repository: &R,
+    validator: &mut Validator,
+    field: &str,
+    rank_id: &str,
+) -> Result<Option<String>, RepositoryError>
+where
+    R: RankRepository + ?Sized,
+{
+    if !validator.object_id(field, rank_id) {
+        return Ok(None);
+    }
+
+    match optional(repository.get_single_rank(rank_id).await)? {
+        Some(_) => Ok(Some(String::from(rank_id))),
+        None => {
+            validator.unknown_reference(field, "rank", rank_id);
+            Ok(None)
+        }
+    }
+}
+
+pub async fn validate_employee<R>(
+    repository: &R,
+    validator: &mut Validator,
+    field: &str,
+    employee_id: &str,
+) -> Result<Option<Employee>, RepositoryError>
+where
+    R: EmployeeRepository + ?Sized,
+{
+    if !validator.object_id(field, employee_id) {
+        return Ok(None);
+    }
+
+    let employee: Option<Employee> = optional(repository.get_single_employee(employee_id).await)?;
+    if employee.is_none() {
+        validator.unknown_reference(field, "employee", employee_id);
+    }
+
+    Ok(employee)
+}
+
+pub async fn validate_store<R>(
+    repository: &R,
+    validator: &mut Validator,
+    field: &str,
+    store_id: &str,
+) -> Result<Option<String>, RepositoryError>
+where
+    R: StoreRepository + ?Sized,
+{
+    if !validator.object_id(field, store_id) {
+        return Ok(None);
+    }
+
+    match optional(repository.get_single_store(store_id).await)? {
+        Some(_) => Ok(Some(String::from(store_id))),
+        None => {
+            validator.unknown_reference(field, "store", store_id);
+            Ok(None)
+        }
+    }
+}
+
+/// `UNKNOWN` only describes stored values this version cannot read, so it
+/// is refused in either validation mode.
+pub fn validate_status(validator: &mut Validator, field: &str, status: Status) {
+    if status == Status::Unknown {
+        validator.add(field, "UNKNOWN cannot be written");
+    }
+}
+
+/*
+ * Validated Entries
+ *
+ * Backends only store what these functions hand them, so that validation
+ * behaves the same whichever backend is in use.
+ */
+pub async fn new_employee<R>(
+    repository: &R,
+    new_entry: CreateEmployee,
+    mode: ValidationMode,
+) -> Result<Employee, RepositoryError>
+where
+    R: StoreRepository + RankRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(mode);
+    validator.non_empty("firstName", &new_entry.first_name);
+    validator.non_empty("lastName", &new_entry.last_name);
+    if let Some(status) = new_entry.status {
+        validate_status(&mut validator, "status", status);
+    }
+    let validated_stores: Vec<String> = validate_store_vec(
+        repository,
+        &mut validator,
+        "stores",
+        &new_entry.stores.unwrap_or_default(),
+    )
+    .await?;
+    let validated_rank: Option<String> =
+        validate_rank(repository, &mut validator, "rankId", &new_entry.rank_id).await?;
+    validator.finish()?;
+
+    Ok(Employee {
+        id: None,
+        first_name: new_entry.first_name,
+        last_name: new_entry.last_name,
+        status: Some(new_entry.status.unwrap_or(Status::None)),
+        stores: Some(validated_stores),
+        rank_id: validated_rank,
+        version: FIRST_VERSION,
+        deleted_at: None,
+        deleted_by: None,
+    })
+}
+
+/// A validated employee update. References that did not resolve in lenient
+/// mode are left out, as if the client had not sent them.
+#[derive(Clone)]
+pub struct EmployeePatch {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub status: MaybeUndefined<Status>,
+    pub stores: MaybeUndefined<Vec<String>>,
+    pub add_stores: Vec<String>,
+    pub remove_stores: Vec<String>,
+    pub rank_id: MaybeUndefined<String>,
+    pub version: Option<i32>,
+}
+
+pub async fn employee_patch<R>(
+    repository: &R,
+    update_entry: UpdateEmployee,
+    mode: ValidationMode,
+) -> Result<EmployeePatch, RepositoryError>
+where
+    R: StoreRepository + RankRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(mode);
+
+    if let Some(first_name) = &update_entry.first_name {
+        validator.non_empty("firstName", first_name);
+    }
+    if let Some(last_name) = &update_entry.last_name {
+        validator.non_empty("lastName", last_name);
+    }
+    if let MaybeUndefined::Value(status) = update_entry.status {
+        validate_status(&mut validator, "status", status);
+    }
+
+    let rank_id: MaybeUndefined<String> = match update_entry.rank_id {
+        MaybeUndefined::Value(rank_id) => {
+            match validate_rank(repository, &mut validator, "rankId", &rank_id).await? {
+                Some(validated_rank) => MaybeUndefined::Value(validated_rank),
+                None => MaybeUndefined::Undefined,
+            }
+        }
+        other => other,
+    };
+
+    let edits_stores: bool =
+        update_entry.add_stores.is_some() || update_entry.remove_stores.is_some();
+    let stores: MaybeUndefined<Vec<String>> = match update_entry.stores {
+        MaybeUndefined::Undefined => MaybeUndefined::Undefined,
+        _ if edits_stores => {
+            validator.add(
+                "stores",
+                "cannot be replaced together with addStores or removeStores",
+            );
+            MaybeUndefined::Undefined
+        }
+        MaybeUndefined::Null => MaybeUndefined::Null,
+        MaybeUndefined::Value(stores) => MaybeUndefined::Value(
+            validate_store_vec(repository, &mut validator, "stores", &stores).await?,
+        ),
+    };
+    let add_stores: Vec<String> = match update_entry.add_stores {
+        Some(add_stores) => {
+            validate_store_vec(repository, &mut validator, "addStores", &add_stores).await?
+        }
+        None => vec![],
+    };
+    validator.finish()?;
+
+    Ok(EmployeePatch {
+        first_name: update_entry.first_name,
+        last_name: update_entry.last_name,
+        status: update_entry.status,
+        stores,
+        add_stores,
+        remove_stores: update_entry.remove_stores.unwrap_or_default(),
+        rank_id,
+        version: update_entry.version,
+    })
+}
+
+impl EmployeePatch {
+    /// Applies the patch the way the MongoDB backend does: removals first,
+    /// additions only for stores not assigned yet.
+    pub fn apply(self, employee: &mut Employee) {
+        if let Some(first_name) = self.first_name {
+            employee.first_name = first_name;
+        }
+        if let Some(last_name) = self.last_name {
+            employee.last_name = last_name;
+        }
+        match self.status {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => employee.status = None,
+            MaybeUndefined::Value(status) => employee.status = Some(status),
+        }
+        match self.stores {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => employee.stores = None,
+            MaybeUndefined::Value(stores) => employee.stores = Some(stores),
+        }
+        if let Some(stores) = employee.stores.as_mut() {
+            stores.retain(|store_id| !self.remove_stores.contains(store_id));
+        }
+        if !self.add_stores.is_empty() {
+            let stores: &mut Vec<String> = employee.stores.get_or_insert_with(Vec::new);
+            for store_id in self.add_stores {
+                if !stores.contains(&store_id) {
+                    stores.push(store_id);
+                }
+            }
+        }
+        match self.rank_id {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => employee.rank_id = None,
+            MaybeUndefined::Value(rank_id) => employee.rank_id = Some(rank_id),
+        }
+        employee.version += 1;
+    }
+}
+
+pub async fn new_store<R>(
+    repository: &R,
+    new_entry: CreateStore,
+    mode: ValidationMode,
+) -> Result<Store, RepositoryError>
+where
+    R: LocationRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(mode);
+    validator.non_empty("name", &new_entry.name);
+    let validated_location: Option<String> = validate_location(
+        repository,
+        &mut validator,
+        "locationId",
+        &new_entry.location_id,
+    )
+    .await?;
+    validator.finish()?;
+
+    Ok(Store {
+        id: None,
+        name: new_entry.name,
+        location_id: validated_location,
+        version: FIRST_VERSION,
+        deleted_at: None,
+        deleted_by: None,
+    })
+}
+
+pub struct StorePatch {
+    pub name: Option<String>,
+    pub location_id: Option<String>,
+    pub version: Option<i32>,
+}
+
+pub async fn store_patch<R>(
+    repository: &R,
+    update_entry: UpdateStore,
+    mode: ValidationMode,
+) -> Result<StorePatch, RepositoryError>
+where
+    R: LocationRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(mode);
+    if let Some(name) = &update_entry.name {
+        validator.non_empty("name", name);
+    }
+    let location_id: Option<String> = match update_entry.location_id {
+        Some(location_id) => {
+            validate_location(repository, &mut validator, "locationId", &location_id).await?
+        }
+        None => None,
+    };
+    validator.finish()?;
+
+    Ok(StorePatch {
+        name: update_entry.name,
+        location_id,
+        version: update_entry.version,
+    })
+}
+
+impl StorePatch {
+    pub fn apply(self, store: &mut Store) {
+        if let Some(name) = self.name {
+            store.name = name;
+        }
+        if let Some(location_id) = self.location_id {
+            store.location_id = Some(location_id);
+        }
+        store.version += 1;
+    }
+}
+
+pub fn new_location(
+    new_entry: CreateLocation,
+    mode: ValidationMode,
+) -> Result<Location, RepositoryError> {
+    let mut validator: Validator = Validator::new(mode);
+    let country: String = validator.country_code("country", &new_entry.country);
+    validator.non_empty("state", &new_entry.state);
+    validator.finish()?;
+
+    Ok(Location {
+        id: None,
+        country,
+        state: new_entry.state,
+        version: FIRST_VERSION,
+        deleted_at: None,
+        deleted_by: None,
+    })
+}
+
+pub struct LocationPatch {
+    pub country: Option<String>,
+    pub state: Option<String>,
+    pub version: Option<i32>,
+}
+
+pub fn location_patch(
+    update_entry: UpdateLocation,
+    mode: ValidationMode,
+) -> Result<LocationPatch, RepositoryError> {
+    let mut validator: Validator = Validator::new(mode);
+    let country: Option<String> = update_entry
+        .country
+        .map(|country| validator.country_code("country", &country));
+    if let Some(state) = &update_entry.state {
+        validator.non_empty("state", state);
+    }
+    validator.finish()?;
+
+    Ok(LocationPatch {
+        country,
+        state: update_entry.state,
+        version: update_entry.version,
+    })
+}
+
+impl LocationPatch {
+    pub fn apply(self, location: &mut Location) {
+        if let Some(country) = self.country {
+            location.country = country;
+        }
+        if let Some(state) = self.state {
+            location.state = state;
+        }
+        location.version += 1;
+    }
+}
+
+pub fn new_rank(new_entry: CreateRank, mode: ValidationMode) -> Result<Rank, RepositoryError> {
+    let mut validator: Validator = Validator::new(mode);
+    validator.non_empty("name", &new_entry.name);
+    validator.finish()?;
+
+    Ok(Rank {
+        id: None,
+        name: new_entry.name,
+        description: new_entry.description,
+        permissions: new_entry.permissions.unwrap_or_default(),
+        version: FIRST_VERSION,
+        deleted_at: None,
+        deleted_by: None,
+    })
+}
+
+pub struct RankPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub permissions: Option<Vec<Permission>>,
+    pub version: Option<i32>,
+}
+
+pub fn rank_patch(
+    update_entry: UpdateRank,
+    mode: ValidationMode,
+) -> Result<RankPatch, RepositoryError> {
+    let mut validator: Validator = Validator::new(mode);
+    if let Some(name) = &update_entry.name {
+        validator.non_empty("name", name);
+    }
+    validator.finish()?;
+
+    Ok(RankPatch {
+        name: update_entry.name,
+        description: update_entry.description,
+        permissions: update_entry.permissions,
+        version: update_entry.version,
+    })
+}
+
+impl RankPatch {
+    pub fn apply(self, rank: &mut Rank) {
+        if let Some(name) = self.name {
+            rank.name = name;
+        }
+        if let Some(description) = self.description {
+            rank.description = Some(description);
+        }
+        if let Some(permissions) = self.permissions {
+            rank.permissions = permissions;
+        }
+        rank.version += 1;
+    }
+}
+
+/*
+ * Versions
+ */
+/// Every update moves an entry to its next version. An update that names
+/// the version it was based on fails with `Conflict` once the entry has
+/// moved on, instead of overwriting the changes it has not seen.
+pub fn check_version(
+    entity: &'static str,
+    id: &str,
+    expected: Option<i32>,
+    actual: i32,
+) -> Result<(), RepositoryError> {
+    match expected {
+        Some(expected) if expected != actual => Err(RepositoryError::Conflict {
+            entity,
+            id: String::from(id),
+            expected,
+            actual,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/*
+ * Soft Deletion
+ */
+/// A deletion made on behalf of a principal.
+pub struct Deletion {
+    pub id: String,
+    pub deleted_by: Option<String>,
+    pub deleted_at: DateTime,
+}
+
+impl Deletion {
+    pub fn new(id: String, deleted_by: Option<String>) -> Self {
+        Deletion {
+            id,
+            deleted_by,
+            deleted_at: DateTime::now(),
+        }
+    }
+
+    /// Marks an entry deleted. Dependents that follow an entry under the
+    /// cascade policy are marked with the same deletion.
+    pub fn apply<T: SoftDeletable>(&self, entry: &mut T) {
+        entry.mark_deleted(Some(self.deleted_at), self.deleted_by.clone());
+    }
+}
+
+pub trait SoftDeletable {
+    fn deleted_at(&self) -> Option<DateTime>;
+    fn mark_deleted(&mut self, deleted_at: Option<DateTime>, deleted_by: Option<String>);
+
+    fn is_deleted(&self) -> bool {
+        self.deleted_at().is_some()
+    }
+
+    fn restore(&mut self) {
+        self.mark_deleted(None, None);
+    }
+}
+
+macro_rules! soft_deletable {
+    ($($entity:ty),*) => {$(
+        impl SoftDeletable for $entity {
+            fn deleted_at(&self) -> Option<DateTime> {
+                self.deleted_at
+            }
+
+            fn mark_deleted(&mut self, deleted_at: Option<DateTime>, deleted_by: Option<String>) {
+                self.deleted_at = deleted_at;
+                self.deleted_by = deleted_by;
+            }
+        }
+    )*};
+}
+
+soft_deletable!(Employee, Store, Location, Rank);
+
+/// Under `Restrict`, a deleted employee only comes back while its rank and
+/// stores are still there; restore them first otherwise. The other policies
+/// let it come back without them, as if they had been deleted after it.
+pub async fn validate_restored_employee<R>(
+    repository: &R,
+    policies: &DeletePolicies,
+    employee: &Employee,
+) -> Result<(), RepositoryError>
+where
+    R: StoreRepository + RankRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(ValidationMode::Strict);
+    if policies.employee_store == OnDelete::Restrict {
+        validate_store_vec(
+            repository,
+            &mut validator,
+            "stores",
+            employee.stores.as_deref().unwrap_or_default(),
+        )
+        .await?;
+    }
+    if let Some(rank_id) = employee
+        .rank_id
+        .as_deref()
+        .filter(|_| policies.employee_rank == OnDelete::Restrict)
+    {
+        validate_rank(repository, &mut validator, "rankId", rank_id).await?;
+    }
+
+    validator.finish()
+}
+
+/// A deleted store only comes back while its location is still there,
+/// unless the location would have been nullified anyway.
+pub async fn validate_restored_store<R>(
+    repository: &R,
+    policies: &DeletePolicies,
+    store: &Store,
+) -> Result<(), RepositoryError>
+where
+    R: LocationRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(ValidationMode::Strict);
+    if let Some(location_id) = store
+        .location_id
+        .as_deref()
+        .filter(|_| policies.store_location != OnDelete::Nullify)
+    {
+        validate_location(repository, &mut validator, "locationId", location_id).await?;
+    }
+
+    validator.finish()
+}
+
+/// Checks a shift as it is going to be stored: its references, that the
+/// employee works at the store and holds the required rank, and that the
+/// employee has no other shift overlapping it. Shifts are newer than lenient
+/// mode and are always validated strictly.
+async fn validate_shift<R>(repository: &R, shift: &Shift) -> Result<(), RepositoryError>
+where
+    R: EmployeeRepository + StoreRepository + RankRepository + ShiftRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(ValidationMode::Strict);
+    let ordered: bool = shift.ends_at > shift.starts_at;
+    if !ordered {
+        validator.add("endsAt", "must be after startsAt");
+    }
+
+    let employee: Option<Employee> =
+        validate_employee(repository, &mut validator, "employeeId", &shift.employee_id).await?;
+    let store_id: Option<String> =
+        validate_store(repository, &mut validator, "storeId", &shift.store_id).await?;
+    let rank_id: Option<String> = match &shift.required_rank_id {
+        Some(rank_id) => {
+            validate_rank(repository, &mut validator, "requiredRankId", rank_id).await?
+        }
+        None => None,
+    };
+
+    if let Some(employee) = employee {
+        if store_id.is_some()
+            && !employee
+                .stores
+                .as_ref()
+                .is_some_and(|stores| stores.contains(&shift.store_id))
+        {
+            validator.add(
+                "storeId",
+                format!(
+                    "employee '{}' is not assigned to store '{}'",
+                    shift.employee_id, shift.store_id
+                ),
+            );
+        }
+        if rank_id.is_some() && employee.rank_id != rank_id {
+            validator.add(
+                "requiredRankId",
+                format!(
+                    "employee '{}' does not hold rank '{}'",
+                    shift.employee_id,
+                    shift.required_rank_id.as_deref().unwrap_or_default()
+                ),
+            );
+        }
+        if ordered {
+            let range: TimeRange = TimeRange {
+                from: Some(shift.starts_at),
+                to: Some(shift.ends_at),
+            };
+            let overlapping: Vec<String> = repository
+                .get_shifts_by_employees(std::slice::from_ref(&shift.employee_id), range)
+                .await?
+                .into_iter()
+                .filter(|other| other.id != shift.id)
+                .filter_map(|other| other.id.map(|id| id.to_hex()))
+                .collect();
+            if !overlapping.is_empty() {
+                validator.add(
+                    "startsAt",
+                    format!(
+                        "overlaps shift {} of the same employee",
+                        overlapping.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+    validator.finish()
+}
+
+pub async fn new_shift<R>(repository: &R, new_entry: CreateShift) -> Result<Shift, RepositoryError>
+where
+    R: EmployeeRepository + StoreRepository + RankRepository + ShiftRepository + ?Sized,
+{
+    let shift: Shift = Shift {
+        id: None,
+        employee_id: new_entry.employee_id,
+        store_id: new_entry.store_id,
+        starts_at: new_entry.starts_at,
+        ends_at: new_entry.ends_at,
+        required_rank_id: new_entry.required_rank_id,
+        version: FIRST_VERSION,
+    };
+    validate_shift(repository, &shift).await?;
+
+    Ok(shift)
+}
+
+#[derive(Clone)]
+pub struct ShiftPatch {
+    pub employee_id: Option<String>,
+    pub store_id: Option<String>,
+    pub starts_at: Option<DateTime>,
+    pub ends_at: Option<DateTime>,
+    pub required_rank_id: MaybeUndefined<String>,
+    pub version: Option<i32>,
+}
+
+/// Fields of a shift depend on each other, so the patch is validated by
+/// applying it to the stored shift and checking the result.
+pub async fn shift_patch<R>(
+    repository: &R,
+    update_entry: UpdateShift,
+) -> Result<ShiftPatch, RepositoryError>
+where
+    R: EmployeeRepository + StoreRepository + RankRepository + ShiftRepository + ?Sized,
+{
+    let mut shift: Shift = repository.get_single_shift(&update_entry.id).await?;
+    let patch: ShiftPatch = ShiftPatch {
+        employee_id: update_entry.employee_id,
+        store_id: update_entry.store_id,
+        starts_at: update_entry.starts_at,
+        ends_at: update_entry.ends_at,
+        required_rank_id: update_entry.required_rank_id,
+        version: update_entry.version,
+    };
+    patch.clone().apply(&mut shift);
+    validate_shift(repository, &shift).await?;
+
+    Ok(patch)
+}
+
+impl ShiftPatch {
+    pub fn apply(self, shift: &mut Shift) {
+        if let Some(employee_id) = self.employee_id {
+            shift.employee_id = employee_id;
+        }
+        if let Some(store_id) = self.store_id {
+            shift.store_id = store_id;
+        }
+        if let Some(starts_at) = self.starts_at {
+            shift.starts_at = starts_at;
+        }
+        if let Some(ends_at) = self.ends_at {
+            shift.ends_at = ends_at;
+        }
+        match self.required_rank_id {
+            MaybeUndefined::Undefined => {}
+            MaybeUndefined::Null => shift.required_rank_id = None,
+            MaybeUndefined::Value(rank_id) => shift.required_rank_id = Some(rank_id),
+        }
+        shift.version += 1;
+    }
+}
+
+/// Checks a requested absence: the employee exists, the days are in order
+/// and none of them is already taken by a requested or approved absence of
+/// the same employee. Like shifts, absences are always validated strictly.
+pub async fn new_absence<R>(
+    repository: &R,
+    new_entry: RequestAbsence,
+) -> Result<Absence, RepositoryError>
+where
+    R: EmployeeRepository + AbsenceRepository + ?Sized,
+{
+    let mut validator: Validator = Validator::new(ValidationMode::Strict);
+    let ordered: bool = new_entry.ends_on >= new_entry.starts_on;
+    if !ordered {
+        validator.add("endsOn", "must not be before startsOn");
+    }
+
+    let employee: Option<Employee> = validate_employee(
+        repository,
+        &mut validator,
+        "employeeId",
+        &new_entry.employee_id,
+    )
+    .await?;
+    if employee.is_some() && ordered {
+        let filter: AbsenceFilter = AbsenceFilter {
+            employee_id: Some(new_entry.employee_id.clone()),
+            state_in: Some(vec![AbsenceState::Requested, AbsenceState::Approved]),
+            from: Some(new_entry.starts_on),
+            to: Some(new_entry.ends_on),
+        };
+        let overlapping: Vec<String> = repository
+            .get_absences(&filter)
+            .await?
+            .into_iter()
+            .filter_map(|other| other.id.map(|id| id.to_hex()))
+            .collect();
+        if !overlapping.is_empty() {
+            validator.add(
+                "startsOn",
+                format!(
+                    "overlaps absence {} of the same employee",
+                    overlapping.join(", ")
+                ),
+            );
+        }
+    }
+    validator.finish()?;
+
+    Ok(Absence {
+        id: None,
+        employee_id: new_entry.employee_id,
+        kind: new_entry.kind,
+        starts_on: new_entry.starts_on,
+        ends_on: new_entry.ends_on,
+        reason: new_entry.reason.filter(|reason| !reason.trim().is_empty()),
+        state: AbsenceState::Requested,
+        requested_at: DateTime::now(),
+        decided_by: None,
+        decided_at: None,
+        version: FIRST_VERSION,
+    })
+}
+
+/// Moves an absence to a new state, recording who decided and when.
+/// Backends check the move against the stored absence and only write it if
+/// the absence is still in the state it was checked in.
+pub struct AbsenceDecision {
+    pub id: String,
+    pub state: AbsenceState,
+    pub version: Option<i32>,
+    pub decided_by: Option<String>,
+    pub decided_at: DateTime,
+}
+
+impl AbsenceDecision {
+    pub fn new(input: DecideAbsence, state: AbsenceState, decided_by: Option<String>) -> Self {
+        AbsenceDecision {
+            id: input.id,
+            state,
+            version: input.version,
+            decided_by,
+            decided_at: DateTime::now(),
+        }
+    }
+
+    pub fn check(&self, absence: &Absence) -> Result<(), RepositoryError> {
+        check_version("absence", &self.id, self.version, absence.version)?;
+        if absence.state.can_become(self.state) {
+            Ok(())
+        } else {
+            Err(RepositoryError::invalid_field(
+                "state",
+                format!(
+                    "a {} absence cannot become {}",
+                    absence.state.as_str().to_lowercase(),
+                    self.state.as_str().to_lowercase()
+                ),
+            ))
+        }
+    }
+
+    pub fn apply(self, absence: &mut Absence) {
+        absence.state = self.state;
+        absence.decided_by = self.decided_by;
+        absence.decided_at = Some(self.decided_at);
+        absence.version += 1;
+    }
+}
+
+/*
+ * Status Reporting
+ */
+/// The day approved absences are checked against to derive an employee's
+/// status. Days follow UTC, whatever the time zone of the site.
+pub fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// The status each employee has while one of the given approved absences
+/// covers the day, keyed by employee ID. Approved absences never overlap, so
+/// there is at most one per employee.
+pub fn absent_statuses(absences: &[Absence]) -> HashMap<String, Status> {
+    absences
+        .iter()
+        .map(|absence| (absence.employee_id.clone(), absence.kind.status()))
+        .collect()
+}
+
+/// The status an employee shows: the one of an approved absence covering the
+/// day, the stored status otherwise.
+pub fn derived_status(absent: &HashMap<String, Status>, employee: &Employee) -> Option<Status> {
+    employee
+        .id
+        .and_then(|id| absent.get(&id.to_hex()).copied())
+        .or(employee.status)
+}
+
+impl StatusTransition {
+    pub fn new(
+        employee_id: String,
+        previous_status: Option<Status>,
+        status: Option<Status>,
+        changed_by: Option<String>,
+    ) -> Self {
+        StatusTransition {
+            id: None,
+            employee_id,
+            previous_status,
+            status,
+            changed_at: DateTime::now(),
+            changed_by,
+        }
+    }
+}
+
+/// Adds up how long the given employees spent in each status within
+/// `from`..`to`, leaving out time that has not passed yet. `history` holds
+/// each employee's transitions up to `to`, oldest first.
+///
+/// Before its first transition an employee counts as being in that
+/// transition's previous status, if it has one. Employees without any
+/// history count as having had their current status all along. A missing
+/// status counts as `NONE`.
+pub fn time_in_status(
+    employees: &[Employee],
+    history: &[StatusTransition],
+    from: DateTime,
+    to: DateTime,
+) -> Vec<TimeInStatus> {
+    let end: i64 = to
+        .timestamp_millis()
+        .min(DateTime::now().timestamp_millis());
+    let start: i64 = from.timestamp_millis();
+    let statuses: Vec<Status> = Status::KNOWN
+        .iter()
+        .chain([&Status::Unknown])
+        .copied()
+        .collect();
+    let mut millis: Vec<i64> = vec![0; statuses.len()];
+    let mut add = |status: Option<Status>, since: i64, until: i64| {
+        let status: Status = status.unwrap_or(Status::None);
+        let overlap: i64 = until.min(end) - since.max(start);
+        if overlap > 0 {
+            let index: usize = statuses
+                .iter()
+                .position(|known| *known == status)
+                .unwrap_or_default();
+            millis[index] += overlap;
+        }
+    };
+
+    for employee in employees {
+        let employee_id: String = employee.id.map(|id| id.to_hex()).unwrap_or_default();
+        let transitions: Vec<&StatusTransition> = history
+            .iter()
+            .filter(|transition| transition.employee_id == employee_id)
+            .collect();
+
+        match transitions.first() {
+            None => add(employee.status, i64::MIN, i64::MAX),
+            Some(first) if first.previous_status.is_some() => add(
+                first.previous_status,
+                i64::MIN,
+                first.changed_at.timestamp_millis(),
+            ),
+            Some(_) => {}
+        }
+        for (index, transition) in transitions.iter().enumerate() {
+            let until: i64 = transitions
+                .get(index + 1)
+                .map_or(i64::MAX, |next| next.changed_at.timestamp_millis());
+            add(
+                transition.status,
+                transition.changed_at.timestamp_millis(),
+                until,
+            );
+        }
+    }
+
+    statuses
+        .into_iter()
+        .zip(millis)
+        .filter(|(_, millis)| *millis > 0)
+        .map(|(status, millis)| TimeInStatus {
+            status,
+            seconds: millis / 1000,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::time_in_status;
+    use crate::schema::project_schema::{
+        Employee, Status, StatusTransition, TimeInStatus, FIRST_VERSION,
+    };
+    use mongodb::bson::{oid::ObjectId, DateTime};
+
+    const HOUR: i64 = 3_600_000;
+
+    fn employee(status: Option<Status>) -> Employee {
+        Employee {
+            id: Some(ObjectId::new()),
+            first_name: String::from("Jane"),
+            last_name: String::from("Doe"),
+            status,
+            stores: None,
+            rank_id: None,
+            version: FIRST_VERSION,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    fn transition(
+        employee: &Employee,
+        previous_status: Option<Status>,
+        status: Status,
+        hour: i64,
+    ) -> StatusTransition {
+        StatusTransition {
+            id: None,
+            employee_id: employee.id.unwrap().to_hex(),
+            previous_status,
+            status: Some(status),
+            changed_at: DateTime::from_millis(hour * HOUR),
+            changed_by: None,
+        }
+    }
+
+    #[test]
+    fn splits_the_period_at_each_transition() {
+        let jane: Employee = employee(Some(Status::Vacation));
+        let history: Vec<StatusTransition> = vec![
+            transition(&jane, Some(Status::None), Status::Working, 2),
+            transition(&jane, Some(Status::Working), Status::Vacation, 5),
+        ];
+
+        let report: Vec<TimeInStatus> = time_in_status(
+            &[jane],
+            &history,
+            DateTime::from_millis(0),
+            DateTime::from_millis(10 * HOUR),
+        );
+
+        assert_eq!(
+            report,
+            vec![
+                TimeInStatus {
+                    status: Status::None,
+                    seconds: 2 * 3600
+                },
+                TimeInStatus {
+                    status: Status::Working,
+                    seconds: 3 * 3600
+                },
+                TimeInStatus {
+                    status: Status::Vacation,
+                    seconds: 5 * 3600
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sums_employees_and_skips_time_before_creation() {
+        let jane: Employee = employee(Some(Status::Working));
+        let john: Employee = employee(None);
+        let history: Vec<StatusTransition> = vec![transition(&jane, None, Status::Working, 4)];
+
+        let report: Vec<TimeInStatus> = time_in_status(
+            &[jane, john],
+            &history,
+            DateTime::from_millis(0),
+            DateTime::from_millis(6 * HOUR),
+        );
+
+        assert_eq!(
+            report,
+            vec![
+                TimeInStatus {
+                    status: Status::None,
+                    seconds: 6 * 3600
+                },
+                TimeInStatus {
+                    status: Status::Working,
+                    seconds: 2 * 3600
+                },
+            ]
+        );
+    }
+}
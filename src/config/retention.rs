@@ -0,0 +1,68 @@
+use mongodb::bson::DateTime;
+use std::env;
+
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// How long deleted entries are kept before a purge removes them for good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub days: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            days: DEFAULT_RETENTION_DAYS,
+        }
+    }
+}
+
+impl Retention {
+    /// Reads `DELETED_RETENTION_DAYS`, falling back to 30 days when it is not
+    /// set.
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("DELETED_RETENTION_DAYS") {
+            Ok(value) => Retention::parse(&value)
+                .map_err(|error| format!("DELETED_RETENTION_DAYS: {}", error)),
+            Err(_) => Ok(Retention::default()),
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        value
+            .trim()
+            .parse::<u32>()
+            .map(|days| Retention { days })
+            .map_err(|_| format!("expected a number of days, got '{}'", value))
+    }
+
+    /// Entries deleted before this point have been kept long enough.
+    pub fn cutoff(&self, now: DateTime) -> DateTime {
+        DateTime::from_millis(now.timestamp_millis() - i64::from(self.days) * DAY_MILLIS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_whole_days() {
+        assert_eq!(Retention::parse(" 7 "), Ok(Retention { days: 7 }));
+        assert!(Retention::parse("-1").is_err());
+        assert!(Retention::parse("a week").is_err());
+    }
+
+    #[test]
+    fn cuts_off_the_retention_period() {
+        let now: DateTime = DateTime::from_millis(10 * DAY_MILLIS);
+
+        assert_eq!(
+            Retention { days: 3 }.cutoff(now),
+            DateTime::from_millis(7 * DAY_MILLIS)
+        );
+        assert_eq!(Retention { days: 0 }.cutoff(now), now);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::error::RepositoryError;
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+
+/// ISO 3166-1 alpha-2 country codes accepted for `Location.country`.
+const COUNTRY_CODES: [&str; 249] = [
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+
+/// How create and update mutations treat input they cannot store as given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationMode {
+    /// Reject the whole mutation, listing every offending field.
+    #[default]
+    Strict,
+    /// Legacy behaviour: drop unknown references and accept values as sent.
+    Lenient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: String::from(field),
+            message: message.into(),
+        }
+    }
+}
+
+/// Collects field errors for one input object, so that a client learns about
+/// every problem at once instead of one per round-trip. In lenient mode only
+/// explicitly added errors are kept; the checks below let everything pass.
+#[derive(Debug, Default)]
+pub struct Validator {
+    mode: ValidationMode,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new(mode: ValidationMode) -> Self {
+        Validator {
+            mode,
+            errors: Vec::new(),
+        }
+    }
+
+    fn is_strict(&self) -> bool {
+        self.mode == ValidationMode::Strict
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError::new(field, message));
+    }
+
+    pub fn non_empty(&mut self, field: &str, value: &str) {
+        if self.is_strict() && value.trim().is_empty() {
+            self.add(field, "must not be empty");
+        }
+    }
+
+    /// Returns whether `value` is a well-formed ObjectId.
+    pub fn object_id(&mut self, field: &str, value: &str) -> bool {
+        let valid: bool = ObjectId::parse_str(value).is_ok();
+        if self.is_strict() && !valid {
+            self.add(field, format!("'{}' is not a valid ID", value));
+        }
+
+        valid
+    }
+
+    pub fn unknown_reference(&mut self, field: &str, entity: &str, id: &str) {
+        if self.is_strict() {
+            self.add(field, format!("{} '{}' does not exist", entity, id));
+        }
+    }
+
+    /// Returns the upper-cased country code in strict mode and the value as
+    /// sent in lenient mode.
+    pub fn country_code(&mut self, field: &str, value: &str) -> String {
+        if !self.is_strict() {
+            return String::from(value);
+        }
+
+        let code: String = value.trim().to_ascii_uppercase();
+        if !COUNTRY_CODES.contains(&code.as_str()) {
+            self.add(
+                field,
+                format!("'{}' is not an ISO 3166-1 alpha-2 country code", value),
+            );
+        }
+
+        code
+    }
+
+    pub fn finish(self) -> Result<(), RepositoryError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RepositoryError::Validation(self.errors))
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use crate::{
+    config::{
+        error::RepositoryError,
+        repository::{
+            today, AbsenceRepository, EmployeeRepository, LocationRepository, RankRepository,
+            Repositories, ShiftRepository, StatusHistoryRepository, StoreRepository, TimeRange,
+        },
+    },
+    schema::project_schema::{Absence, Employee, Location, Rank, Shift, StatusTransition, Store},
+};
+use async_graphql::{
+    dataloader::{DataLoader, HashMapCache, Loader},
+    Data,
+};
+use async_graphql_rocket::GraphQLRequest;
+use async_trait::async_trait;
+use rocket::tokio;
+use std::{collections::HashMap, sync::Arc};
+
+/// The loader type attached to every request. Resolvers must look loaders up
+/// under this type, as `DataLoader<T>` alone names the uncached variant.
+pub type RequestLoader<T> = DataLoader<T, HashMapCache>;
+
+pub struct EmployeeLoader {
+    repository: Arc<dyn EmployeeRepository>,
+}
+
+pub struct StoreLoader {
+    repository: Arc<dyn StoreRepository>,
+}
+
+pub struct LocationLoader {
+    repository: Arc<dyn LocationRepository>,
+}
+
+pub struct RankLoader {
+    repository: Arc<dyn RankRepository>,
+}
+
+/// Loads the approved absence covering today, keyed by employee ID. Days
+/// are taken in UTC.
+pub struct CurrentAbsenceLoader {
+    repository: Arc<dyn AbsenceRepository>,
+}
+
+/// Loads the employees assigned to a store, keyed by store ID.
+pub struct StoreEmployeesLoader {
+    repository: Arc<dyn EmployeeRepository>,
+}
+
+/// Loads the employees holding a rank, keyed by rank ID.
+pub struct RankEmployeesLoader {
+    repository: Arc<dyn EmployeeRepository>,
+}
+
+/// Loads the stores at a location, keyed by location ID.
+pub struct LocationStoresLoader {
+    repository: Arc<dyn StoreRepository>,
+}
+
+/// Loads every absence of an employee, keyed by employee ID.
+pub struct EmployeeAbsencesLoader {
+    repository: Arc<dyn AbsenceRepository>,
+}
+
+/// Loads the shifts of an employee within a range.
+pub struct EmployeeShiftsLoader {
+    repository: Arc<dyn ShiftRepository>,
+}
+
+/// Loads the status history of an employee within a range.
+pub struct StatusHistoryLoader {
+    repository: Arc<dyn StatusHistoryRepository>,
+}
+
+/// An employee ID together with the range its listing is cut to.
+pub type RangedKey = (String, TimeRange);
+
+fn key_by_id<T>(doc_vec: Vec<T>, id_of: fn(&T) -> Option<String>) -> HashMap<String, T> {
+    doc_vec
+        .into_iter()
+        .filter_map(|doc| id_of(&doc).map(|id| (id, doc)))
+        .collect()
+}
+
+/// Sorts entries into the groups of the requested keys. An entry may belong
+/// to several keys, and every requested key gets a group, even if empty.
+fn group_by_key<T: Clone>(
+    keys: &[String],
+    doc_vec: Vec<T>,
+    keys_of: impl Fn(&T) -> Vec<String>,
+) -> HashMap<String, Vec<T>> {
+    let mut groups: HashMap<String, Vec<T>> =
+        keys.iter().map(|key| (key.clone(), vec![])).collect();
+    for doc in doc_vec {
+        for key in keys_of(&doc) {
+            if let Some(group) = groups.get_mut(&key) {
+                group.push(doc.clone());
+            }
+        }
+    }
+    groups
+}
+
+/// The employee IDs asked for per range. Sibling fields mostly share their
+/// arguments, so a batch usually holds a single range.
+fn by_range(keys: &[RangedKey]) -> HashMap<TimeRange, Vec<String>> {
+    let mut ranges: HashMap<TimeRange, Vec<String>> = HashMap::new();
+    for (employee_id, range) in keys {
+        ranges.entry(*range).or_default().push(employee_id.clone());
+    }
+    ranges
+}
+
+#[async_trait]
+impl Loader<String> for EmployeeLoader {
+    type Value = Employee;
+    type Error = RepositoryError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Employee>, RepositoryError> {
+        let employee_vec: Vec<Employee> = self.repository.get_employees_by_ids(keys).await?;
+
+        Ok(key_by_id(employee_vec, |employee| {
+            employee.id.map(|id| id.to_string())
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for StoreLoader {
+    type Value = Store;
+    type Error = RepositoryError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Store>, RepositoryError> {
+        let store_vec: Vec<Store> = self.repository.get_stores_by_ids(keys).await?;
+
+        Ok(key_by_id(store_vec, |store| {
+            store.id.map(|id| id.to_string())
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for LocationLoader {
+    type Value = Location;
+    type Error = RepositoryError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Location>, RepositoryError> {
+        let location_vec: Vec<Location> = self.repository.get_locations_by_ids(keys).await?;
+
+        Ok(key_by_id(location_vec, |location| {
+            location.id.map(|id| id.to_string())
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for RankLoader {
+    type Value = Rank;
+    type Error = RepositoryError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Rank>, RepositoryError> {
+        let rank_vec: Vec<Rank> = self.repository.get_ranks_by_ids(keys).await?;
+
+        Ok(key_by_id(rank_vec, |rank| rank.id.map(|id| id.to_string())))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for CurrentAbsenceLoader {
+    type Value = Absence;
+    type Error = RepositoryError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Absence>, RepositoryError> {
+        let absence_vec: Vec<Absence> = self
+            .repository
+            .get_approved_absences_on(keys, today())
+            .await?;
+
+        Ok(key_by_id(absence_vec, |absence| {
+            Some(absence.employee_id.clone())
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for StoreEmployeesLoader {
+    type Value = Vec<Employee>;
+    type Error = RepositoryError;
+
+    async fn load(
+        &self,
+        keys: &[String],
+    ) -> Result<HashMap<String, Vec<Employee>>, RepositoryError> {
+        let employee_vec: Vec<Employee> = self.repository.get_employees_by_stores(keys).await?;
+
+        Ok(group_by_key(keys, employee_vec, |employee| {
+            employee.stores.clone().unwrap_or_default()
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for RankEmployeesLoader {
+    type Value = Vec<Employee>;
+    type Error = RepositoryError;
+
+    async fn load(
+        &self,
+        keys: &[String],
+    ) -> Result<HashMap<String, Vec<Employee>>, RepositoryError> {
+        let employee_vec: Vec<Employee> = self.repository.get_employees_by_ranks(keys).await?;
+
+        Ok(group_by_key(keys, employee_vec, |employee| {
+            employee.rank_id.iter().cloned().collect()
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for LocationStoresLoader {
+    type Value = Vec<Store>;
+    type Error = RepositoryError;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Vec<Store>>, RepositoryError> {
+        let store_vec: Vec<Store> = self.repository.get_stores_by_locations(keys).await?;
+
+        Ok(group_by_key(keys, store_vec, |store| {
+            store.location_id.iter().cloned().collect()
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<String> for EmployeeAbsencesLoader {
+    type Value = Vec<Absence>;
+    type Error = RepositoryError;
+
+    async fn load(
+        &self,
+        keys: &[String],
+    ) -> Result<HashMap<String, Vec<Absence>>, RepositoryError> {
+        let absence_vec: Vec<Absence> = self.repository.get_absences_by_employees(keys).await?;
+
+        Ok(group_by_key(keys, absence_vec, |absence| {
+            vec![absence.employee_id.clone()]
+        }))
+    }
+}
+
+#[async_trait]
+impl Loader<RangedKey> for EmployeeShiftsLoader {
+    type Value = Vec<Shift>;
+    type Error = RepositoryError;
+
+    async fn load(
+        &self,
+        keys: &[RangedKey],
+    ) -> Result<HashMap<RangedKey, Vec<Shift>>, RepositoryError> {
+        let mut groups: HashMap<RangedKey, Vec<Shift>> = HashMap::new();
+        for (range, employee_ids) in by_range(keys) {
+            let shift_vec: Vec<Shift> = self
+                .repository
+                .get_shifts_by_employees(&employee_ids, range)
+                .await?;
+            let grouped = group_by_key(&employee_ids, shift_vec, |shift| {
+                vec![shift.employee_id.clone()]
+            });
+            groups.extend(grouped.into_iter().map(|(id, group)| ((id, range), group)));
+        }
+
+        Ok(groups)
+    }
+}
+
+#[async_trait]
+impl Loader<RangedKey> for StatusHistoryLoader {
+    type Value = Vec<StatusTransition>;
+    type Error = RepositoryError;
+
+    async fn load(
+        &self,
+        keys: &[RangedKey],
+    ) -> Result<HashMap<RangedKey, Vec<StatusTransition>>, RepositoryError> {
+        let mut groups: HashMap<RangedKey, Vec<StatusTransition>> = HashMap::new();
+        for (range, employee_ids) in by_range(keys) {
+            let transition_vec: Vec<StatusTransition> = self
+                .repository
+                .get_status_history(&employee_ids, range)
+                .await?;
+            let grouped = group_by_key(&employee_ids, transition_vec, |transition| {
+                vec![transition.employee_id.clone()]
+            });
+            groups.extend(grouped.into_iter().map(|(id, group)| ((id, range), group)));
+        }
+
+        Ok(groups)
+    }
+}
+
+fn request_loader<T>(loader: T) -> RequestLoader<T> {
+    DataLoader::with_cache(loader, tokio::spawn, HashMapCache::default())
+}
+
+/// Puts one loader of every kind into `data`. Without `cache` the loaders
+/// only batch.
+fn insert_loaders(data: &mut Data, db: &Repositories, cache: bool) {
+    fn insert<T: Send + Sync + 'static>(data: &mut Data, loader: T, cache: bool) {
+        let loader: RequestLoader<T> = request_loader(loader);
+        loader.enable_all_cache(cache);
+        data.insert(loader);
+    }
+
+    insert(
+        data,
+        EmployeeLoader {
+            repository: db.employees.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        StoreLoader {
+            repository: db.stores.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        LocationLoader {
+            repository: db.locations.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        RankLoader {
+            repository: db.ranks.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        CurrentAbsenceLoader {
+            repository: db.absences.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        StoreEmployeesLoader {
+            repository: db.employees.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        RankEmployeesLoader {
+            repository: db.employees.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        LocationStoresLoader {
+            repository: db.stores.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        EmployeeAbsencesLoader {
+            repository: db.absences.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        EmployeeShiftsLoader {
+            repository: db.shifts.clone(),
+        },
+        cache,
+    );
+    insert(
+        data,
+        StatusHistoryLoader {
+            repository: db.status_history.clone(),
+        },
+        cache,
+    );
+}
+
+/// Attaches a fresh set of loaders to a single request, so that the cache
+/// never outlives the request it was filled by.
+pub fn with_loaders(mut request: GraphQLRequest, db: &Repositories) -> GraphQLRequest {
+    insert_loaders(&mut request.0.data, db, true);
+    request
+}
+
+/// Loaders for a WebSocket connection. A connection lives for as long as the
+/// client keeps it open, so these only batch and never cache.
+pub fn connection_loaders(db: &Repositories) -> Data {
+    let mut data: Data = Data::default();
+    insert_loaders(&mut data, db, false);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_loaders;
+    use crate::{
+        config::{integrity::DeletePolicies, memory::InMemory, repository::Repositories},
+        schema::project_schema::{Employee, Store},
+    };
+    use async_graphql::{
+        value, EmptyMutation, EmptySubscription, Object, Request, Response, Schema,
+    };
+    use async_graphql_rocket::GraphQLRequest;
+    use mongodb::bson::{doc, from_document};
+
+    /// Hands out entries whose relations resolve without a single lookup
+    /// result, so that only finding the loaders is exercised.
+    struct Probe;
+
+    #[Object]
+    impl Probe {
+        async fn employee(&self) -> Employee {
+            from_document(doc! {"first_name": "Jane", "last_name": "Doe", "stores": []}).unwrap()
+        }
+
+        async fn store(&self) -> Store {
+            from_document(doc! {"name": "Mitte", "location_id": null}).unwrap()
+        }
+    }
+
+    #[rocket::async_test]
+    async fn resolves_relations_through_request_loaders() {
+        let schema = Schema::build(Probe, EmptyMutation, EmptySubscription).finish();
+        let query: &str = "{ employee { stores { location { state } } rank { name } } store { location { state } } }";
+        let request: GraphQLRequest = with_loaders(
+            GraphQLRequest(Request::new(query)),
+            &Repositories::new(InMemory::new(DeletePolicies::default())),
+        );
+        let response: Response = schema.execute(request.0).await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data,
+            value!({
+                "employee": {"stores": [], "rank": null},
+                "store": {"location": null},
+            })
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{
+    config::{auth::Principal, error::optional, repository::Repositories},
+    handler::data_loader::{EmployeeLoader, RankLoader, RequestLoader},
+    schema::project_schema::{
+        Absence, DecideAbsence, Employee, Permission, Rank, RequestAbsence, UpdateEmployee,
+    },
+};
+use async_graphql::{Context, Error, ErrorExtensions, Guard, Result, ResultExt};
+use async_trait::async_trait;
+
+fn unauthenticated() -> Error {
+    Error::new("Authentication required")
+        .extend_with(|_, extensions| extensions.set("code", "UNAUTHENTICATED"))
+}
+
+fn forbidden(message: String) -> Error {
+    Error::new(message).extend_with(|_, extensions| extensions.set("code", "FORBIDDEN"))
+}
+
+fn principal<'a>(context: &'a Context<'_>) -> Result<&'a Principal> {
+    context.data_opt::<Principal>().ok_or_else(unauthenticated)
+}
+
+/// The permissions granted by the rank of the principal's employee entry.
+async fn granted_permissions(
+    context: &Context<'_>,
+    principal: &Principal,
+) -> Result<Vec<Permission>> {
+    let employee_loader: &RequestLoader<EmployeeLoader> =
+        context.data::<RequestLoader<EmployeeLoader>>()?;
+    let employee: Option<Employee> = employee_loader
+        .load_one(principal.subject.clone())
+        .await
+        .extend()?;
+
+    let rank_id: String = match employee.and_then(|employee| employee.rank_id) {
+        Some(rank_id) => rank_id,
+        None => return Ok(vec![]),
+    };
+
+    let rank_loader: &RequestLoader<RankLoader> = context.data::<RequestLoader<RankLoader>>()?;
+    let rank: Option<Rank> = rank_loader.load_one(rank_id).await.extend()?;
+
+    Ok(rank.map(|rank| rank.permissions).unwrap_or_default())
+}
+
+async fn require_permission(context: &Context<'_>, permission: Permission) -> Result<()> {
+    let principal: &Principal = principal(context)?;
+    if principal.is_admin
+        || granted_permissions(context, principal)
+            .await?
+            .contains(&permission)
+    {
+        Ok(())
+    } else {
+        Err(forbidden(format!("Missing permission {:?}", permission)))
+    }
+}
+
+/// Lets only authenticated principals whose rank grants the given permission
+/// through. Administrators listed in `ADMIN_SUBJECTS` pass every check.
+pub struct PermissionGuard {
+    permission: Permission,
+}
+
+impl PermissionGuard {
+    pub fn new(permission: Permission) -> Self {
+        PermissionGuard { permission }
+    }
+}
+
+#[async_trait]
+impl Guard for PermissionGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        require_permission(context, self.permission).await
+    }
+}
+
+/// Lets only administrators listed in `ADMIN_SUBJECTS` through, whatever
+/// their rank grants.
+pub struct AdminGuard;
+
+#[async_trait]
+impl Guard for AdminGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        if principal(context)?.is_admin {
+            Ok(())
+        } else {
+            Err(forbidden(String::from("Administrator access required")))
+        }
+    }
+}
+
+/// Leaves listings of live entries open to everyone, but lets only
+/// administrators include the entries marked deleted.
+pub struct IncludeDeletedGuard {
+    include_deleted: bool,
+}
+
+impl IncludeDeletedGuard {
+    pub fn new(include_deleted: bool) -> Self {
+        IncludeDeletedGuard { include_deleted }
+    }
+}
+
+#[async_trait]
+impl Guard for IncludeDeletedGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        if self.include_deleted {
+            AdminGuard.check(context).await
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Lets an employee holding `UpdateOwnStatus` change the status of their own
+/// entry, provided the update touches nothing else.
+pub struct OwnStatusGuard {
+    employee_id: String,
+    status_only: bool,
+}
+
+impl OwnStatusGuard {
+    pub fn new(input: &UpdateEmployee) -> Self {
+        let status_only: bool = input.first_name.is_none()
+            && input.last_name.is_none()
+            && input.stores.is_undefined()
+            && input.add_stores.is_none()
+            && input.remove_stores.is_none()
+            && input.rank_id.is_undefined();
+
+        OwnStatusGuard {
+            employee_id: input.id.clone(),
+            status_only,
+        }
+    }
+
+    fn covers(&self, principal: &Principal) -> bool {
+        self.status_only && principal.subject == self.employee_id
+    }
+}
+
+#[async_trait]
+impl Guard for OwnStatusGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        if !self.covers(principal(context)?) {
+            return Err(forbidden(String::from(
+                "Employees may only change their own status",
+            )));
+        }
+
+        require_permission(context, Permission::UpdateOwnStatus).await
+    }
+}
+
+/// Lets an employee holding `RequestAbsences` request absences for
+/// themselves and cancel their own.
+pub struct OwnAbsenceGuard {
+    target: AbsenceTarget,
+}
+
+enum AbsenceTarget {
+    Employee(String),
+    Absence(String),
+}
+
+impl OwnAbsenceGuard {
+    pub fn request(input: &RequestAbsence) -> Self {
+        OwnAbsenceGuard {
+            target: AbsenceTarget::Employee(input.employee_id.clone()),
+        }
+    }
+
+    pub fn cancel(input: &DecideAbsence) -> Self {
+        OwnAbsenceGuard {
+            target: AbsenceTarget::Absence(input.id.clone()),
+        }
+    }
+
+    /// The employee the absence belongs to, if it exists.
+    async fn owner(&self, context: &Context<'_>) -> Result<Option<String>> {
+        match &self.target {
+            AbsenceTarget::Employee(employee_id) => Ok(Some(employee_id.clone())),
+            AbsenceTarget::Absence(absence_id) => {
+                let db: &Repositories = context.data::<Repositories>()?;
+                let absence: Option<Absence> =
+                    optional(db.absences.get_single_absence(absence_id).await).extend()?;
+
+                Ok(absence.map(|absence| absence.employee_id))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Guard for OwnAbsenceGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        let principal: &Principal = principal(context)?;
+        if self.owner(context).await?.as_deref() != Some(principal.subject.as_str()) {
+            return Err(forbidden(String::from(
+                "Employees may only request and cancel their own absences",
+            )));
+        }
+
+        require_permission(context, Permission::RequestAbsences).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnStatusGuard, PermissionGuard};
+    use crate::{
+        config::auth::Principal,
+        handler::graphql_handler::{Mutation, Query},
+        schema::project_schema::{Permission, Status, UpdateEmployee},
+    };
+    use async_graphql::{
+        EmptyMutation, EmptySubscription, MaybeUndefined, Object, Request, Response, Schema, Value,
+    };
+
+    struct Probe;
+
+    #[Object]
+    impl Probe {
+        #[graphql(guard = "PermissionGuard::new(Permission::ManageRanks)")]
+        async fn restricted(&self) -> bool {
+            true
+        }
+    }
+
+    fn principal(subject: &str, is_admin: bool) -> Principal {
+        Principal {
+            subject: String::from(subject),
+            is_admin,
+        }
+    }
+
+    fn status_update(id: &str) -> UpdateEmployee {
+        UpdateEmployee {
+            id: String::from(id),
+            first_name: None,
+            last_name: None,
+            status: MaybeUndefined::Value(Status::Vacation),
+            stores: MaybeUndefined::Undefined,
+            add_stores: None,
+            remove_stores: None,
+            rank_id: MaybeUndefined::Undefined,
+            version: None,
+        }
+    }
+
+    fn error_code(response: &Response) -> Option<&Value> {
+        response
+            .errors
+            .first()
+            .and_then(|error| error.extensions.as_ref())
+            .and_then(|extensions| extensions.get("code"))
+    }
+
+    #[rocket::async_test]
+    async fn rejects_anonymous_request() {
+        let schema = Schema::build(Probe, EmptyMutation, EmptySubscription).finish();
+        let response: Response = schema.execute(Request::new("{ restricted }")).await;
+
+        assert_eq!(error_code(&response), Some(&Value::from("UNAUTHENTICATED")));
+    }
+
+    #[rocket::async_test]
+    async fn guards_every_mutation() {
+        let schema = Schema::build(Query, Mutation, EmptySubscription).finish();
+        let response: Response = schema
+            .execute(Request::new(
+                r#"mutation { createRank(input: {name: "Manager"}) { id } }"#,
+            ))
+            .await;
+
+        assert_eq!(error_code(&response), Some(&Value::from("UNAUTHENTICATED")));
+    }
+
+    #[rocket::async_test]
+    async fn admits_admin_without_rank_lookup() {
+        let schema = Schema::build(Probe, EmptyMutation, EmptySubscription).finish();
+        let response: Response = schema
+            .execute(Request::new("{ restricted }").data(principal("root", true)))
+            .await;
+
+        assert!(response.errors.is_empty());
+    }
+
+    #[test]
+    fn own_status_guard_covers_only_own_status() {
+        let alice: Principal = principal("alice", false);
+        assert!(OwnStatusGuard::new(&status_update("alice")).covers(&alice));
+        assert!(!OwnStatusGuard::new(&status_update("bob")).covers(&alice));
+
+        let mut rename: UpdateEmployee = status_update("alice");
+        rename.first_name = Some(String::from("Alicia"));
+        assert!(!OwnStatusGuard::new(&rename).covers(&alice));
+    }
+}
@@ -1,84 +1,678 @@
 use crate::{
-    config::mongo::MongoDB,
-    schema::project_schema::{Employee, CreateEmployee, FetchEmployee, DeleteEmployee, UpdateEmployee,
-                             Store, CreateStore, FetchStore,
-                             Location, CreateLocation, FetchLocation,
-                             Rank, CreateRank, FetchRank},
+    config::{
+        auth::Principal,
+        error::{optional, RepositoryError},
+        event_bus::{Event, EventBus},
+        pagination::{Page, PageRequest},
+        repository::{
+            time_in_status, today, AbsenceDecision, Deletion, Repositories, SoftDeletable,
+            TimeRange,
+        },
+        validation::ValidationMode,
+    },
+    handler::{
+        graphql_guard::{
+            AdminGuard, IncludeDeletedGuard, OwnAbsenceGuard, OwnStatusGuard, PermissionGuard,
+        },
+        request_guard::ClientAddress,
+        subscription_handler::Subscription,
+    },
+    schema::project_schema::{
+        Absence, AbsenceFilter, AbsenceState, AuditEntry, AuditFilter, ChangeKind,
+        ConnectionFields, CreateEmployee, CreateLocation, CreateRank, CreateShift, CreateStore,
+        DecideAbsence, DeleteEmployee, DeleteLocation, DeleteRank, DeleteShift, DeleteStore,
+        Employee, EmployeeFilter, EmployeeOrderBy, EmployeeStatusChange, FetchAbsence,
+        FetchEmployee, FetchLocation, FetchRank, FetchShift, FetchStore, Location, LocationChange,
+        LocationFilter, LocationOrderBy, Permission, Rank, RankChange, RankFilter, RankOrderBy,
+        RequestAbsence, RestoreEmployee, RestoreLocation, RestoreRank, RestoreStore, Shift, Status,
+        StatusTransition, Store, StoreChange, StoreFilter, StoreOrderBy, TimeInStatus,
+        UpdateEmployee, UpdateLocation, UpdateRank, UpdateShift, UpdateStore,
+    },
 };
-use async_graphql::{Context, EmptySubscription, FieldResult, Object, Schema};
+use async_graphql::{
+    connection::{query, Connection, Edge, EmptyFields},
+    Context, Error, ErrorExtensions, FieldResult, Object, OutputType, ResultExt, Schema,
+};
+use chrono::NaiveDate;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::Serialize;
 
 pub struct Query;
 pub struct Mutation;
 
+fn validation_mode(context: &Context<'_>) -> ValidationMode {
+    context
+        .data_opt::<ValidationMode>()
+        .copied()
+        .unwrap_or_default()
+}
+
+fn subject(context: &Context<'_>) -> Option<String> {
+    context
+        .data_opt::<Principal>()
+        .map(|principal| principal.subject.clone())
+}
+
+/// Records a change of the status an employee shows, made by the principal
+/// of the request. Like the audit entry, a transition that cannot be
+/// recorded is logged rather than failing the update that made it.
+async fn record_transition(
+    context: &Context<'_>,
+    employee: &Employee,
+    previous_status: Option<Status>,
+) {
+    let db: &Repositories = context.data_unchecked::<Repositories>();
+    let employee_id: String = employee.id.map(|id| id.to_hex()).unwrap_or_default();
+    let transition: StatusTransition = StatusTransition::new(
+        employee_id.clone(),
+        previous_status,
+        employee.status,
+        subject(context),
+    );
+    if let Err(error) = db.status_history.record_status_transition(transition).await {
+        log::error!(
+            "Cannot record the status transition of employee {}: {}",
+            employee_id,
+            error
+        );
+    }
+}
+
+/// Records and publishes the change of status an approval or cancellation
+/// makes when the absence covers today. The status the employee shows moves
+/// to or back from the one of the absence; the stored status stays as it is.
+async fn absence_transition(
+    context: &Context<'_>,
+    previous_absence: Option<&Absence>,
+    decided_absence: &Absence,
+) {
+    let day: NaiveDate = today();
+    if decided_absence.starts_on > day || decided_absence.ends_on < day {
+        return;
+    }
+    let was_approved: bool =
+        previous_absence.is_some_and(|absence| absence.state == AbsenceState::Approved);
+    let absent_status: Option<Status> = Some(decided_absence.kind.status());
+
+    let db: &Repositories = context.data_unchecked::<Repositories>();
+    let employee: Employee = match db
+        .employees
+        .get_single_employee(&decided_absence.employee_id)
+        .await
+    {
+        Ok(employee) => employee,
+        Err(error) => {
+            log::error!(
+                "Cannot report the status change of employee {}: {}",
+                decided_absence.employee_id,
+                error
+            );
+            return;
+        }
+    };
+    let (previous_status, status) = match decided_absence.state {
+        AbsenceState::Approved if !was_approved => (employee.status, absent_status),
+        AbsenceState::Cancelled if was_approved => (absent_status, employee.status),
+        _ => return,
+    };
+    if previous_status == status {
+        return;
+    }
+
+    let changed_employee: Employee = Employee { status, ..employee };
+    record_transition(context, &changed_employee, previous_status).await;
+    publish(
+        context,
+        Event::EmployeeStatusChanged(EmployeeStatusChange {
+            employee: changed_employee,
+            previous_status,
+        }),
+    );
+}
+
+/// Whether an approved absence covers the employee today, in which case a
+/// change of the stored status does not change the status it shows.
+async fn absent_today(context: &Context<'_>, employee: &Employee) -> bool {
+    let db: &Repositories = context.data_unchecked::<Repositories>();
+    let employee_id: String = employee.id.map(|id| id.to_hex()).unwrap_or_default();
+    match db
+        .absences
+        .get_approved_absences_on(std::slice::from_ref(&employee_id), today())
+        .await
+    {
+        Ok(absences) => !absences.is_empty(),
+        Err(error) => {
+            log::error!(
+                "Cannot look up the absences of employee {}: {}",
+                employee_id,
+                error
+            );
+            false
+        }
+    }
+}
+
+/// Appends the audit entry for the mutation being resolved, naming the
+/// principal and client address of the request. The mutation is written by
+/// then, so an entry that cannot be appended is logged instead of failing a
+/// mutation that already happened.
+async fn audit<T: Serialize>(
+    context: &Context<'_>,
+    entity_type: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    let db: &Repositories = context.data_unchecked::<Repositories>();
+    let client_ip: Option<String> = context
+        .data_opt::<ClientAddress>()
+        .and_then(|address| address.0)
+        .map(|ip| ip.to_string());
+    let operation: &str = context.field().name();
+    let appended: Result<AuditEntry, RepositoryError> = match AuditEntry::new(
+        operation,
+        entity_type,
+        before,
+        after,
+        subject(context),
+        client_ip,
+    ) {
+        Ok(entry) => db.audit.append_audit_entry(entry).await,
+        Err(error) => Err(error),
+    };
+
+    if let Err(error) = appended {
+        log::error!("Cannot audit {} of {}: {}", operation, entity_type, error);
+    }
+}
+
+/// Appends the audit entry for a deletion, which only marked the entry.
+async fn audit_deletion<T: Serialize + SoftDeletable + Clone>(
+    context: &Context<'_>,
+    entity_type: &str,
+    deleted: &T,
+) {
+    let mut previous: T = deleted.clone();
+    previous.restore();
+
+    audit(context, entity_type, Some(&previous), Some(deleted)).await
+}
+
+/// Moves an absence on behalf of the principal making the request.
+async fn decide(
+    context: &Context<'_>,
+    input: DecideAbsence,
+    state: AbsenceState,
+) -> FieldResult<Absence> {
+    let db: &Repositories = context.data_unchecked::<Repositories>();
+    let previous_absence: Option<Absence> =
+        optional(db.absences.get_single_absence(&input.id).await).extend()?;
+    let decided_absence: Absence = db
+        .absences
+        .decide_absence(AbsenceDecision::new(input, state, subject(context)))
+        .await
+        .extend()?;
+    audit(
+        context,
+        "Absence",
+        previous_absence.as_ref(),
+        Some(&decided_absence),
+    )
+    .await;
+    absence_transition(context, previous_absence.as_ref(), &decided_absence).await;
+
+    Ok(decided_absence)
+}
+
+fn publish(context: &Context<'_>, event: Event) {
+    if let Some(bus) = context.data_opt::<EventBus>() {
+        bus.publish_local(event);
+    }
+}
+
+pub type EntityConnection<T> = Connection<String, T, ConnectionFields, EmptyFields>;
+
+fn page_to_connection<T: OutputType>(
+    page: Page<T>,
+    id_of: fn(&T) -> Option<ObjectId>,
+) -> EntityConnection<T> {
+    let mut connection: EntityConnection<T> = Connection::with_additional_fields(
+        page.has_previous_page,
+        page.has_next_page,
+        ConnectionFields {
+            total_count: page.total_count,
+        },
+    );
+
+    connection.edges.extend(page.items.into_iter().map(|node| {
+        let cursor: String = id_of(&node).map(|id| id.to_hex()).unwrap_or_default();
+        Edge::new(cursor, node)
+    }));
+
+    connection
+}
+
 #[Object(extends)]
+#[allow(clippy::too_many_arguments)]
 impl Query {
     /*
      * Employee Queries
      */
-    async fn get_employee(&self, context: &Context<'_>, input: FetchEmployee) -> FieldResult<Employee> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let found_employee: Employee = db.get_single_employee(&input.id).unwrap();
+    async fn get_employee(
+        &self,
+        context: &Context<'_>,
+        input: FetchEmployee,
+    ) -> FieldResult<Employee> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let found_employee: Employee =
+            db.employees.get_single_employee(&input.id).await.extend()?;
 
         Ok(found_employee)
     }
 
-    async fn get_all_employees(&self, context: &Context<'_>) -> FieldResult<Vec<Employee>> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let employee_vec: Vec<Employee> = db.get_all_employees().unwrap();
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn get_all_employees(
+        &self,
+        context: &Context<'_>,
+        filter: Option<EmployeeFilter>,
+        order_by: Option<EmployeeOrderBy>,
+    ) -> FieldResult<Vec<Employee>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let employee_vec: Vec<Employee> = db
+            .employees
+            .get_all_employees(&filter.unwrap_or_default(), order_by.as_ref())
+            .await
+            .extend()?;
 
         Ok(employee_vec)
     }
 
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn employees(
+        &self,
+        context: &Context<'_>,
+        filter: Option<EmployeeFilter>,
+        order_by: Option<EmployeeOrderBy>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> FieldResult<EntityConnection<Employee>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let request: PageRequest = PageRequest::new(after, before, first, last).extend()?;
+                let page: Page<Employee> = db
+                    .employees
+                    .get_employee_page(&filter.unwrap_or_default(), order_by.as_ref(), &request)
+                    .await
+                    .extend()?;
+
+                Ok::<_, Error>(page_to_connection(page, |employee| employee.id))
+            },
+        )
+        .await
+    }
+
     /*
      * Store Queries
      */
     async fn get_store(&self, context: &Context<'_>, input: FetchStore) -> FieldResult<Store> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let found_store: Store = db.get_single_store(&input.id).unwrap().unwrap();
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let found_store: Store = db.stores.get_single_store(&input.id).await.extend()?;
 
         Ok(found_store)
     }
 
-    async fn get_all_stores(&self, context: &Context<'_>) -> FieldResult<Vec<Store>> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let store_vec: Vec<Store> = db.get_all_stores().unwrap();
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn get_all_stores(
+        &self,
+        context: &Context<'_>,
+        filter: Option<StoreFilter>,
+        order_by: Option<StoreOrderBy>,
+    ) -> FieldResult<Vec<Store>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let store_vec: Vec<Store> = db
+            .stores
+            .get_all_stores(&filter.unwrap_or_default(), order_by.as_ref())
+            .await
+            .extend()?;
 
         Ok(store_vec)
     }
 
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn stores(
+        &self,
+        context: &Context<'_>,
+        filter: Option<StoreFilter>,
+        order_by: Option<StoreOrderBy>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> FieldResult<EntityConnection<Store>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let request: PageRequest = PageRequest::new(after, before, first, last).extend()?;
+                let page: Page<Store> = db
+                    .stores
+                    .get_store_page(&filter.unwrap_or_default(), order_by.as_ref(), &request)
+                    .await
+                    .extend()?;
+
+                Ok::<_, Error>(page_to_connection(page, |store| store.id))
+            },
+        )
+        .await
+    }
+
     /*
      * Location Queries
      */
-    async fn get_location(&self, context: &Context<'_>, input: FetchLocation) -> FieldResult<Location> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let found_location: Location = db.get_single_location(&input.id).unwrap().unwrap();
+    async fn get_location(
+        &self,
+        context: &Context<'_>,
+        input: FetchLocation,
+    ) -> FieldResult<Location> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let found_location: Location =
+            db.locations.get_single_location(&input.id).await.extend()?;
 
         Ok(found_location)
     }
 
-    async fn get_all_locations(&self, context: &Context<'_>) -> FieldResult<Vec<Location>> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let location_vec: Vec<Location> = db.get_all_locations().unwrap();
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn get_all_locations(
+        &self,
+        context: &Context<'_>,
+        filter: Option<LocationFilter>,
+        order_by: Option<LocationOrderBy>,
+    ) -> FieldResult<Vec<Location>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let location_vec: Vec<Location> = db
+            .locations
+            .get_all_locations(&filter.unwrap_or_default(), order_by.as_ref())
+            .await
+            .extend()?;
 
         Ok(location_vec)
     }
 
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn locations(
+        &self,
+        context: &Context<'_>,
+        filter: Option<LocationFilter>,
+        order_by: Option<LocationOrderBy>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> FieldResult<EntityConnection<Location>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let request: PageRequest = PageRequest::new(after, before, first, last).extend()?;
+                let page: Page<Location> = db
+                    .locations
+                    .get_location_page(&filter.unwrap_or_default(), order_by.as_ref(), &request)
+                    .await
+                    .extend()?;
+
+                Ok::<_, Error>(page_to_connection(page, |location| location.id))
+            },
+        )
+        .await
+    }
+
     /*
      * Rank Queries
      */
     async fn get_rank(&self, context: &Context<'_>, input: FetchRank) -> FieldResult<Rank> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let found_rank: Rank = db.get_single_rank(&input.id).unwrap().unwrap();
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let found_rank: Rank = db.ranks.get_single_rank(&input.id).await.extend()?;
 
         Ok(found_rank)
     }
 
-    async fn get_all_ranks(&self, context: &Context<'_>) -> FieldResult<Vec<Rank>> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let rank_vec: Vec<Rank> = db.get_all_ranks().unwrap();
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn get_all_ranks(
+        &self,
+        context: &Context<'_>,
+        filter: Option<RankFilter>,
+        order_by: Option<RankOrderBy>,
+    ) -> FieldResult<Vec<Rank>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let rank_vec: Vec<Rank> = db
+            .ranks
+            .get_all_ranks(&filter.unwrap_or_default(), order_by.as_ref())
+            .await
+            .extend()?;
 
         Ok(rank_vec)
     }
+
+    #[graphql(
+        guard = "IncludeDeletedGuard::new(filter.as_ref().is_some_and(|filter| filter.include_deleted))"
+    )]
+    async fn ranks(
+        &self,
+        context: &Context<'_>,
+        filter: Option<RankFilter>,
+        order_by: Option<RankOrderBy>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> FieldResult<EntityConnection<Rank>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let request: PageRequest = PageRequest::new(after, before, first, last).extend()?;
+                let page: Page<Rank> = db
+                    .ranks
+                    .get_rank_page(&filter.unwrap_or_default(), order_by.as_ref(), &request)
+                    .await
+                    .extend()?;
+
+                Ok::<_, Error>(page_to_connection(page, |rank| rank.id))
+            },
+        )
+        .await
+    }
+
+    /*
+     * Shift Queries
+     */
+    async fn get_shift(&self, context: &Context<'_>, input: FetchShift) -> FieldResult<Shift> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let found_shift: Shift = db.shifts.get_single_shift(&input.id).await.extend()?;
+
+        Ok(found_shift)
+    }
+
+    /// The shifts at a store overlapping `from`..`to`, by start time.
+    async fn shifts(
+        &self,
+        context: &Context<'_>,
+        store_id: String,
+        from: DateTime,
+        to: DateTime,
+    ) -> FieldResult<Vec<Shift>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let shift_vec: Vec<Shift> = db
+            .shifts
+            .get_shifts_by_store(
+                &store_id,
+                TimeRange {
+                    from: Some(from),
+                    to: Some(to),
+                },
+            )
+            .await
+            .extend()?;
+
+        Ok(shift_vec)
+    }
+
+    /*
+     * Absence Queries
+     */
+    async fn get_absence(
+        &self,
+        context: &Context<'_>,
+        input: FetchAbsence,
+    ) -> FieldResult<Absence> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let found_absence: Absence = db.absences.get_single_absence(&input.id).await.extend()?;
+
+        Ok(found_absence)
+    }
+
+    /// The absences matching the filter, by first day.
+    async fn absences(
+        &self,
+        context: &Context<'_>,
+        filter: Option<AbsenceFilter>,
+    ) -> FieldResult<Vec<Absence>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let absence_vec: Vec<Absence> = db
+            .absences
+            .get_absences(&filter.unwrap_or_default())
+            .await
+            .extend()?;
+
+        Ok(absence_vec)
+    }
+
+    /*
+     * Status Reports
+     */
+    /// The time spent in each status within `from`..`to` by one employee,
+    /// or by the employees currently assigned to one store together, as
+    /// recorded in their status history.
+    async fn time_in_status(
+        &self,
+        context: &Context<'_>,
+        employee_id: Option<String>,
+        store_id: Option<String>,
+        from: DateTime,
+        to: DateTime,
+    ) -> FieldResult<Vec<TimeInStatus>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        if to <= from {
+            return Err(RepositoryError::invalid_field("to", "must be after from").extend());
+        }
+
+        let employee_vec: Vec<Employee> = match (employee_id, store_id) {
+            (Some(employee_id), None) => vec![db
+                .employees
+                .get_single_employee(&employee_id)
+                .await
+                .extend()?],
+            (None, Some(store_id)) => {
+                db.stores.get_single_store(&store_id).await.extend()?;
+                db.employees
+                    .get_employees_by_stores(std::slice::from_ref(&store_id))
+                    .await
+                    .extend()?
+            }
+            _ => {
+                return Err(RepositoryError::invalid_field(
+                    "employeeId",
+                    "give either employeeId or storeId",
+                )
+                .extend())
+            }
+        };
+        let employee_ids: Vec<String> = employee_vec
+            .iter()
+            .filter_map(|employee| employee.id.map(|id| id.to_hex()))
+            .collect();
+        let history: Vec<StatusTransition> = db
+            .status_history
+            .get_status_history(
+                &employee_ids,
+                TimeRange {
+                    from: None,
+                    to: Some(to),
+                },
+            )
+            .await
+            .extend()?;
+
+        Ok(time_in_status(&employee_vec, &history, from, to))
+    }
+
+    /*
+     * Audit Queries
+     */
+    /// Every write made through a mutation, oldest first.
+    #[graphql(guard = "AdminGuard")]
+    async fn audit_log(
+        &self,
+        context: &Context<'_>,
+        filter: Option<AuditFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> FieldResult<EntityConnection<AuditEntry>> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let request: PageRequest = PageRequest::new(after, before, first, last).extend()?;
+                let page: Page<AuditEntry> = db
+                    .audit
+                    .get_audit_page(&filter.unwrap_or_default(), &request)
+                    .await
+                    .extend()?;
+
+                Ok::<_, Error>(page_to_connection(page, |entry| entry.id))
+            },
+        )
+        .await
+    }
 }
 
 #[Object]
@@ -86,56 +680,488 @@ impl Mutation {
     /*
      * Employee Mutations
      */
-    async fn create_employee(&self, context: &Context<'_>, input: CreateEmployee) -> FieldResult<Employee> {
-        let db: &&MongoDB  = &context.data_unchecked::<MongoDB>();
-        let created_employee = db.create_employee(input).unwrap();
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageEmployees)")]
+    async fn create_employee(
+        &self,
+        context: &Context<'_>,
+        input: CreateEmployee,
+    ) -> FieldResult<Employee> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let created_employee = db
+            .employees
+            .create_employee(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(context, "Employee", None, Some(&created_employee)).await;
+        if created_employee.status.is_some() {
+            record_transition(context, &created_employee, None).await;
+        }
+        publish(context, Event::EmployeeCreated(created_employee.clone()));
 
         Ok(created_employee)
     }
 
-    async fn update_employee(&self, context: &Context<'_>, input: UpdateEmployee) -> FieldResult<Employee> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let updated_employee = db.update_employee(input).unwrap();
+    #[graphql(
+        guard = "OwnStatusGuard::new(&input).or(PermissionGuard::new(Permission::ManageEmployees))"
+    )]
+    async fn update_employee(
+        &self,
+        context: &Context<'_>,
+        input: UpdateEmployee,
+    ) -> FieldResult<Employee> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let (previous_employee, updated_employee) = db
+            .employees
+            .update_employee(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(
+            context,
+            "Employee",
+            Some(&previous_employee),
+            Some(&updated_employee),
+        )
+        .await;
+
+        if previous_employee.status != updated_employee.status
+            && !absent_today(context, &updated_employee).await
+        {
+            record_transition(context, &updated_employee, previous_employee.status).await;
+            publish(
+                context,
+                Event::EmployeeStatusChanged(EmployeeStatusChange {
+                    employee: updated_employee.clone(),
+                    previous_status: previous_employee.status,
+                }),
+            );
+        }
 
         Ok(updated_employee)
     }
 
-    async fn delete_employee(&self, context: &Context<'_>, input: DeleteEmployee) -> FieldResult<Employee> {
-        let db: &&MongoDB = &context.data_unchecked::<MongoDB>();
-        let deleted_employee = db.delete_employee(input).unwrap();
+    #[graphql(guard = "PermissionGuard::new(Permission::DeleteEmployees)")]
+    async fn delete_employee(
+        &self,
+        context: &Context<'_>,
+        input: DeleteEmployee,
+    ) -> FieldResult<Employee> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_employee = db
+            .employees
+            .delete_employee(Deletion::new(input.id, subject(context)))
+            .await
+            .extend()?;
+        audit_deletion(context, "Employee", &deleted_employee).await;
+        publish(context, Event::EmployeeDeleted(deleted_employee.clone()));
 
         Ok(deleted_employee)
     }
 
+    #[graphql(guard = "PermissionGuard::new(Permission::DeleteEmployees)")]
+    async fn restore_employee(
+        &self,
+        context: &Context<'_>,
+        input: RestoreEmployee,
+    ) -> FieldResult<Employee> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_employee: Employee = db
+            .employees
+            .get_deleted_employee(&input.id)
+            .await
+            .extend()?;
+        let restored_employee: Employee =
+            db.employees.restore_employee(&input.id).await.extend()?;
+        audit(
+            context,
+            "Employee",
+            Some(&deleted_employee),
+            Some(&restored_employee),
+        )
+        .await;
+        publish(context, Event::EmployeeRestored(restored_employee.clone()));
+
+        Ok(restored_employee)
+    }
+
     /*
      * Store Mutations
      */
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageStores)")]
     async fn create_store(&self, context: &Context<'_>, input: CreateStore) -> FieldResult<Store> {
-        let db: &&MongoDB  = &context.data_unchecked::<MongoDB>();
-        let created_store: Store = db.create_store(input).unwrap();
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let created_store: Store = db
+            .stores
+            .create_store(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(context, "Store", None, Some(&created_store)).await;
+        publish(
+            context,
+            Event::StoreChanged(StoreChange {
+                kind: ChangeKind::Created,
+                store: created_store.clone(),
+            }),
+        );
 
         Ok(created_store)
     }
 
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageStores)")]
+    async fn update_store(&self, context: &Context<'_>, input: UpdateStore) -> FieldResult<Store> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let previous_store: Option<Store> =
+            optional(db.stores.get_single_store(&input.id).await).extend()?;
+        let updated_store: Store = db
+            .stores
+            .update_store(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(
+            context,
+            "Store",
+            previous_store.as_ref(),
+            Some(&updated_store),
+        )
+        .await;
+        publish(
+            context,
+            Event::StoreChanged(StoreChange {
+                kind: ChangeKind::Updated,
+                store: updated_store.clone(),
+            }),
+        );
+
+        Ok(updated_store)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageStores)")]
+    async fn delete_store(&self, context: &Context<'_>, input: DeleteStore) -> FieldResult<Store> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_store: Store = db
+            .stores
+            .delete_store(Deletion::new(input.id, subject(context)))
+            .await
+            .extend()?;
+        audit_deletion(context, "Store", &deleted_store).await;
+        publish(
+            context,
+            Event::StoreChanged(StoreChange {
+                kind: ChangeKind::Deleted,
+                store: deleted_store.clone(),
+            }),
+        );
+
+        Ok(deleted_store)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageStores)")]
+    async fn restore_store(
+        &self,
+        context: &Context<'_>,
+        input: RestoreStore,
+    ) -> FieldResult<Store> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_store: Store = db.stores.get_deleted_store(&input.id).await.extend()?;
+        let restored_store: Store = db.stores.restore_store(&input.id).await.extend()?;
+        audit(
+            context,
+            "Store",
+            Some(&deleted_store),
+            Some(&restored_store),
+        )
+        .await;
+        publish(
+            context,
+            Event::StoreChanged(StoreChange {
+                kind: ChangeKind::Restored,
+                store: restored_store.clone(),
+            }),
+        );
+
+        Ok(restored_store)
+    }
+
     /*
      * Location Mutations
      */
-    async fn create_location(&self, context: &Context<'_>, input: CreateLocation) -> FieldResult<Location> {
-        let db: &&MongoDB  = &context.data_unchecked::<MongoDB>();
-        let created_location: Location = db.create_location(input).unwrap();
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageLocations)")]
+    async fn create_location(
+        &self,
+        context: &Context<'_>,
+        input: CreateLocation,
+    ) -> FieldResult<Location> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let created_location: Location = db
+            .locations
+            .create_location(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(context, "Location", None, Some(&created_location)).await;
+        publish(
+            context,
+            Event::LocationChanged(LocationChange {
+                kind: ChangeKind::Created,
+                location: created_location.clone(),
+            }),
+        );
 
         Ok(created_location)
     }
 
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageLocations)")]
+    async fn update_location(
+        &self,
+        context: &Context<'_>,
+        input: UpdateLocation,
+    ) -> FieldResult<Location> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let previous_location: Option<Location> =
+            optional(db.locations.get_single_location(&input.id).await).extend()?;
+        let updated_location: Location = db
+            .locations
+            .update_location(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(
+            context,
+            "Location",
+            previous_location.as_ref(),
+            Some(&updated_location),
+        )
+        .await;
+        publish(
+            context,
+            Event::LocationChanged(LocationChange {
+                kind: ChangeKind::Updated,
+                location: updated_location.clone(),
+            }),
+        );
+
+        Ok(updated_location)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageLocations)")]
+    async fn delete_location(
+        &self,
+        context: &Context<'_>,
+        input: DeleteLocation,
+    ) -> FieldResult<Location> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_location: Location = db
+            .locations
+            .delete_location(Deletion::new(input.id, subject(context)))
+            .await
+            .extend()?;
+        audit_deletion(context, "Location", &deleted_location).await;
+        publish(
+            context,
+            Event::LocationChanged(LocationChange {
+                kind: ChangeKind::Deleted,
+                location: deleted_location.clone(),
+            }),
+        );
+
+        Ok(deleted_location)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageLocations)")]
+    async fn restore_location(
+        &self,
+        context: &Context<'_>,
+        input: RestoreLocation,
+    ) -> FieldResult<Location> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_location: Location = db
+            .locations
+            .get_deleted_location(&input.id)
+            .await
+            .extend()?;
+        let restored_location: Location =
+            db.locations.restore_location(&input.id).await.extend()?;
+        audit(
+            context,
+            "Location",
+            Some(&deleted_location),
+            Some(&restored_location),
+        )
+        .await;
+        publish(
+            context,
+            Event::LocationChanged(LocationChange {
+                kind: ChangeKind::Restored,
+                location: restored_location.clone(),
+            }),
+        );
+
+        Ok(restored_location)
+    }
+
     /*
      * Rank Mutations
      */
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageRanks)")]
     async fn create_rank(&self, context: &Context<'_>, input: CreateRank) -> FieldResult<Rank> {
-        let db: &&MongoDB  = &context.data_unchecked::<MongoDB>();
-        let created_rank: Rank = db.create_rank(input).unwrap();
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let created_rank: Rank = db
+            .ranks
+            .create_rank(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(context, "Rank", None, Some(&created_rank)).await;
+        publish(
+            context,
+            Event::RankChanged(RankChange {
+                kind: ChangeKind::Created,
+                rank: created_rank.clone(),
+            }),
+        );
 
         Ok(created_rank)
     }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageRanks)")]
+    async fn update_rank(&self, context: &Context<'_>, input: UpdateRank) -> FieldResult<Rank> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let previous_rank: Option<Rank> =
+            optional(db.ranks.get_single_rank(&input.id).await).extend()?;
+        let updated_rank: Rank = db
+            .ranks
+            .update_rank(input, validation_mode(context))
+            .await
+            .extend()?;
+        audit(context, "Rank", previous_rank.as_ref(), Some(&updated_rank)).await;
+        publish(
+            context,
+            Event::RankChanged(RankChange {
+                kind: ChangeKind::Updated,
+                rank: updated_rank.clone(),
+            }),
+        );
+
+        Ok(updated_rank)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageRanks)")]
+    async fn delete_rank(&self, context: &Context<'_>, input: DeleteRank) -> FieldResult<Rank> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_rank: Rank = db
+            .ranks
+            .delete_rank(Deletion::new(input.id, subject(context)))
+            .await
+            .extend()?;
+        audit_deletion(context, "Rank", &deleted_rank).await;
+        publish(
+            context,
+            Event::RankChanged(RankChange {
+                kind: ChangeKind::Deleted,
+                rank: deleted_rank.clone(),
+            }),
+        );
+
+        Ok(deleted_rank)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageRanks)")]
+    async fn restore_rank(&self, context: &Context<'_>, input: RestoreRank) -> FieldResult<Rank> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_rank: Rank = db.ranks.get_deleted_rank(&input.id).await.extend()?;
+        let restored_rank: Rank = db.ranks.restore_rank(&input.id).await.extend()?;
+        audit(context, "Rank", Some(&deleted_rank), Some(&restored_rank)).await;
+        publish(
+            context,
+            Event::RankChanged(RankChange {
+                kind: ChangeKind::Restored,
+                rank: restored_rank.clone(),
+            }),
+        );
+
+        Ok(restored_rank)
+    }
+
+    /*
+     * Shift Mutations
+     */
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageShifts)")]
+    async fn create_shift(&self, context: &Context<'_>, input: CreateShift) -> FieldResult<Shift> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let created_shift: Shift = db.shifts.create_shift(input).await.extend()?;
+        audit(context, "Shift", None, Some(&created_shift)).await;
+
+        Ok(created_shift)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageShifts)")]
+    async fn update_shift(&self, context: &Context<'_>, input: UpdateShift) -> FieldResult<Shift> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let previous_shift: Option<Shift> =
+            optional(db.shifts.get_single_shift(&input.id).await).extend()?;
+        let updated_shift: Shift = db.shifts.update_shift(input).await.extend()?;
+        audit(
+            context,
+            "Shift",
+            previous_shift.as_ref(),
+            Some(&updated_shift),
+        )
+        .await;
+
+        Ok(updated_shift)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageShifts)")]
+    async fn delete_shift(&self, context: &Context<'_>, input: DeleteShift) -> FieldResult<Shift> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let deleted_shift: Shift = db.shifts.delete_shift(input).await.extend()?;
+        audit(context, "Shift", Some(&deleted_shift), None).await;
+
+        Ok(deleted_shift)
+    }
+
+    /*
+     * Absence Mutations
+     */
+    #[graphql(
+        guard = "OwnAbsenceGuard::request(&input).or(PermissionGuard::new(Permission::ManageAbsences))"
+    )]
+    async fn request_absence(
+        &self,
+        context: &Context<'_>,
+        input: RequestAbsence,
+    ) -> FieldResult<Absence> {
+        let db: &Repositories = context.data_unchecked::<Repositories>();
+        let requested_absence: Absence = db.absences.request_absence(input).await.extend()?;
+        audit(context, "Absence", None, Some(&requested_absence)).await;
+
+        Ok(requested_absence)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageAbsences)")]
+    async fn approve_absence(
+        &self,
+        context: &Context<'_>,
+        input: DecideAbsence,
+    ) -> FieldResult<Absence> {
+        decide(context, input, AbsenceState::Approved).await
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageAbsences)")]
+    async fn reject_absence(
+        &self,
+        context: &Context<'_>,
+        input: DecideAbsence,
+    ) -> FieldResult<Absence> {
+        decide(context, input, AbsenceState::Rejected).await
+    }
+
+    #[graphql(
+        guard = "OwnAbsenceGuard::cancel(&input).or(PermissionGuard::new(Permission::ManageAbsences))"
+    )]
+    async fn cancel_absence(
+        &self,
+        context: &Context<'_>,
+        input: DecideAbsence,
+    ) -> FieldResult<Absence> {
+        decide(context, input, AbsenceState::Cancelled).await
+    }
 }
 
-pub type ProjectSchema = Schema<Query, Mutation, EmptySubscription>;
\ No newline at end of file
+pub type ProjectSchema = Schema<Query, Mutation, Subscription>;
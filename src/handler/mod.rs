@@ -1 +1,9 @@
-pub mod graphql_handler;
\ No newline at end of file
+pub mod data_loader;
+pub mod graphql_guard;
+pub mod graphql_handler;
+pub mod relation_handler;
+pub mod request_guard;
+#[cfg(test)]
+mod resolver_tests;
+pub mod subscription_handler;
+pub mod websocket_handler;
@@ -0,0 +1,224 @@
+use crate::{
+    config::repository::TimeRange,
+    handler::data_loader::{
+        CurrentAbsenceLoader, EmployeeAbsencesLoader, EmployeeLoader, EmployeeShiftsLoader,
+        LocationLoader, LocationStoresLoader, RankEmployeesLoader, RankLoader, RequestLoader,
+        StatusHistoryLoader, StoreEmployeesLoader, StoreLoader,
+    },
+    schema::project_schema::{
+        Absence, Employee, Location, Rank, Shift, Status, StatusTransition, Store,
+    },
+};
+use async_graphql::{ComplexObject, Context, FieldResult, ResultExt};
+use mongodb::bson::DateTime;
+use std::collections::HashMap;
+
+#[ComplexObject]
+impl Employee {
+    /// `VACATION` or `ILLNESS` while an approved absence covers today (UTC),
+    /// the stored status otherwise. `statusIn` filters, `statusHistory`,
+    /// `timeInStatus` and `employeeStatusChanged` go by this status too;
+    /// only ordering by `STATUS` uses the stored one.
+    async fn status(&self, context: &Context<'_>) -> FieldResult<Option<Status>> {
+        let loader: &RequestLoader<CurrentAbsenceLoader> =
+            context.data_unchecked::<RequestLoader<CurrentAbsenceLoader>>();
+        let employee_id: String = match self.id {
+            Some(id) => id.to_string(),
+            None => return Ok(self.status),
+        };
+        let absence: Option<Absence> = loader.load_one(employee_id).await.extend()?;
+
+        Ok(absence.map(|absence| absence.kind.status()).or(self.status))
+    }
+
+    #[graphql(name = "stores")]
+    async fn assigned_stores(&self, context: &Context<'_>) -> FieldResult<Vec<Store>> {
+        let loader: &RequestLoader<StoreLoader> =
+            context.data_unchecked::<RequestLoader<StoreLoader>>();
+        let store_ids: Vec<String> = self.stores.clone().unwrap_or_default();
+        let mut store_map: HashMap<String, Store> =
+            loader.load_many(store_ids.iter().cloned()).await.extend()?;
+
+        let store_vec: Vec<Store> = store_ids
+            .iter()
+            .filter_map(|store_id| store_map.remove(store_id))
+            .collect();
+
+        Ok(store_vec)
+    }
+
+    /// The stores the employee is assigned to, leaving out deleted ones.
+    async fn store_ids(&self, context: &Context<'_>) -> FieldResult<Option<Vec<String>>> {
+        if self.stores.is_none() {
+            return Ok(None);
+        }
+        let store_vec: Vec<Store> = self.assigned_stores(context).await?;
+
+        Ok(Some(
+            store_vec
+                .iter()
+                .filter_map(|store| store.id.map(|id| id.to_string()))
+                .collect(),
+        ))
+    }
+
+    async fn rank(&self, context: &Context<'_>) -> FieldResult<Option<Rank>> {
+        let loader: &RequestLoader<RankLoader> =
+            context.data_unchecked::<RequestLoader<RankLoader>>();
+        match self.rank_id.clone() {
+            Some(rank_id) => loader.load_one(rank_id).await.extend(),
+            None => Ok(None),
+        }
+    }
+
+    /// The rank of the employee, unless it is deleted.
+    async fn rank_id(&self, context: &Context<'_>) -> FieldResult<Option<String>> {
+        let rank: Option<Rank> = self.rank(context).await?;
+
+        Ok(rank.and(self.rank_id.clone()))
+    }
+
+    /// The shifts of the employee overlapping `from`..`to`, by start time.
+    async fn shifts(
+        &self,
+        context: &Context<'_>,
+        from: Option<DateTime>,
+        to: Option<DateTime>,
+    ) -> FieldResult<Vec<Shift>> {
+        let loader: &RequestLoader<EmployeeShiftsLoader> =
+            context.data_unchecked::<RequestLoader<EmployeeShiftsLoader>>();
+        let employee_id: String = self.id.map(|id| id.to_string()).unwrap_or_default();
+        let shift_vec: Option<Vec<Shift>> = loader
+            .load_one((employee_id, TimeRange { from, to }))
+            .await
+            .extend()?;
+
+        Ok(shift_vec.unwrap_or_default())
+    }
+
+    /// The changes of the status made within `from`..`to`, oldest first.
+    /// Approving or cancelling an absence that covers the day is a change;
+    /// an absence beginning or ending with the calendar is not recorded.
+    async fn status_history(
+        &self,
+        context: &Context<'_>,
+        from: Option<DateTime>,
+        to: Option<DateTime>,
+    ) -> FieldResult<Vec<StatusTransition>> {
+        let loader: &RequestLoader<StatusHistoryLoader> =
+            context.data_unchecked::<RequestLoader<StatusHistoryLoader>>();
+        let employee_id: String = self.id.map(|id| id.to_string()).unwrap_or_default();
+        let transition_vec: Option<Vec<StatusTransition>> = loader
+            .load_one((employee_id, TimeRange { from, to }))
+            .await
+            .extend()?;
+
+        Ok(transition_vec.unwrap_or_default())
+    }
+
+    /// The absences of the employee, by first day.
+    async fn absences(&self, context: &Context<'_>) -> FieldResult<Vec<Absence>> {
+        let loader: &RequestLoader<EmployeeAbsencesLoader> =
+            context.data_unchecked::<RequestLoader<EmployeeAbsencesLoader>>();
+        let employee_id: String = self.id.map(|id| id.to_string()).unwrap_or_default();
+        let absence_vec: Option<Vec<Absence>> = loader.load_one(employee_id).await.extend()?;
+
+        Ok(absence_vec.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Store {
+    async fn location(&self, context: &Context<'_>) -> FieldResult<Option<Location>> {
+        let loader: &RequestLoader<LocationLoader> =
+            context.data_unchecked::<RequestLoader<LocationLoader>>();
+
+        match self.location_id.clone() {
+            Some(location_id) => loader.load_one(location_id).await.extend(),
+            None => Ok(None),
+        }
+    }
+
+    /// The location of the store, unless it is deleted.
+    async fn location_id(&self, context: &Context<'_>) -> FieldResult<Option<String>> {
+        let location: Option<Location> = self.location(context).await?;
+
+        Ok(location.and(self.location_id.clone()))
+    }
+
+    async fn employees(&self, context: &Context<'_>) -> FieldResult<Vec<Employee>> {
+        let loader: &RequestLoader<StoreEmployeesLoader> =
+            context.data_unchecked::<RequestLoader<StoreEmployeesLoader>>();
+        let store_id: String = self.id.map(|id| id.to_string()).unwrap_or_default();
+        let employee_vec: Option<Vec<Employee>> = loader.load_one(store_id).await.extend()?;
+
+        Ok(employee_vec.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Location {
+    async fn stores(&self, context: &Context<'_>) -> FieldResult<Vec<Store>> {
+        let loader: &RequestLoader<LocationStoresLoader> =
+            context.data_unchecked::<RequestLoader<LocationStoresLoader>>();
+        let location_id: String = self.id.map(|id| id.to_string()).unwrap_or_default();
+        let store_vec: Option<Vec<Store>> = loader.load_one(location_id).await.extend()?;
+
+        Ok(store_vec.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Rank {
+    async fn employees(&self, context: &Context<'_>) -> FieldResult<Vec<Employee>> {
+        let loader: &RequestLoader<RankEmployeesLoader> =
+            context.data_unchecked::<RequestLoader<RankEmployeesLoader>>();
+        let rank_id: String = self.id.map(|id| id.to_string()).unwrap_or_default();
+        let employee_vec: Option<Vec<Employee>> = loader.load_one(rank_id).await.extend()?;
+
+        Ok(employee_vec.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Shift {
+    async fn employee(&self, context: &Context<'_>) -> FieldResult<Option<Employee>> {
+        let loader: &RequestLoader<EmployeeLoader> =
+            context.data_unchecked::<RequestLoader<EmployeeLoader>>();
+
+        loader.load_one(self.employee_id.clone()).await.extend()
+    }
+
+    async fn store(&self, context: &Context<'_>) -> FieldResult<Option<Store>> {
+        let loader: &RequestLoader<StoreLoader> =
+            context.data_unchecked::<RequestLoader<StoreLoader>>();
+
+        loader.load_one(self.store_id.clone()).await.extend()
+    }
+
+    async fn required_rank(&self, context: &Context<'_>) -> FieldResult<Option<Rank>> {
+        let loader: &RequestLoader<RankLoader> =
+            context.data_unchecked::<RequestLoader<RankLoader>>();
+        match self.required_rank_id.clone() {
+            Some(rank_id) => loader.load_one(rank_id).await.extend(),
+            None => Ok(None),
+        }
+    }
+
+    /// The rank required for the shift, unless it is deleted.
+    async fn required_rank_id(&self, context: &Context<'_>) -> FieldResult<Option<String>> {
+        let rank: Option<Rank> = self.required_rank(context).await?;
+
+        Ok(rank.and(self.required_rank_id.clone()))
+    }
+}
+
+#[ComplexObject]
+impl Absence {
+    async fn employee(&self, context: &Context<'_>) -> FieldResult<Option<Employee>> {
+        let loader: &RequestLoader<EmployeeLoader> =
+            context.data_unchecked::<RequestLoader<EmployeeLoader>>();
+
+        loader.load_one(self.employee_id.clone()).await.extend()
+    }
+}
@@ -0,0 +1,88 @@
+use crate::config::{
+    auth::{JwtVerifier, Principal},
+    validation::ValidationMode,
+};
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+use std::net::IpAddr;
+
+pub const VALIDATION_MODE_HEADER: &str = "X-Validation-Mode";
+
+/// The principal behind the request's bearer token, if it sent one. A request
+/// without an `Authorization` header is anonymous; a request with a token
+/// that does not verify is rejected outright.
+pub struct Authentication(pub Option<Principal>);
+
+/// The address of the client that sent the request, as far as Rocket can
+/// tell. Behind a proxy this is the `X-Real-IP` header, if Rocket is
+/// configured to trust it.
+pub struct ClientAddress(pub Option<IpAddr>);
+
+/// Legacy clients opt into lenient validation by sending
+/// `X-Validation-Mode: lenient`; everyone else is validated strictly.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ValidationMode {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let lenient: bool = request
+            .headers()
+            .get_one(VALIDATION_MODE_HEADER)
+            .is_some_and(|mode| mode.eq_ignore_ascii_case("lenient"));
+
+        Outcome::Success(if lenient {
+            ValidationMode::Lenient
+        } else {
+            ValidationMode::Strict
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientAddress {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientAddress(request.client_ip()))
+    }
+}
+
+/// Verifies the value of an `Authorization` header, which must carry a bearer
+/// token. Shared by the HTTP guard and the WebSocket `connection_init`
+/// payload so both accept exactly the same tokens.
+pub fn authenticate(header: &str, verifier: &JwtVerifier) -> Result<Principal, String> {
+    let token: &str = header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| String::from("expected a bearer token"))?;
+
+    verifier.verify(token.trim())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authentication {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let header: &str = match request.headers().get_one("Authorization") {
+            Some(header) => header,
+            None => return Outcome::Success(Authentication(None)),
+        };
+
+        let verifier: &JwtVerifier = match request.rocket().state::<JwtVerifier>() {
+            Some(verifier) => verifier,
+            None => {
+                return Outcome::Failure((
+                    Status::InternalServerError,
+                    String::from("no token verifier configured"),
+                ))
+            }
+        };
+
+        match authenticate(header, verifier) {
+            Ok(principal) => Outcome::Success(Authentication(Some(principal))),
+            Err(error) => Outcome::Failure((Status::Unauthorized, error)),
+        }
+    }
+}
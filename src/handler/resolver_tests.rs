@@ -0,0 +1,1748 @@
+use crate::{
+    config::{
+        auth::Principal,
+        error::RepositoryError,
+        event_bus::{Event, EventBus},
+        integrity::{DeletePolicies, OnDelete},
+        memory::InMemory,
+        mongo::MongoDB,
+        mongo_config::MongoConfig,
+        pagination::{Page, PageRequest},
+        postgres::PostgresDB,
+        repository::{
+            AuditRepository, PurgeReport, Repositories, StatusHistoryRepository, TimeRange,
+        },
+        retention::Retention,
+    },
+    handler::{
+        data_loader::with_loaders,
+        graphql_handler::{Mutation, ProjectSchema, Query},
+        subscription_handler::Subscription,
+    },
+    schema::project_schema::{
+        AuditEntry, AuditFilter, EmployeeStatusChange, Status, StatusTransition,
+    },
+};
+use async_graphql::{Request, Response, Schema, Value};
+use async_graphql_rocket::GraphQLRequest;
+use async_trait::async_trait;
+use chrono::{Days, NaiveDate, Utc};
+use futures_util::{FutureExt, StreamExt};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::serde::json::{json, Value as Json};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPool},
+    Executor,
+};
+use std::{env, str::FromStr, sync::Arc};
+
+const UNKNOWN_ID: &str = "000000000000000000000000";
+
+/// Runs each listed test once per backend. The Postgres and MongoDB runs are
+/// ignored by default and need a server at `POSTGRES_TEST_URI` or
+/// `MONGO_TEST_URI`; run them with `cargo test -- --ignored`. Every Postgres
+/// run works in a schema of its own, every MongoDB run in a database of its
+/// own.
+macro_rules! backend_tests {
+    ($($test:ident),* $(,)?) => {
+        mod memory {
+            $(#[rocket::async_test]
+            async fn $test() {
+                super::$test(super::Backend::Memory).await
+            })*
+        }
+
+        mod postgres {
+            $(#[rocket::async_test]
+            #[ignore = "needs a PostgreSQL server at POSTGRES_TEST_URI"]
+            async fn $test() {
+                super::$test(super::Backend::Postgres).await
+            })*
+        }
+
+        mod mongo {
+            $(#[rocket::async_test]
+            #[ignore = "needs a MongoDB server at MONGO_TEST_URI"]
+            async fn $test() {
+                super::$test(super::Backend::Mongo).await
+            })*
+        }
+    };
+}
+
+backend_tests! {
+    creates_and_resolves_relations,
+    rejects_unknown_references,
+    reports_invalid_and_missing_ids,
+    updates_employee_stores,
+    restricts_deleting_referenced_rank,
+    rejects_duplicate_names,
+    refuses_to_write_unknown_status,
+    cascades_location_deletion,
+    clears_rank_references,
+    nullifies_store_locations,
+    pages_through_ordered_employees,
+    pages_backwards,
+    pages_past_missing_sort_values,
+    filters_employees_by_name,
+    forbids_mutations_without_permission,
+    publishes_only_actual_status_changes,
+    schedules_shifts_at_assigned_stores,
+    rejects_overlapping_shifts,
+    removes_shifts_with_their_employee,
+    derives_status_from_approved_absences,
+    filters_and_reports_by_derived_status,
+    rejects_overlapping_absences,
+    allows_only_forward_absence_transitions,
+    lets_employees_request_their_own_absences,
+    records_status_transitions,
+    audits_every_mutation,
+    keeps_mutations_when_auditing_fails,
+    keeps_status_changes_when_history_fails,
+    soft_deletes_and_restores,
+    restores_cascaded_dependents,
+    frees_names_of_deleted_entries,
+    restores_shifts_and_absences_with_their_employee,
+    purges_expired_deletions,
+    updates_with_expected_version,
+    resolves_reverse_edges_for_every_parent,
+}
+
+enum Backend {
+    Memory,
+    Postgres,
+    Mongo,
+}
+
+struct Harness {
+    schema: ProjectSchema,
+    repositories: Repositories,
+    bus: EventBus,
+}
+
+impl Harness {
+    async fn new(backend: Backend, policies: DeletePolicies) -> Self {
+        let repositories: Repositories = match backend {
+            Backend::Memory => Repositories::new(InMemory::new(policies)),
+            Backend::Postgres => Repositories::new(postgres_backend(policies).await),
+            Backend::Mongo => Repositories::new(mongo_backend(policies).await),
+        };
+
+        Harness::with_repositories(repositories)
+    }
+
+    fn with_repositories(repositories: Repositories) -> Self {
+        let bus: EventBus = EventBus::default();
+        let schema: ProjectSchema = Schema::build(Query, Mutation, Subscription)
+            .data(repositories.clone())
+            .data(bus.clone())
+            .finish();
+
+        Harness {
+            schema,
+            repositories,
+            bus,
+        }
+    }
+
+    async fn execute_as(&self, principal: Principal, query: &str) -> Response {
+        let request: GraphQLRequest = with_loaders(
+            GraphQLRequest(Request::new(query).data(principal)),
+            &self.repositories,
+        );
+        self.schema.execute(request.0).await
+    }
+
+    async fn execute(&self, query: &str) -> Response {
+        self.execute_as(root(), query).await
+    }
+
+    async fn data(&self, query: &str) -> Json {
+        let response: Response = self.execute(query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()
+    }
+
+    async fn create(&self, mutation: &str) -> String {
+        let data: Json = self
+            .data(&format!("mutation {{ created: {} {{ id }} }}", mutation))
+            .await;
+        String::from(data["created"]["id"].as_str().unwrap())
+    }
+
+    async fn create_rank(&self) -> String {
+        self.create(r#"createRank(input: {name: "Staff"})"#).await
+    }
+
+    async fn create_employee(&self, first_name: &str, stores: &[&str], rank_id: &str) -> String {
+        self.create(&format!(r#"createEmployee(input: {{firstName: "{}", lastName: "Doe", stores: {:?}, rankId: "{}"}})"#,
+            first_name, stores, rank_id)).await
+    }
+
+    async fn create_store(&self, name: &str) -> String {
+        let location_id: String = self
+            .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+            .await;
+        self.create(&format!(
+            r#"createStore(input: {{name: "{}", locationId: "{}"}})"#,
+            name, location_id
+        ))
+        .await
+    }
+
+    async fn create_shift(
+        &self,
+        employee_id: &str,
+        store_id: &str,
+        starts_at: &str,
+        ends_at: &str,
+    ) -> Response {
+        self.execute(&format!(r#"mutation {{ createShift(input: {{employeeId: "{}", storeId: "{}", startsAt: "{}", endsAt: "{}"}}) {{ id }} }}"#,
+            employee_id, store_id, starts_at, ends_at)).await
+    }
+
+    async fn request_absence(
+        &self,
+        principal: Principal,
+        employee_id: &str,
+        starts_on: NaiveDate,
+        ends_on: NaiveDate,
+    ) -> Response {
+        self.execute_as(principal, &format!(r#"mutation {{ requestAbsence(input: {{employeeId: "{}", type: ILLNESS, startsOn: "{}", endsOn: "{}"}}) {{ id }} }}"#,
+            employee_id, starts_on, ends_on)).await
+    }
+
+    async fn decide_absence(
+        &self,
+        principal: Principal,
+        mutation: &str,
+        absence_id: &str,
+    ) -> Response {
+        self.execute_as(
+            principal,
+            &format!(
+                r#"mutation {{ {}(input: {{id: "{}"}}) {{ state }} }}"#,
+                mutation, absence_id
+            ),
+        )
+        .await
+    }
+}
+
+fn root() -> Principal {
+    Principal {
+        subject: String::from("root"),
+        is_admin: true,
+    }
+}
+
+fn created_id(response: &Response, field: &str) -> String {
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    String::from(
+        response.data.clone().into_json().unwrap()[field]["id"]
+            .as_str()
+            .unwrap(),
+    )
+}
+
+async fn postgres_backend(policies: DeletePolicies) -> PostgresDB {
+    let uri: String = env::var("POSTGRES_TEST_URI").expect("POSTGRES_TEST_URI is not set");
+    let schema_name: String = format!("test_{}", ObjectId::new().to_hex());
+
+    let setup: PgPool = PgPool::connect(&uri).await.unwrap();
+    setup
+        .execute(format!("CREATE SCHEMA {}", schema_name).as_str())
+        .await
+        .unwrap();
+    setup.close().await;
+
+    let options: PgConnectOptions = PgConnectOptions::from_str(&uri)
+        .unwrap()
+        .options([("search_path", schema_name)]);
+    let db: PostgresDB = PostgresDB::new(PgPool::connect_with(options).await.unwrap(), policies);
+    db.create_tables().await.unwrap();
+    db
+}
+
+async fn mongo_backend(policies: DeletePolicies) -> MongoDB {
+    let config: MongoConfig = MongoConfig {
+        uri: env::var("MONGO_TEST_URI").expect("MONGO_TEST_URI is not set"),
+        database: format!("test_{}", ObjectId::new().to_hex()),
+        ..MongoConfig::default()
+    };
+
+    let db: MongoDB = MongoDB::connect(&config, policies).await.unwrap();
+    db.migrate().await.unwrap();
+    db
+}
+
+fn error_code(response: &Response) -> Option<&Value> {
+    response
+        .errors
+        .first()
+        .and_then(|error| error.extensions.as_ref())
+        .and_then(|extensions| extensions.get("code"))
+}
+
+async fn creates_and_resolves_relations(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let location_id: String = harness
+        .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+        .await;
+    let store_id: String = harness
+        .create(&format!(
+            r#"createStore(input: {{name: "Mitte", locationId: "{}"}})"#,
+            location_id
+        ))
+        .await;
+    let rank_id: String = harness
+        .create(r#"createRank(input: {name: "Manager"})"#)
+        .await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+
+    let data: Json = harness.data(&format!(
+        r#"{{ getEmployee(input: {{id: "{}"}}) {{ firstName status stores {{ name location {{ state }} employees {{ id }} }} rank {{ name }} }} }}"#,
+        employee_id)).await;
+
+    assert_eq!(
+        data["getEmployee"],
+        json!({
+            "firstName": "Jane",
+            "status": "NONE",
+            "stores": [{"name": "Mitte", "location": {"state": "Berlin"}, "employees": [{"id": employee_id}]}],
+            "rank": {"name": "Manager"},
+        })
+    );
+}
+
+async fn rejects_unknown_references(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let response: Response = harness.execute(&format!(
+        r#"mutation {{ createEmployee(input: {{firstName: "Jane", lastName: "Doe", stores: ["{}"], rankId: ""}}) {{ id }} }}"#,
+        UNKNOWN_ID)).await;
+
+    assert_eq!(
+        error_code(&response),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert_eq!(
+        harness.data("{ getAllEmployees { id } }").await,
+        json!({"getAllEmployees": []})
+    );
+}
+
+async fn reports_invalid_and_missing_ids(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+
+    let response: Response = harness
+        .execute(r#"{ getStore(input: {id: "not-an-id"}) { id } }"#)
+        .await;
+    assert_eq!(error_code(&response), Some(&Value::from("INVALID_ID")));
+
+    let response: Response = harness
+        .execute(&format!(
+            r#"{{ getStore(input: {{id: "{}"}}) {{ id }} }}"#,
+            UNKNOWN_ID
+        ))
+        .await;
+    assert_eq!(error_code(&response), Some(&Value::from("NOT_FOUND")));
+}
+
+async fn updates_employee_stores(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let location_id: String = harness
+        .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+        .await;
+    let first_store: String = harness
+        .create(&format!(
+            r#"createStore(input: {{name: "Mitte", locationId: "{}"}})"#,
+            location_id
+        ))
+        .await;
+    let second_store: String = harness
+        .create(&format!(
+            r#"createStore(input: {{name: "Pankow", locationId: "{}"}})"#,
+            location_id
+        ))
+        .await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&first_store], &rank_id)
+        .await;
+
+    let data: Json = harness.data(&format!(
+        r#"mutation {{ updateEmployee(input: {{id: "{}", addStores: ["{}", "{}"], removeStores: ["{}"]}}) {{ storeIds }} }}"#,
+        employee_id, second_store, second_store, first_store)).await;
+
+    assert_eq!(data["updateEmployee"]["storeIds"], json!([second_store]));
+}
+
+async fn restricts_deleting_referenced_rank(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    harness.create_employee("Jane", &[], &rank_id).await;
+
+    let response: Response = harness
+        .execute(&format!(
+            r#"mutation {{ deleteRank(input: {{id: "{}"}}) {{ id }} }}"#,
+            rank_id
+        ))
+        .await;
+
+    assert_eq!(error_code(&response), Some(&Value::from("HAS_DEPENDENTS")));
+}
+
+async fn rejects_duplicate_names(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    harness.create_rank().await;
+    let rank_id: String = harness
+        .create(r#"createRank(input: {name: "Manager"})"#)
+        .await;
+
+    let created: Response = harness
+        .execute(r#"mutation { createRank(input: {name: "Staff"}) { id } }"#)
+        .await;
+    let renamed: Response = harness
+        .execute(&format!(
+            r#"mutation {{ updateRank(input: {{id: "{}", name: "Staff"}}) {{ id }} }}"#,
+            rank_id
+        ))
+        .await;
+    let kept: Json = harness
+        .data(&format!(
+            r#"mutation {{ updateRank(input: {{id: "{}", name: "Manager"}}) {{ name }} }}"#,
+            rank_id
+        ))
+        .await;
+
+    assert_eq!(error_code(&created), Some(&Value::from("DUPLICATE")));
+    assert_eq!(error_code(&renamed), Some(&Value::from("DUPLICATE")));
+    assert_eq!(kept["updateRank"]["name"], "Manager");
+}
+
+async fn refuses_to_write_unknown_status(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+
+    let created: Response = harness.execute(&format!(
+        r#"mutation {{ createEmployee(input: {{firstName: "John", lastName: "Doe", status: UNKNOWN, rankId: "{}"}}) {{ id }} }}"#, rank_id)).await;
+    let updated: Response = harness
+        .execute(&format!(
+            r#"mutation {{ updateEmployee(input: {{id: "{}", status: UNKNOWN}}) {{ id }} }}"#,
+            employee_id
+        ))
+        .await;
+
+    assert_eq!(
+        error_code(&created),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert_eq!(
+        error_code(&updated),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    let data: Json = harness
+        .data(&format!(
+            r#"{{ getEmployee(input: {{id: "{}"}}) {{ status }} }}"#,
+            employee_id
+        ))
+        .await;
+    assert_eq!(data["getEmployee"]["status"], "NONE");
+}
+
+async fn cascades_location_deletion(backend: Backend) {
+    let harness: Harness = Harness::new(
+        backend,
+        DeletePolicies {
+            employee_store: OnDelete::Cascade,
+            employee_rank: OnDelete::Restrict,
+            store_location: OnDelete::Cascade,
+        },
+    )
+    .await;
+    let rank_id: String = harness.create_rank().await;
+    let location_id: String = harness
+        .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+        .await;
+    let store_id: String = harness
+        .create(&format!(
+            r#"createStore(input: {{name: "Mitte", locationId: "{}"}})"#,
+            location_id
+        ))
+        .await;
+    harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteLocation(input: {{id: "{}"}}) {{ id }} }}"#,
+            location_id
+        ))
+        .await;
+
+    let data: Json = harness
+        .data("{ getAllStores { id } getAllEmployees { storeIds } }")
+        .await;
+    assert_eq!(
+        data,
+        json!({"getAllStores": [], "getAllEmployees": [{"storeIds": []}]})
+    );
+}
+
+async fn clears_rank_references(backend: Backend) {
+    let harness: Harness = Harness::new(
+        backend,
+        DeletePolicies {
+            employee_store: OnDelete::Restrict,
+            employee_rank: OnDelete::Cascade,
+            store_location: OnDelete::Restrict,
+        },
+    )
+    .await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteRank(input: {{id: "{}"}}) {{ id }} }}"#,
+            rank_id
+        ))
+        .await;
+
+    let data: Json = harness
+        .data(&format!(
+            r#"{{ getEmployee(input: {{id: "{}"}}) {{ rankId rank {{ id }} }} }}"#,
+            employee_id
+        ))
+        .await;
+    assert_eq!(data["getEmployee"], json!({"rankId": null, "rank": null}));
+}
+
+async fn nullifies_store_locations(backend: Backend) {
+    let harness: Harness = Harness::new(
+        backend,
+        DeletePolicies {
+            employee_store: OnDelete::Restrict,
+            employee_rank: OnDelete::Restrict,
+            store_location: OnDelete::Nullify,
+        },
+    )
+    .await;
+    let location_id: String = harness
+        .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+        .await;
+    let store_id: String = harness
+        .create(&format!(
+            r#"createStore(input: {{name: "Mitte", locationId: "{}"}})"#,
+            location_id
+        ))
+        .await;
+
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteLocation(input: {{id: "{}"}}) {{ id }} }}"#,
+            location_id
+        ))
+        .await;
+
+    let data: Json = harness
+        .data(&format!(
+            r#"{{ getStore(input: {{id: "{}"}}) {{ locationId location {{ id }} }} }}"#,
+            store_id
+        ))
+        .await;
+    assert_eq!(
+        data["getStore"],
+        json!({"locationId": null, "location": null})
+    );
+}
+
+async fn pages_through_ordered_employees(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    for first_name in ["Eve", "Bob", "Dan", "Alice", "Carol"] {
+        harness.create_employee(first_name, &[], &rank_id).await;
+    }
+
+    let query = |after: &str| {
+        format!(
+            r#"{{ employees(first: 2, after: {}, orderBy: {{field: FIRST_NAME}}) {{ totalCount pageInfo {{ hasNextPage endCursor }} nodes {{ firstName }} }} }}"#,
+            after
+        )
+    };
+
+    let first: Json = harness.data(&query("null")).await;
+    assert_eq!(
+        first["employees"]["nodes"],
+        json!([{"firstName": "Alice"}, {"firstName": "Bob"}])
+    );
+    assert_eq!(first["employees"]["totalCount"], json!(5));
+    assert_eq!(first["employees"]["pageInfo"]["hasNextPage"], json!(true));
+
+    let cursor: String = first["employees"]["pageInfo"]["endCursor"].to_string();
+    let second: Json = harness.data(&query(&cursor)).await;
+    assert_eq!(
+        second["employees"]["nodes"],
+        json!([{"firstName": "Carol"}, {"firstName": "Dan"}])
+    );
+}
+
+async fn pages_backwards(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    for first_name in ["Eve", "Bob", "Dan", "Alice", "Carol"] {
+        harness.create_employee(first_name, &[], &rank_id).await;
+    }
+
+    let last: Json = harness.data(r#"{ employees(last: 2, orderBy: {field: FIRST_NAME, direction: DESC}) { pageInfo { hasPreviousPage startCursor } nodes { firstName } } }"#).await;
+    assert_eq!(
+        last["employees"]["nodes"],
+        json!([{"firstName": "Bob"}, {"firstName": "Alice"}])
+    );
+    assert_eq!(
+        last["employees"]["pageInfo"]["hasPreviousPage"],
+        json!(true)
+    );
+
+    let cursor: String = last["employees"]["pageInfo"]["startCursor"].to_string();
+    let previous: Json = harness.data(&format!(
+        r#"{{ employees(last: 2, before: {}, orderBy: {{field: FIRST_NAME, direction: DESC}}) {{ nodes {{ firstName }} }} }}"#,
+        cursor)).await;
+    assert_eq!(
+        previous["employees"]["nodes"],
+        json!([{"firstName": "Dan"}, {"firstName": "Carol"}])
+    );
+}
+
+async fn pages_past_missing_sort_values(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    for (first_name, status) in [
+        ("Alice", "WORKING"),
+        ("Bob", "null"),
+        ("Carol", "VACATION"),
+        ("Dan", "null"),
+        ("Eve", "WORKING"),
+    ] {
+        let employee_id: String = harness.create_employee(first_name, &[], &rank_id).await;
+        harness
+            .data(&format!(
+                r#"mutation {{ updateEmployee(input: {{id: "{}", status: {}}}) {{ id }} }}"#,
+                employee_id, status
+            ))
+            .await;
+    }
+
+    for direction in ["ASC", "DESC"] {
+        let page = |arguments: String| {
+            format!(
+                r#"{{ employees({}, orderBy: {{field: STATUS, direction: {}}}) {{ pageInfo {{ startCursor endCursor }} nodes {{ firstName }} }} }}"#,
+                arguments, direction
+            )
+        };
+        let names = |data: &Json| -> Vec<String> {
+            data["employees"]["nodes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|node| node["firstName"].as_str().unwrap().to_owned())
+                .collect()
+        };
+        let everyone: Vec<String> = names(&harness.data(&page(String::from("first: 10"))).await);
+        assert_eq!(everyone.len(), 5);
+
+        let mut forward: Vec<String> = Vec::new();
+        let mut cursor: String = String::from("null");
+        for _ in 0..5 {
+            let data: Json = harness
+                .data(&page(format!("first: 1, after: {}", cursor)))
+                .await;
+            forward.extend(names(&data));
+            cursor = data["employees"]["pageInfo"]["endCursor"].to_string();
+        }
+        assert_eq!(forward, everyone);
+
+        let mut backward: Vec<String> = Vec::new();
+        let mut cursor: String = String::from("null");
+        for _ in 0..5 {
+            let data: Json = harness
+                .data(&page(format!("last: 1, before: {}", cursor)))
+                .await;
+            backward.splice(0..0, names(&data));
+            cursor = data["employees"]["pageInfo"]["startCursor"].to_string();
+        }
+        assert_eq!(backward, everyone);
+    }
+}
+
+async fn filters_employees_by_name(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    for first_name in ["Jane", "Janet", "John"] {
+        harness.create_employee(first_name, &[], &rank_id).await;
+    }
+
+    let data: Json = harness.data(r#"{ getAllEmployees(filter: {namePrefix: "jan"}, orderBy: {field: FIRST_NAME, direction: DESC}) { firstName } }"#).await;
+
+    assert_eq!(
+        data["getAllEmployees"],
+        json!([{"firstName": "Janet"}, {"firstName": "Jane"}])
+    );
+}
+
+async fn forbids_mutations_without_permission(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let principal: Principal = Principal {
+        subject: employee_id,
+        is_admin: false,
+    };
+
+    let response: Response = harness
+        .execute_as(
+            principal,
+            r#"mutation { createRank(input: {name: "Manager"}) { id } }"#,
+        )
+        .await;
+
+    assert_eq!(error_code(&response), Some(&Value::from("FORBIDDEN")));
+}
+
+async fn publishes_only_actual_status_changes(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let mut changes = Box::pin(harness.bus.subscribe(|event| match event {
+        Event::EmployeeStatusChanged(change) => Some(change),
+        _ => None,
+    }));
+
+    let update: String = format!(
+        r#"mutation {{ updateEmployee(input: {{id: "{}", status: WORKING}}) {{ id }} }}"#,
+        employee_id
+    );
+    harness.data(&update).await;
+    harness.data(&update).await;
+
+    let change: EmployeeStatusChange = changes.next().await.unwrap();
+    assert_eq!(change.previous_status, Some(Status::None));
+    assert_eq!(change.employee.status, Some(Status::Working));
+    assert!(changes.next().now_or_never().is_none());
+}
+
+async fn schedules_shifts_at_assigned_stores(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let store_id: String = harness.create_store("Mitte").await;
+    let other_store_id: String = harness.create_store("Pankow").await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+
+    let created: Response = harness
+        .create_shift(
+            &employee_id,
+            &store_id,
+            "2024-05-06T08:00:00Z",
+            "2024-05-06T16:00:00Z",
+        )
+        .await;
+    let elsewhere: Response = harness
+        .create_shift(
+            &employee_id,
+            &other_store_id,
+            "2024-05-07T08:00:00Z",
+            "2024-05-07T16:00:00Z",
+        )
+        .await;
+    let reversed: Response = harness
+        .create_shift(
+            &employee_id,
+            &store_id,
+            "2024-05-08T16:00:00Z",
+            "2024-05-08T08:00:00Z",
+        )
+        .await;
+
+    assert!(created.errors.is_empty(), "{:?}", created.errors);
+    assert_eq!(
+        error_code(&elsewhere),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert_eq!(
+        error_code(&reversed),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    let data: Json = harness.data(&format!(r#"{{
+        shifts(storeId: "{}", from: "2024-05-06T00:00:00Z", to: "2024-05-07T00:00:00Z") {{ startsAt endsAt employee {{ firstName }} store {{ name }} }}
+        getEmployee(input: {{id: "{}"}}) {{ shifts(from: "2024-05-06T16:00:00Z") {{ id }} }}
+    }}"#, store_id, employee_id)).await;
+    assert_eq!(
+        data,
+        json!({
+            "shifts": [{"startsAt": "2024-05-06T08:00:00+00:00", "endsAt": "2024-05-06T16:00:00+00:00", "employee": {"firstName": "Jane"}, "store": {"name": "Mitte"}}],
+            "getEmployee": {"shifts": []},
+        })
+    );
+}
+
+async fn rejects_overlapping_shifts(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let store_id: String = harness.create_store("Mitte").await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+    harness
+        .create_shift(
+            &employee_id,
+            &store_id,
+            "2024-05-06T08:00:00Z",
+            "2024-05-06T16:00:00Z",
+        )
+        .await;
+
+    let overlapping: Response = harness
+        .create_shift(
+            &employee_id,
+            &store_id,
+            "2024-05-06T15:00:00Z",
+            "2024-05-06T20:00:00Z",
+        )
+        .await;
+    let adjacent: Response = harness
+        .create_shift(
+            &employee_id,
+            &store_id,
+            "2024-05-06T16:00:00Z",
+            "2024-05-06T20:00:00Z",
+        )
+        .await;
+    let adjacent_id: String = String::from(
+        adjacent.data.clone().into_json().unwrap()["createShift"]["id"]
+            .as_str()
+            .unwrap(),
+    );
+    let moved: Response = harness.execute(&format!(r#"mutation {{ updateShift(input: {{id: "{}", startsAt: "2024-05-06T12:00:00Z"}}) {{ id }} }}"#, adjacent_id)).await;
+
+    assert_eq!(
+        error_code(&overlapping),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert!(adjacent.errors.is_empty(), "{:?}", adjacent.errors);
+    assert_eq!(error_code(&moved), Some(&Value::from("VALIDATION_FAILED")));
+}
+
+async fn removes_shifts_with_their_employee(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let store_id: String = harness.create_store("Mitte").await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+    harness
+        .create_shift(
+            &employee_id,
+            &store_id,
+            "2024-05-06T08:00:00Z",
+            "2024-05-06T16:00:00Z",
+        )
+        .await;
+
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteEmployee(input: {{id: "{}"}}) {{ id }} }}"#,
+            employee_id
+        ))
+        .await;
+
+    let data: Json = harness.data(&format!(r#"{{ shifts(storeId: "{}", from: "2024-05-01T00:00:00Z", to: "2024-06-01T00:00:00Z") {{ id }} }}"#, store_id)).await;
+    assert_eq!(data, json!({"shifts": []}));
+}
+
+async fn derives_status_from_approved_absences(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let today: NaiveDate = Utc::now().date_naive();
+    let status_query: String = format!(
+        r#"{{ getEmployee(input: {{id: "{}"}}) {{ status absences {{ state decidedBy }} }} }}"#,
+        employee_id
+    );
+
+    let requested: Response = harness
+        .request_absence(
+            root(),
+            &employee_id,
+            today - Days::new(1),
+            today + Days::new(1),
+        )
+        .await;
+    let absence_id: String = created_id(&requested, "requestAbsence");
+    assert_eq!(
+        harness.data(&status_query).await["getEmployee"]["status"],
+        "NONE"
+    );
+
+    harness
+        .decide_absence(root(), "approveAbsence", &absence_id)
+        .await;
+    assert_eq!(
+        harness.data(&status_query).await["getEmployee"],
+        json!({
+            "status": "ILLNESS",
+            "absences": [{"state": "APPROVED", "decidedBy": "root"}],
+        })
+    );
+
+    harness
+        .decide_absence(root(), "cancelAbsence", &absence_id)
+        .await;
+    assert_eq!(
+        harness.data(&status_query).await["getEmployee"]["status"],
+        "NONE"
+    );
+}
+
+async fn filters_and_reports_by_derived_status(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let today: NaiveDate = Utc::now().date_naive();
+    let update = |status: &str| {
+        format!(
+            r#"mutation {{ updateEmployee(input: {{id: "{}", status: {}}}) {{ id }} }}"#,
+            employee_id, status
+        )
+    };
+    let report: String = format!(
+        r#"{{
+            working: getAllEmployees(filter: {{statusIn: [WORKING]}}) {{ firstName }}
+            ill: getAllEmployees(filter: {{statusIn: [ILLNESS]}}) {{ firstName status }}
+            getEmployee(input: {{id: "{}"}}) {{ statusHistory(from: "2000-01-01T00:00:00Z") {{ status }} }}
+        }}"#,
+        employee_id
+    );
+    harness.data(&update("WORKING")).await;
+    let mut changes = Box::pin(harness.bus.subscribe(|event| match event {
+        Event::EmployeeStatusChanged(change) => Some(change),
+        _ => None,
+    }));
+
+    let requested: Response = harness
+        .request_absence(root(), &employee_id, today, today + Days::new(2))
+        .await;
+    let absence_id: String = created_id(&requested, "requestAbsence");
+    harness
+        .decide_absence(root(), "approveAbsence", &absence_id)
+        .await;
+
+    assert_eq!(
+        harness.data(&report).await,
+        json!({
+            "working": [],
+            "ill": [{"firstName": "Jane", "status": "ILLNESS"}],
+            "getEmployee": {"statusHistory": [{"status": "NONE"}, {"status": "WORKING"}, {"status": "ILLNESS"}]},
+        })
+    );
+    let approved: EmployeeStatusChange = changes.next().await.unwrap();
+    assert_eq!(approved.previous_status, Some(Status::Working));
+    assert_eq!(approved.employee.status, Some(Status::Illness));
+
+    harness.data(&update("EMERGENCY_SERVICE")).await;
+    assert!(changes.next().now_or_never().is_none());
+
+    harness
+        .decide_absence(root(), "cancelAbsence", &absence_id)
+        .await;
+    let cancelled: EmployeeStatusChange = changes.next().await.unwrap();
+    assert_eq!(cancelled.previous_status, Some(Status::Illness));
+    assert_eq!(cancelled.employee.status, Some(Status::EmergencyService));
+    assert_eq!(
+        harness.data(&report).await["getEmployee"]["statusHistory"],
+        json!([{"status": "NONE"}, {"status": "WORKING"}, {"status": "ILLNESS"}, {"status": "EMERGENCY_SERVICE"}])
+    );
+}
+
+async fn rejects_overlapping_absences(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let day = |day: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+    let first: Response = harness
+        .request_absence(root(), &employee_id, day(6), day(10))
+        .await;
+
+    let overlapping: Response = harness
+        .request_absence(root(), &employee_id, day(10), day(12))
+        .await;
+    let reversed: Response = harness
+        .request_absence(root(), &employee_id, day(14), day(13))
+        .await;
+    harness
+        .decide_absence(
+            root(),
+            "rejectAbsence",
+            &created_id(&first, "requestAbsence"),
+        )
+        .await;
+    let after_rejection: Response = harness
+        .request_absence(root(), &employee_id, day(10), day(12))
+        .await;
+
+    assert_eq!(
+        error_code(&overlapping),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert_eq!(
+        error_code(&reversed),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert!(
+        after_rejection.errors.is_empty(),
+        "{:?}",
+        after_rejection.errors
+    );
+}
+
+async fn allows_only_forward_absence_transitions(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let day = |day: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+    let absence_id: String = created_id(
+        &harness
+            .request_absence(root(), &employee_id, day(6), day(6))
+            .await,
+        "requestAbsence",
+    );
+
+    let rejected: Response = harness
+        .decide_absence(root(), "rejectAbsence", &absence_id)
+        .await;
+    let approved: Response = harness
+        .decide_absence(root(), "approveAbsence", &absence_id)
+        .await;
+    let cancelled: Response = harness
+        .decide_absence(root(), "cancelAbsence", &absence_id)
+        .await;
+
+    assert!(rejected.errors.is_empty(), "{:?}", rejected.errors);
+    assert_eq!(
+        error_code(&approved),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    assert_eq!(
+        error_code(&cancelled),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+    let data: Json = harness
+        .data(&format!(
+            r#"{{ getAbsence(input: {{id: "{}"}}) {{ state }} }}"#,
+            absence_id
+        ))
+        .await;
+    assert_eq!(data["getAbsence"]["state"], "REJECTED");
+}
+
+async fn lets_employees_request_their_own_absences(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness
+        .create(r#"createRank(input: {name: "Staff", permissions: [REQUEST_ABSENCES]})"#)
+        .await;
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let colleague_id: String = harness.create_employee("John", &[], &rank_id).await;
+    let jane = || Principal {
+        subject: employee_id.clone(),
+        is_admin: false,
+    };
+    let day = |day: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+
+    let own: Response = harness
+        .request_absence(jane(), &employee_id, day(6), day(8))
+        .await;
+    let colleagues: Response = harness
+        .request_absence(jane(), &colleague_id, day(6), day(8))
+        .await;
+    let absence_id: String = created_id(&own, "requestAbsence");
+    let approved: Response = harness
+        .decide_absence(jane(), "approveAbsence", &absence_id)
+        .await;
+    let cancelled: Response = harness
+        .decide_absence(jane(), "cancelAbsence", &absence_id)
+        .await;
+
+    assert_eq!(error_code(&colleagues), Some(&Value::from("FORBIDDEN")));
+    assert_eq!(error_code(&approved), Some(&Value::from("FORBIDDEN")));
+    assert!(cancelled.errors.is_empty(), "{:?}", cancelled.errors);
+}
+
+async fn records_status_transitions(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let store_id: String = harness.create_store("Mitte").await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+
+    for status in ["WORKING", "WORKING", "VACATION"] {
+        harness
+            .data(&format!(
+                r#"mutation {{ updateEmployee(input: {{id: "{}", status: {}}}) {{ id }} }}"#,
+                employee_id, status
+            ))
+            .await;
+    }
+
+    let data: Json = harness.data(&format!(r#"{{
+        getEmployee(input: {{id: "{}"}}) {{ statusHistory(from: "2000-01-01T00:00:00Z") {{ previousStatus status changedBy }} }}
+        timeInStatus(storeId: "{}", from: "2000-01-01T00:00:00Z", to: "2100-01-01T00:00:00Z") {{ status }}
+    }}"#, employee_id, store_id)).await;
+    assert_eq!(
+        data["getEmployee"]["statusHistory"],
+        json!([
+            {"previousStatus": null, "status": "NONE", "changedBy": "root"},
+            {"previousStatus": "NONE", "status": "WORKING", "changedBy": "root"},
+            {"previousStatus": "WORKING", "status": "VACATION", "changedBy": "root"},
+        ])
+    );
+    assert!(data["timeInStatus"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|entry| ["NONE", "WORKING", "VACATION"].contains(&entry["status"].as_str().unwrap())));
+
+    let ambiguous: Response = harness.execute(&format!(
+        r#"{{ timeInStatus(employeeId: "{}", storeId: "{}", from: "2000-01-01T00:00:00Z", to: "2100-01-01T00:00:00Z") {{ status }} }}"#,
+        employee_id, store_id)).await;
+    assert_eq!(
+        error_code(&ambiguous),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+}
+
+async fn audits_every_mutation(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    harness
+        .data(&format!(
+            r#"mutation {{ updateRank(input: {{id: "{}", name: "Manager"}}) {{ id }} }}"#,
+            rank_id
+        ))
+        .await;
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteRank(input: {{id: "{}"}}) {{ id }} }}"#,
+            rank_id
+        ))
+        .await;
+    harness
+        .data(&format!(
+            r#"mutation {{ restoreRank(input: {{id: "{}"}}) {{ id }} }}"#,
+            rank_id
+        ))
+        .await;
+
+    let data: Json = harness.data(&format!(r#"{{
+        auditLog(filter: {{entityType: "Rank", entityId: "{}"}}) {{ totalCount edges {{ node {{ actor operation diff }} }} }}
+    }}"#, rank_id)).await;
+    let nodes: Vec<&Json> = data["auditLog"]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| &edge["node"])
+        .collect();
+    assert_eq!(data["auditLog"]["totalCount"], json!(4));
+    assert_eq!(
+        nodes
+            .iter()
+            .map(|node| node["operation"].as_str().unwrap())
+            .collect::<Vec<_>>(),
+        ["createRank", "updateRank", "deleteRank", "restoreRank"]
+    );
+    assert!(nodes.iter().all(|node| node["actor"] == json!("root")));
+    assert_eq!(
+        nodes[1]["diff"],
+        json!({"name": {"before": "Staff", "after": "Manager"}, "version": {"before": 1, "after": 2}})
+    );
+    assert_eq!(
+        nodes[2]["diff"]["deleted_by"],
+        json!({"before": null, "after": "root"})
+    );
+    assert_eq!(
+        nodes[3]["diff"]["deleted_by"],
+        json!({"before": "root", "after": null})
+    );
+    assert!(nodes[3]["diff"].get("name").is_none());
+
+    let employee_id: String = harness
+        .create_employee("Jane", &[], &harness.create_rank().await)
+        .await;
+    let principal: Principal = Principal {
+        subject: employee_id,
+        is_admin: false,
+    };
+    let response: Response = harness
+        .execute_as(principal, "{ auditLog { totalCount } }")
+        .await;
+    assert_eq!(error_code(&response), Some(&Value::from("FORBIDDEN")));
+}
+
+/// An audit log that refuses every entry.
+struct UnavailableAudit;
+
+#[async_trait]
+impl AuditRepository for UnavailableAudit {
+    async fn append_audit_entry(&self, _: AuditEntry) -> Result<AuditEntry, RepositoryError> {
+        Err(RepositoryError::Backend(String::from(
+            "audit log unavailable",
+        )))
+    }
+
+    async fn get_audit_page(
+        &self,
+        _: &AuditFilter,
+        _: &PageRequest,
+    ) -> Result<Page<AuditEntry>, RepositoryError> {
+        Err(RepositoryError::Backend(String::from(
+            "audit log unavailable",
+        )))
+    }
+}
+
+async fn keeps_mutations_when_auditing_fails(backend: Backend) {
+    let mut repositories: Repositories = Harness::new(backend, DeletePolicies::default())
+        .await
+        .repositories;
+    repositories.audit = Arc::new(UnavailableAudit);
+    let harness: Harness = Harness::with_repositories(repositories);
+    let rank_id: String = harness.create_rank().await;
+
+    let renamed: Response = harness
+        .execute(&format!(
+            r#"mutation {{ updateRank(input: {{id: "{}", name: "Manager"}}) {{ name }} }}"#,
+            rank_id
+        ))
+        .await;
+
+    assert!(renamed.errors.is_empty(), "{:?}", renamed.errors);
+    assert_eq!(
+        harness
+            .data(&format!(
+                r#"{{ getRank(input: {{id: "{}"}}) {{ name }} }}"#,
+                rank_id
+            ))
+            .await,
+        json!({"getRank": {"name": "Manager"}})
+    );
+}
+
+/// A status history that refuses every transition.
+struct UnavailableStatusHistory;
+
+#[async_trait]
+impl StatusHistoryRepository for UnavailableStatusHistory {
+    async fn record_status_transition(
+        &self,
+        _: StatusTransition,
+    ) -> Result<StatusTransition, RepositoryError> {
+        Err(RepositoryError::Backend(String::from(
+            "status history unavailable",
+        )))
+    }
+
+    async fn get_status_history(
+        &self,
+        _: &[String],
+        _: TimeRange,
+    ) -> Result<Vec<StatusTransition>, RepositoryError> {
+        Err(RepositoryError::Backend(String::from(
+            "status history unavailable",
+        )))
+    }
+}
+
+async fn keeps_status_changes_when_history_fails(backend: Backend) {
+    let mut repositories: Repositories = Harness::new(backend, DeletePolicies::default())
+        .await
+        .repositories;
+    repositories.status_history = Arc::new(UnavailableStatusHistory);
+    let harness: Harness = Harness::with_repositories(repositories);
+    let employee_id: String = harness
+        .create_employee("Jane", &[], &harness.create_rank().await)
+        .await;
+    let mut changes = Box::pin(harness.bus.subscribe(|event| match event {
+        Event::EmployeeStatusChanged(change) => Some(change),
+        _ => None,
+    }));
+
+    let updated: Response = harness
+        .execute(&format!(
+            r#"mutation {{ updateEmployee(input: {{id: "{}", status: WORKING}}) {{ status }} }}"#,
+            employee_id
+        ))
+        .await;
+
+    assert!(updated.errors.is_empty(), "{:?}", updated.errors);
+    let change: EmployeeStatusChange = changes.next().await.unwrap();
+    assert_eq!(change.employee.status, Some(Status::Working));
+    assert_eq!(
+        harness
+            .data(&format!(
+                r#"{{ getEmployee(input: {{id: "{}"}}) {{ status }} }}"#,
+                employee_id
+            ))
+            .await,
+        json!({"getEmployee": {"status": "WORKING"}})
+    );
+}
+
+async fn soft_deletes_and_restores(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[], &harness.create_rank().await)
+        .await;
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteEmployee(input: {{id: "{}"}}) {{ id }} }}"#,
+            employee_id
+        ))
+        .await;
+
+    assert_eq!(
+        harness.data("{ getAllEmployees { id } }").await,
+        json!({"getAllEmployees": []})
+    );
+    let response: Response = harness
+        .execute(&format!(
+            r#"{{ getEmployee(input: {{id: "{}"}}) {{ id }} }}"#,
+            employee_id
+        ))
+        .await;
+    assert_eq!(error_code(&response), Some(&Value::from("NOT_FOUND")));
+
+    let data: Json = harness
+        .data("{ employees(filter: {includeDeleted: true}) { edges { node { id deletedBy } } } }")
+        .await;
+    assert_eq!(
+        data["employees"]["edges"],
+        json!([{"node": {"id": employee_id, "deletedBy": "root"}}])
+    );
+
+    let principal: Principal = Principal {
+        subject: employee_id.clone(),
+        is_admin: false,
+    };
+    let response: Response = harness
+        .execute_as(
+            principal,
+            "{ getAllEmployees(filter: {includeDeleted: true}) { id } }",
+        )
+        .await;
+    assert_eq!(error_code(&response), Some(&Value::from("FORBIDDEN")));
+
+    let mut events = Box::pin(harness.bus.subscribe(|event| match event {
+        Event::EmployeeCreated(employee) => Some(("created", employee)),
+        Event::EmployeeRestored(employee) => Some(("restored", employee)),
+        _ => None,
+    }));
+    let data: Json = harness
+        .data(&format!(
+            r#"mutation {{ restoreEmployee(input: {{id: "{}"}}) {{ id deletedAt }} }}"#,
+            employee_id
+        ))
+        .await;
+    assert_eq!(
+        data["restoreEmployee"],
+        json!({"id": employee_id, "deletedAt": null})
+    );
+    let (kind, employee) = events.next().await.unwrap();
+    assert_eq!(kind, "restored");
+    assert_eq!(employee.id.map(|id| id.to_hex()), Some(employee_id.clone()));
+    assert!(events.next().now_or_never().is_none());
+    assert_eq!(
+        harness.data("{ getAllEmployees { id } }").await,
+        json!({"getAllEmployees": [{"id": employee_id}]})
+    );
+}
+
+async fn restores_cascaded_dependents(backend: Backend) {
+    let harness: Harness = Harness::new(
+        backend,
+        DeletePolicies {
+            employee_store: OnDelete::Cascade,
+            employee_rank: OnDelete::Cascade,
+            store_location: OnDelete::Cascade,
+        },
+    )
+    .await;
+    let rank_id: String = harness.create_rank().await;
+    let location_id: String = harness
+        .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+        .await;
+    let store_id: String = harness
+        .create(&format!(
+            r#"createStore(input: {{name: "Mitte", locationId: "{}"}})"#,
+            location_id
+        ))
+        .await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+    let query: String = format!(
+        r#"{{ getEmployee(input: {{id: "{}"}}) {{ storeIds rankId stores {{ locationId }} }} }}"#,
+        employee_id
+    );
+
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteLocation(input: {{id: "{}"}}) {{ id }} deleteRank(input: {{id: "{}"}}) {{ id }} }}"#,
+            location_id, rank_id
+        ))
+        .await;
+    assert_eq!(
+        harness.data(&query).await["getEmployee"],
+        json!({"storeIds": [], "rankId": null, "stores": []})
+    );
+
+    harness
+        .data(&format!(
+            r#"mutation {{ restoreLocation(input: {{id: "{}"}}) {{ id }} restoreRank(input: {{id: "{}"}}) {{ id }} }}"#,
+            location_id, rank_id
+        ))
+        .await;
+    assert_eq!(
+        harness.data("{ getAllStores { id } }").await,
+        json!({"getAllStores": [{"id": store_id}]})
+    );
+    assert_eq!(
+        harness.data(&query).await["getEmployee"],
+        json!({"storeIds": [store_id], "rankId": rank_id, "stores": [{"locationId": location_id}]})
+    );
+}
+
+async fn frees_names_of_deleted_entries(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let store_id: String = harness.create_store("Mitte").await;
+    harness
+        .data(&format!(
+            r#"mutation {{ deleteStore(input: {{id: "{}"}}) {{ id }} }}"#,
+            store_id
+        ))
+        .await;
+
+    let recreated_id: String = harness.create_store("Mitte").await;
+    assert_ne!(recreated_id, store_id);
+
+    let response: Response = harness
+        .execute(&format!(
+            r#"mutation {{ restoreStore(input: {{id: "{}"}}) {{ id }} }}"#,
+            store_id
+        ))
+        .await;
+    assert_eq!(error_code(&response), Some(&Value::from("DUPLICATE")));
+
+    harness
+        .data(&format!(
+            r#"mutation {{ updateStore(input: {{id: "{}", name: "Mitte Nord"}}) {{ id }} }}"#,
+            recreated_id
+        ))
+        .await;
+    let data: Json = harness
+        .data(&format!(
+            r#"mutation {{ restoreStore(input: {{id: "{}"}}) {{ name }} }}"#,
+            store_id
+        ))
+        .await;
+    assert_eq!(data["restoreStore"], json!({"name": "Mitte"}));
+}
+
+async fn restores_shifts_and_absences_with_their_employee(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let store_id: String = harness.create_store("Mitte").await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+    let shift_id: String = created_id(
+        &harness
+            .create_shift(
+                &employee_id,
+                &store_id,
+                "2024-05-06T08:00:00Z",
+                "2024-05-06T16:00:00Z",
+            )
+            .await,
+        "createShift",
+    );
+    let absence_id: String = created_id(
+        &harness
+            .request_absence(
+                root(),
+                &employee_id,
+                NaiveDate::from_ymd_opt(2024, 5, 13).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 14).unwrap(),
+            )
+            .await,
+        "requestAbsence",
+    );
+    let plans_query: String = format!(
+        r#"{{
+            shifts(storeId: "{}", from: "2024-05-01T00:00:00Z", to: "2024-06-01T00:00:00Z") {{ id }}
+            absences(filter: {{employeeId: "{}"}}) {{ id }}
+        }}"#,
+        store_id, employee_id
+    );
+    let delete_employee: String = format!(
+        r#"mutation {{ deleteEmployee(input: {{id: "{}"}}) {{ id }} }}"#,
+        employee_id
+    );
+
+    harness.data(&delete_employee).await;
+    assert_eq!(
+        harness.data(&plans_query).await,
+        json!({"shifts": [], "absences": []})
+    );
+    let hidden: Response = harness
+        .execute(&format!(
+            r#"{{ getShift(input: {{id: "{}"}}) {{ id }} }}"#,
+            shift_id
+        ))
+        .await;
+    assert_eq!(error_code(&hidden), Some(&Value::from("NOT_FOUND")));
+
+    harness
+        .data(&format!(
+            r#"mutation {{ restoreEmployee(input: {{id: "{}"}}) {{ id }} }}"#,
+            employee_id
+        ))
+        .await;
+    assert_eq!(
+        harness.data(&plans_query).await,
+        json!({"shifts": [{"id": shift_id}], "absences": [{"id": absence_id}]})
+    );
+
+    harness.data(&delete_employee).await;
+    let later: DateTime = DateTime::from_millis(DateTime::now().timestamp_millis() + 1000);
+    let report: PurgeReport = harness
+        .repositories
+        .purge
+        .purge_deleted(later)
+        .await
+        .unwrap();
+    assert_eq!(report.employees, 1);
+    assert_eq!(
+        harness.data(&plans_query).await,
+        json!({"shifts": [], "absences": []})
+    );
+}
+
+async fn purges_expired_deletions(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let store_id: String = harness.create_store("Mitte").await;
+    let employee_id: String = harness
+        .create_employee("Jane", &[&store_id], &rank_id)
+        .await;
+    for (mutation, id) in [
+        ("deleteEmployee", &employee_id),
+        ("deleteStore", &store_id),
+        ("deleteRank", &rank_id),
+    ] {
+        harness
+            .data(&format!(
+                r#"mutation {{ {}(input: {{id: "{}"}}) {{ id }} }}"#,
+                mutation, id
+            ))
+            .await;
+    }
+
+    let response: Response = harness
+        .execute(&format!(
+            r#"mutation {{ restoreEmployee(input: {{id: "{}"}}) {{ id }} }}"#,
+            employee_id
+        ))
+        .await;
+    assert_eq!(
+        error_code(&response),
+        Some(&Value::from("VALIDATION_FAILED"))
+    );
+
+    let report: PurgeReport = harness
+        .repositories
+        .purge
+        .purge_deleted(Retention::default().cutoff(DateTime::now()))
+        .await
+        .unwrap();
+    assert_eq!(report, PurgeReport::default());
+
+    let later: DateTime = DateTime::from_millis(DateTime::now().timestamp_millis() + 1000);
+    let report: PurgeReport = harness
+        .repositories
+        .purge
+        .purge_deleted(later)
+        .await
+        .unwrap();
+    assert_eq!(
+        report,
+        PurgeReport {
+            employees: 1,
+            stores: 1,
+            locations: 0,
+            ranks: 1
+        }
+    );
+    assert_eq!(
+        harness
+            .data("{ getAllEmployees(filter: {includeDeleted: true}) { id } }")
+            .await,
+        json!({"getAllEmployees": []})
+    );
+}
+
+async fn updates_with_expected_version(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let rank_id: String = harness.create_rank().await;
+    let update = |name: &str, version: &str| {
+        format!(
+            r#"mutation {{ updateRank(input: {{id: "{}", name: "{}"{}}}) {{ name version }} }}"#,
+            rank_id, name, version
+        )
+    };
+
+    let data: Json = harness.data(&update("Manager", ", version: 1")).await;
+    assert_eq!(data["updateRank"], json!({"name": "Manager", "version": 2}));
+
+    let response: Response = harness.execute(&update("Owner", ", version: 1")).await;
+    assert_eq!(error_code(&response), Some(&Value::from("CONFLICT")));
+    assert_eq!(
+        response.errors[0]
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("version")),
+        Some(&Value::from(2))
+    );
+
+    let data: Json = harness.data(&update("Owner", "")).await;
+    assert_eq!(data["updateRank"], json!({"name": "Owner", "version": 3}));
+
+    let store_id: String = harness.create_store("Mitte").await;
+    let response: Response = harness.execute(&format!(r#"mutation {{ updateStore(input: {{id: "{}", name: "Pankow", version: 4}}) {{ id }} }}"#, store_id)).await;
+    assert_eq!(error_code(&response), Some(&Value::from("CONFLICT")));
+
+    let employee_id: String = harness.create_employee("Jane", &[], &rank_id).await;
+    let today: NaiveDate = Utc::now().date_naive();
+    let absence_id: String = created_id(
+        &harness
+            .request_absence(root(), &employee_id, today, today)
+            .await,
+        "requestAbsence",
+    );
+    let decide = |mutation: &str, version: i32| {
+        format!(
+            r#"mutation {{ {}(input: {{id: "{}", version: {}}}) {{ state version }} }}"#,
+            mutation, absence_id, version
+        )
+    };
+    let data: Json = harness.data(&decide("approveAbsence", 1)).await;
+    assert_eq!(
+        data["approveAbsence"],
+        json!({"state": "APPROVED", "version": 2})
+    );
+    let response: Response = harness.execute(&decide("cancelAbsence", 1)).await;
+    assert_eq!(error_code(&response), Some(&Value::from("CONFLICT")));
+}
+
+async fn resolves_reverse_edges_for_every_parent(backend: Backend) {
+    let harness: Harness = Harness::new(backend, DeletePolicies::default()).await;
+    let location_id: String = harness
+        .create(r#"createLocation(input: {country: "DE", state: "Berlin"})"#)
+        .await;
+    let mut store_ids: Vec<String> = vec![];
+    for name in ["Mitte", "Pankow"] {
+        store_ids.push(
+            harness
+                .create(&format!(
+                    r#"createStore(input: {{name: "{}", locationId: "{}"}})"#,
+                    name, location_id
+                ))
+                .await,
+        );
+    }
+    let staff_id: String = harness.create_rank().await;
+    let manager_id: String = harness
+        .create(r#"createRank(input: {name: "Manager"})"#)
+        .await;
+    let jane_id: String = harness
+        .create_employee("Jane", &[&store_ids[0], &store_ids[1]], &staff_id)
+        .await;
+    let john_id: String = harness
+        .create_employee("John", &[&store_ids[0]], &manager_id)
+        .await;
+    for (employee_id, day) in [(&jane_id, "06"), (&john_id, "07")] {
+        let starts_at: String = format!("2024-05-{}T08:00:00Z", day);
+        let ends_at: String = format!("2024-05-{}T16:00:00Z", day);
+        let response: Response = harness
+            .create_shift(employee_id, &store_ids[0], &starts_at, &ends_at)
+            .await;
+        created_id(&response, "createShift");
+    }
+    harness
+        .data(&format!(
+            r#"mutation {{ updateEmployee(input: {{id: "{}", status: WORKING}}) {{ id }} }}"#,
+            jane_id
+        ))
+        .await;
+
+    let data: Json = harness
+        .data(
+            r#"{
+        getAllLocations { stores { name employees { firstName } } }
+        getAllRanks { name employees { firstName } }
+        getAllEmployees {
+            firstName
+            shifts(from: "2024-05-01T00:00:00Z") { startsAt }
+            statusHistory(from: "2000-01-01T00:00:00Z") { status }
+            absences { id }
+        }
+    }"#,
+        )
+        .await;
+
+    assert_eq!(
+        data["getAllLocations"],
+        json!([{"stores": [
+            {"name": "Mitte", "employees": [{"firstName": "Jane"}, {"firstName": "John"}]},
+            {"name": "Pankow", "employees": [{"firstName": "Jane"}]},
+        ]}])
+    );
+    assert_eq!(
+        data["getAllRanks"],
+        json!([
+            {"name": "Staff", "employees": [{"firstName": "Jane"}]},
+            {"name": "Manager", "employees": [{"firstName": "John"}]},
+        ])
+    );
+    assert_eq!(
+        data["getAllEmployees"],
+        json!([
+            {
+                "firstName": "Jane",
+                "shifts": [{"startsAt": "2024-05-06T08:00:00+00:00"}],
+                "statusHistory": [{"status": "NONE"}, {"status": "WORKING"}],
+                "absences": [],
+            },
+            {
+                "firstName": "John",
+                "shifts": [{"startsAt": "2024-05-07T08:00:00+00:00"}],
+                "statusHistory": [{"status": "NONE"}],
+                "absences": [],
+            },
+        ])
+    );
+}
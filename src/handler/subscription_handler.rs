@@ -0,0 +1,88 @@
+use crate::{
+    config::event_bus::{Event, EventBus},
+    schema::project_schema::{
+        Employee, EmployeeStatusChange, LocationChange, RankChange, StoreChange,
+    },
+};
+use async_graphql::{futures_util::Stream, Context, Subscription};
+
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// Fires whenever an employee's status changes, optionally only for the
+    /// employees assigned to the given store. Approving or cancelling an
+    /// absence that covers today fires too; an absence beginning or ending
+    /// with the calendar does not.
+    async fn employee_status_changed(
+        &self,
+        context: &Context<'_>,
+        store_id: Option<String>,
+    ) -> impl Stream<Item = EmployeeStatusChange> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(move |event| match event {
+            Event::EmployeeStatusChanged(change)
+                if store_id.as_ref().is_none_or(|store_id| {
+                    change
+                        .employee
+                        .stores
+                        .as_ref()
+                        .is_some_and(|stores| stores.contains(store_id))
+                }) =>
+            {
+                Some(change)
+            }
+            _ => None,
+        })
+    }
+
+    async fn employee_created(&self, context: &Context<'_>) -> impl Stream<Item = Employee> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(|event| match event {
+            Event::EmployeeCreated(employee) => Some(employee),
+            _ => None,
+        })
+    }
+
+    async fn employee_deleted(&self, context: &Context<'_>) -> impl Stream<Item = Employee> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(|event| match event {
+            Event::EmployeeDeleted(employee) => Some(employee),
+            _ => None,
+        })
+    }
+
+    /// Fires when a deleted employee is restored, with the employee as it is
+    /// afterwards.
+    async fn employee_restored(&self, context: &Context<'_>) -> impl Stream<Item = Employee> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(|event| match event {
+            Event::EmployeeRestored(employee) => Some(employee),
+            _ => None,
+        })
+    }
+
+    async fn store_changed(&self, context: &Context<'_>) -> impl Stream<Item = StoreChange> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(|event| match event {
+            Event::StoreChanged(change) => Some(change),
+            _ => None,
+        })
+    }
+
+    async fn location_changed(&self, context: &Context<'_>) -> impl Stream<Item = LocationChange> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(|event| match event {
+            Event::LocationChanged(change) => Some(change),
+            _ => None,
+        })
+    }
+
+    async fn rank_changed(&self, context: &Context<'_>) -> impl Stream<Item = RankChange> {
+        let bus: &EventBus = context.data_unchecked::<EventBus>();
+        bus.subscribe(|event| match event {
+            Event::RankChanged(change) => Some(change),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,346 @@
+use crate::{
+    config::{auth::JwtVerifier, repository::Repositories},
+    handler::{
+        data_loader::connection_loaders,
+        graphql_handler::ProjectSchema,
+        request_guard::{authenticate, ClientAddress},
+    },
+};
+use async_graphql::{
+    http::{WebSocket, WebSocketProtocols, WsMessage},
+    Data, Error, Result,
+};
+use futures_util::{future, SinkExt, StreamExt};
+use rocket::{
+    fairing::AdHoc,
+    figment::{
+        providers::{Env, Serialized},
+        Figment,
+    },
+    serde::json::Value,
+    tokio::{
+        self,
+        net::{TcpListener, TcpStream},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, StatusCode},
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+};
+
+pub const DEFAULT_WEBSOCKET_PORT: u16 = 8001;
+
+/// Where the GraphQL WebSocket endpoint listens. Rocket cannot upgrade its own
+/// connections, so subscriptions are served from a second port on Rocket's
+/// address. Later sources override earlier ones:
+///
+/// 1. the defaults below,
+/// 2. the `websocket` table of the active profile in `Rocket.toml`,
+/// 3. `GRAPHQL_WS_*` environment variables (`GRAPHQL_WS_PORT`,
+///    `GRAPHQL_WS_URL`).
+///
+/// `url` is the endpoint advertised to clients, for deployments behind a
+/// proxy or TLS terminator; it defaults to `ws://<host>:<port>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketConfig {
+    pub port: u16,
+    pub url: Option<String>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        WebSocketConfig {
+            port: DEFAULT_WEBSOCKET_PORT,
+            url: None,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    pub fn figment(rocket: &Figment) -> Figment {
+        Figment::from(Serialized::defaults(WebSocketConfig::default()))
+            .merge(rocket.focus("websocket"))
+            .merge(Env::prefixed("GRAPHQL_WS_"))
+    }
+
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        let config: WebSocketConfig = figment.extract().map_err(|error| error.to_string())?;
+        if let Some(url) = &config.url {
+            if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                return Err(format!(
+                    "the endpoint URL '{}' must start with 'ws://' or 'wss://'",
+                    url
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The URL clients connect to, as seen from a request sent to `host`.
+    pub fn endpoint(&self, host: &str) -> String {
+        match &self.url {
+            Some(url) => url.clone(),
+            None => format!("ws://{}:{}", host, self.port),
+        }
+    }
+}
+
+/// Binds the WebSocket listener once Rocket ignites, serving the managed
+/// schema, repositories and token verifier.
+pub fn websocket_fairing() -> AdHoc {
+    AdHoc::on_ignite("GraphQL WebSocket", |rocket| async move {
+        let address: IpAddr = rocket
+            .figment()
+            .extract_inner("address")
+            .unwrap_or_else(|error| panic!("Invalid Rocket address: {}", error));
+        let config: WebSocketConfig =
+            WebSocketConfig::from_figment(&WebSocketConfig::figment(rocket.figment()))
+                .unwrap_or_else(|error| panic!("Invalid WebSocket configuration: {}", error));
+        let address: SocketAddr = SocketAddr::new(address, config.port);
+        let schema: ProjectSchema = rocket
+            .state::<ProjectSchema>()
+            .expect("schema is managed")
+            .clone();
+        let db: Repositories = rocket
+            .state::<Repositories>()
+            .expect("repositories are managed")
+            .clone();
+        let verifier: JwtVerifier = rocket
+            .state::<JwtVerifier>()
+            .expect("token verifier is managed")
+            .clone();
+
+        let listener: TcpListener = TcpListener::bind(address).await.unwrap_or_else(|error| {
+            panic!("Cannot listen for WebSockets on {}: {}", address, error)
+        });
+        tokio::spawn(serve(listener, schema, db, verifier));
+
+        rocket.manage(config)
+    })
+}
+
+async fn serve(
+    listener: TcpListener,
+    schema: ProjectSchema,
+    db: Repositories,
+    verifier: JwtVerifier,
+) {
+    loop {
+        if let Ok((stream, peer)) = listener.accept().await {
+            tokio::spawn(handle_connection(
+                stream,
+                peer,
+                schema.clone(),
+                db.clone(),
+                verifier.clone(),
+            ));
+        }
+    }
+}
+
+/// The first sub-protocol offered by the client that async-graphql speaks,
+/// preferring whichever the client listed first.
+fn negotiate_protocol(request: &Request) -> Option<WebSocketProtocols> {
+    request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|protocol| protocol.trim().parse().ok())
+}
+
+// The handshake callback's error type is fixed by tungstenite.
+#[allow(clippy::result_large_err)]
+async fn handle_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    schema: ProjectSchema,
+    db: Repositories,
+    verifier: JwtVerifier,
+) {
+    let mut protocol: Option<WebSocketProtocols> = None;
+    let handshake = accept_hdr_async(
+        stream,
+        |request: &Request, mut response: Response| -> Result<Response, ErrorResponse> {
+            protocol = negotiate_protocol(request);
+            match protocol {
+                Some(protocol) => {
+                    response.headers_mut().insert(
+                        SEC_WEBSOCKET_PROTOCOL,
+                        HeaderValue::from_static(protocol.sec_websocket_protocol()),
+                    );
+                    Ok(response)
+                }
+                None => {
+                    let mut rejection: ErrorResponse = ErrorResponse::new(Some(String::from(
+                        "Unsupported WebSocket sub-protocol",
+                    )));
+                    *rejection.status_mut() = StatusCode::BAD_REQUEST;
+                    Err(rejection)
+                }
+            }
+        },
+    );
+
+    let (mut sink, source) = match handshake.await {
+        Ok(websocket) => websocket.split(),
+        Err(_) => return,
+    };
+    let protocol: WebSocketProtocols = match protocol {
+        Some(protocol) => protocol,
+        None => return,
+    };
+
+    let input = source
+        .take_while(|message| future::ready(message.is_ok()))
+        .filter_map(|message| {
+            future::ready(match message {
+                Ok(Message::Text(text)) => Some(text.into_bytes()),
+                Ok(Message::Binary(bytes)) => Some(bytes),
+                _ => None,
+            })
+        });
+
+    let mut output = WebSocket::new(schema, input, protocol).on_connection_init(move |payload| {
+        future::ready(connection_data(payload, peer, &db, &verifier))
+    });
+
+    while let Some(message) = output.next().await {
+        let message: Message = match message {
+            WsMessage::Text(text) => Message::Text(text),
+            WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                code: CloseCode::from(code),
+                reason: reason.into(),
+            })),
+        };
+
+        if sink.send(message).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Data shared by every operation of a connection. Clients authenticate by
+/// sending `{"Authorization": "Bearer <token>"}` as the `connection_init`
+/// payload; without it the connection stays anonymous, like an HTTP request
+/// without the header.
+fn connection_data(
+    payload: Value,
+    peer: SocketAddr,
+    db: &Repositories,
+    verifier: &JwtVerifier,
+) -> Result<Data> {
+    let mut data: Data = connection_loaders(db);
+    data.insert(ClientAddress(Some(peer.ip())));
+
+    let header: Option<&str> = payload
+        .as_object()
+        .and_then(|fields| {
+            fields
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case("Authorization"))
+        })
+        .and_then(|(_, value)| value.as_str());
+
+    if let Some(header) = header {
+        data.insert(authenticate(header, verifier).map_err(Error::new)?);
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        auth::{mint_token, Principal},
+        integrity::DeletePolicies,
+        memory::InMemory,
+    };
+    use rocket::{
+        figment::providers::{Format, Toml},
+        serde::json::json,
+    };
+
+    const SECRET: &[u8] = b"test-secret";
+
+    fn config(toml: &str) -> Result<WebSocketConfig, String> {
+        let rocket: Figment = Figment::from(Toml::string(toml));
+
+        WebSocketConfig::from_figment(&WebSocketConfig::figment(&rocket))
+    }
+
+    fn connect(payload: Value) -> Result<Data> {
+        let db: Repositories = Repositories::new(InMemory::new(DeletePolicies::default()));
+        let peer: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 40000));
+
+        connection_data(payload, peer, &db, &JwtVerifier::hs256(SECRET))
+    }
+
+    #[test]
+    fn reads_port_and_url_from_rocket_config() {
+        let config: WebSocketConfig = config(
+            r#"
+            [websocket]
+            port = 9001
+            url = "wss://example.com/graphql"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 9001);
+        assert_eq!(config.endpoint("localhost"), "wss://example.com/graphql");
+    }
+
+    #[test]
+    fn defaults_endpoint_to_request_host() {
+        let config: WebSocketConfig = config("").unwrap();
+
+        assert_eq!(config.port, DEFAULT_WEBSOCKET_PORT);
+        assert_eq!(config.endpoint("localhost"), "ws://localhost:8001");
+    }
+
+    #[test]
+    fn rejects_non_websocket_url() {
+        assert!(config("[websocket]\nurl = \"http://example.com\"").is_err());
+    }
+
+    #[test]
+    fn authenticates_connection_init_token() {
+        let token: String = mint_token(SECRET, "alice", 300);
+        let data: Data = connect(json!({"Authorization": format!("Bearer {}", token)})).unwrap();
+
+        assert_eq!(
+            data.get(&std::any::TypeId::of::<Principal>())
+                .and_then(|principal| principal.downcast_ref::<Principal>())
+                .map(|principal| principal.subject.as_str()),
+            Some("alice")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_connection_init_token() {
+        assert!(connect(json!({"Authorization": "Bearer not.a.token"})).is_err());
+        assert!(connect(json!({"Authorization": mint_token(SECRET, "alice", 300)})).is_err());
+        assert!(connect(
+            json!({"authorization": format!("Bearer {}", mint_token(b"other", "alice", 300))})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn keeps_connection_without_token_anonymous() {
+        let data: Data = connect(json!({})).unwrap();
+
+        assert!(data.get(&std::any::TypeId::of::<Principal>()).is_none());
+    }
+}
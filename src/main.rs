@@ -2,32 +2,215 @@ mod config;
 mod handler;
 mod schema;
 
-use async_graphql::{http::{playground_source, GraphQLPlaygroundConfig}, EmptySubscription, Schema};
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    Schema,
+};
 use async_graphql_rocket::{GraphQLQuery, GraphQLRequest, GraphQLResponse};
-use config::mongo::MongoDB;
-use handler::graphql_handler::{Mutation, ProjectSchema, Query};
-use rocket::{response::content, routes, State};
+use config::{
+    auth::JwtVerifier,
+    change_stream::{self, ChangeStreamBridge},
+    event_bus::EventBus,
+    integrity::DeletePolicies,
+    memory::InMemory,
+    mongo::MongoDB,
+    mongo_config::MongoConfig,
+    postgres::PostgresDB,
+    repository::{Repositories, StorageBackend},
+    retention::Retention,
+    validation::ValidationMode,
+};
+use handler::{
+    data_loader::with_loaders,
+    graphql_handler::{Mutation, ProjectSchema, Query},
+    request_guard::{Authentication, ClientAddress},
+    subscription_handler::Subscription,
+    websocket_handler::{websocket_fairing, WebSocketConfig},
+};
+use mongodb::bson::DateTime;
+use rocket::{http::uri::Host, response::content, routes, Build, Rocket, State};
+use std::{env, process};
+
+fn prepare_request(
+    request: GraphQLRequest,
+    db: &Repositories,
+    mode: ValidationMode,
+    auth: Authentication,
+    address: ClientAddress,
+) -> GraphQLRequest {
+    let request: GraphQLRequest = with_loaders(request, db).data(mode).data(address);
+    match auth.0 {
+        Some(principal) => request.data(principal),
+        None => request,
+    }
+}
 
 #[rocket::get("/graphql?<query..>")]
-async fn graphql_query(schema: &State<ProjectSchema>, query: GraphQLQuery) -> GraphQLResponse {
-    query.execute(schema).await
+async fn graphql_query(
+    schema: &State<ProjectSchema>,
+    db: &State<Repositories>,
+    mode: ValidationMode,
+    auth: Authentication,
+    address: ClientAddress,
+    query: GraphQLQuery,
+) -> GraphQLResponse {
+    prepare_request(GraphQLRequest::from(query), db, mode, auth, address)
+        .execute(schema)
+        .await
 }
 
-#[rocket::post("/graphql", data="<request>", format="application/json")]
-async fn graphql_mutation(schema: &State<ProjectSchema>, request: GraphQLRequest) -> GraphQLResponse {
-    request.execute(schema).await
+#[rocket::post("/graphql", data = "<request>", format = "application/json")]
+async fn graphql_mutation(
+    schema: &State<ProjectSchema>,
+    db: &State<Repositories>,
+    mode: ValidationMode,
+    auth: Authentication,
+    address: ClientAddress,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    prepare_request(request, db, mode, auth, address)
+        .execute(schema)
+        .await
 }
 
 #[rocket::get("/")]
-async fn graphql_playground() -> content::RawHtml<String> {
-    content::RawHtml(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+async fn graphql_playground(
+    host: &Host<'_>,
+    websocket: &State<WebSocketConfig>,
+) -> content::RawHtml<String> {
+    let subscription_endpoint: String = websocket.endpoint(host.domain().as_str());
+    content::RawHtml(playground_source(
+        GraphQLPlaygroundConfig::new("/graphql").subscription_endpoint(&subscription_endpoint),
+    ))
+}
+
+#[rocket::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    let command: Option<String> = env::args().nth(1);
+    match command.as_deref() {
+        None => {
+            if let Err(error) = rocket().await.launch().await {
+                eprintln!("Launch failed: {}", error);
+                process::exit(1);
+            }
+        }
+        Some("migrate") => migrate().await,
+        Some("purge") => purge().await,
+        Some(command) => {
+            eprintln!("Unknown command '{}'. Run without arguments to start the server, with 'migrate' to apply pending migrations, or with 'purge' to remove expired deletions.", command);
+            process::exit(2);
+        }
+    }
+}
+
+/// Applies pending MongoDB migrations and exits, for deployments that run
+/// them as a separate step with `migrate_on_start` turned off.
+async fn migrate() {
+    let config = MongoConfig::load()
+        .unwrap_or_else(|error| panic!("Invalid MongoDB configuration: {}", error));
+    let policies = DeletePolicies::from_env()
+        .unwrap_or_else(|error| panic!("Invalid on-delete policy configuration: {}", error));
+    let db = MongoDB::connect(&config, policies)
+        .await
+        .unwrap_or_else(|error| panic!("Cannot migrate without MongoDB: {}", error));
+
+    match db.migrate().await {
+        Ok(applied) if applied.is_empty() => println!("No pending migrations"),
+        Ok(applied) => {
+            for migration in applied {
+                println!(
+                    "Applied migration {}: {}",
+                    migration.version, migration.description
+                );
+            }
+        }
+        Err(error) => {
+            eprintln!("Migration failed: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Removes the entries that were deleted longer ago than
+/// `DELETED_RETENTION_DAYS` from the configured backend and exits.
+async fn purge() {
+    let retention = Retention::from_env()
+        .unwrap_or_else(|error| panic!("Invalid retention configuration: {}", error));
+    let backend = StorageBackend::from_env()
+        .unwrap_or_else(|error| panic!("Invalid storage configuration: {}", error));
+    let cutoff: DateTime = retention.cutoff(DateTime::now());
+
+    let repositories: Repositories = match backend {
+        StorageBackend::Mongo => Repositories::new(MongoDB::init().await),
+        StorageBackend::Postgres => Repositories::new(PostgresDB::init().await),
+        StorageBackend::Memory => {
+            println!("The memory backend keeps nothing between runs, there is nothing to purge");
+            return;
+        }
+    };
+
+    match repositories.purge.purge_deleted(cutoff).await {
+        Ok(report) => println!(
+            "Purged entries deleted before {}: {} employees, {} stores, {} locations, {} ranks",
+            cutoff, report.employees, report.stores, report.locations, report.ranks
+        ),
+        Err(error) => {
+            eprintln!("Purge failed: {}", error);
+            process::exit(1);
+        }
+    }
 }
 
-#[rocket::launch]
-fn rocket() -> _ {
-    let db = MongoDB::init();
-    let schema =  Schema::build(Query, Mutation, EmptySubscription)
-        .data(db)
+async fn rocket() -> Rocket<Build> {
+    let verifier = JwtVerifier::from_env()
+        .unwrap_or_else(|error| panic!("Invalid JWT configuration: {}", error));
+    let backend = StorageBackend::from_env()
+        .unwrap_or_else(|error| panic!("Invalid storage configuration: {}", error));
+    let (repositories, bus) = match backend {
+        StorageBackend::Mongo => {
+            let db = MongoDB::init().await;
+            let bus = if change_stream::enabled_from_env()
+                .unwrap_or_else(|error| panic!("Invalid change stream configuration: {}", error))
+            {
+                let bus = EventBus::default().fed_by_change_streams();
+                ChangeStreamBridge::new(
+                    db.clone(),
+                    bus.clone(),
+                    change_stream::instance_from_env(),
+                )
+                .spawn();
+                bus
+            } else {
+                EventBus::default()
+            };
+            (Repositories::new(db), bus)
+        }
+        StorageBackend::Postgres => (
+            Repositories::new(PostgresDB::init().await),
+            EventBus::default(),
+        ),
+        StorageBackend::Memory => {
+            let policies = DeletePolicies::from_env().unwrap_or_else(|error| {
+                panic!("Invalid on-delete policy configuration: {}", error)
+            });
+            (
+                Repositories::new(InMemory::new(policies)),
+                EventBus::default(),
+            )
+        }
+    };
+    let schema = Schema::build(Query, Mutation, Subscription)
+        .data(repositories.clone())
+        .data(bus)
         .finish();
-    rocket::build().manage(schema).mount("/", routes![graphql_query, graphql_mutation, graphql_playground])
-}
\ No newline at end of file
+    rocket::build()
+        .manage(schema)
+        .manage(repositories)
+        .manage(verifier)
+        .attach(websocket_fairing())
+        .mount(
+            "/",
+            routes![graphql_query, graphql_mutation, graphql_playground],
+        )
+}
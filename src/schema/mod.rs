@@ -1 +1 @@
-pub mod project_schema;
\ No newline at end of file
+pub mod project_schema;
@@ -1,19 +1,48 @@
+use crate::handler::graphql_guard::PermissionGuard;
+use async_graphql::{Enum, InputObject, MaybeUndefined, SimpleObject};
+use chrono::NaiveDate;
+use mongodb::bson::{oid::ObjectId, Bson, DateTime, Document};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
-use async_graphql::{Enum, InputObject, SimpleObject};
-use mongodb::bson::{
-    oid::ObjectId
-};
-use serde::{Deserialize, Serialize};
+
+/// The version every entry starts at. Entries stored before versions were
+/// introduced count as being at it too.
+pub const FIRST_VERSION: i32 = 1;
+
+fn first_version() -> i32 {
+    FIRST_VERSION
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct Employee {
-    #[serde(rename="_id", skip_serializing_if="Option::is_none")]
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub first_name: String,
     pub last_name: String,
+    /// The status as stored. Clients see it through the `status` resolver,
+    /// which lets an approved absence covering today take precedence.
+    #[graphql(skip)]
     pub status: Option<Status>,
+    /// The references as stored. They keep pointing at deleted stores and
+    /// ranks until those are purged; the `storeIds` and `rankId` resolvers
+    /// leave them out.
+    #[graphql(skip)]
     pub stores: Option<Vec<String>>,
+    #[graphql(skip)]
     pub rank_id: Option<String>,
+    /// Counts the updates made to the entry, starting at 1. An update that
+    /// names the version it was based on fails with `CONFLICT` once the
+    /// entry has moved past it.
+    #[serde(default = "first_version")]
+    pub version: i32,
+    /// When the entry was deleted. Deleted entries are kept until they are
+    /// purged, but left out of every query unless an administrator asks for
+    /// them with `includeDeleted`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 #[derive(InputObject)]
@@ -22,7 +51,7 @@ pub struct CreateEmployee {
     pub last_name: String,
     pub status: Option<Status>,
     pub stores: Option<Vec<String>>,
-    pub rank_id: String
+    pub rank_id: String,
 }
 
 #[derive(InputObject)]
@@ -35,63 +64,215 @@ pub struct DeleteEmployee {
     pub id: String,
 }
 
+#[derive(InputObject)]
+pub struct RestoreEmployee {
+    pub id: String,
+}
+
+/// Omitted fields are left untouched, fields explicitly set to `null` are
+/// cleared. `addStores`/`removeStores` edit the store assignments in place and
+/// cannot be combined with replacing `stores` as a whole.
 #[derive(InputObject)]
 pub struct UpdateEmployee {
     pub id: String,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
-    pub status: Option<Status>,
-    pub stores: Option<Vec<String>>,
-    pub rank_id: Option<String>
+    #[graphql(default)]
+    pub status: MaybeUndefined<Status>,
+    #[graphql(default)]
+    pub stores: MaybeUndefined<Vec<String>>,
+    pub add_stores: Option<Vec<String>>,
+    pub remove_stores: Option<Vec<String>>,
+    #[graphql(default)]
+    pub rank_id: MaybeUndefined<String>,
+    /// The version the update is based on. When given, the update only
+    /// applies while the entry is still at that version.
+    pub version: Option<i32>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+#[derive(InputObject, Default)]
+pub struct EmployeeFilter {
+    /// Matches the status employees show, so an approved absence covering
+    /// today (UTC) counts as its `VACATION` or `ILLNESS`.
+    pub status_in: Option<Vec<Status>>,
+    pub store_id: Option<String>,
+    pub rank_id: Option<String>,
+    pub name_prefix: Option<String>,
+    pub name_contains: Option<String>,
+    /// Also list entries marked deleted. Only administrators may ask for
+    /// them.
+    #[graphql(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum EmployeeOrderField {
+    FirstName,
+    LastName,
+    Status,
+}
+
+#[derive(InputObject)]
+pub struct EmployeeOrderBy {
+    pub field: EmployeeOrderField,
+    #[graphql(default)]
+    pub direction: OrderDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
 pub enum Status {
     None,
     Working,
     EmergencyService,
     Vacation,
     Illness,
+    /// The stored status is not one this version knows. It is only ever
+    /// read, never written.
+    Unknown,
+}
+
+impl Status {
+    pub const KNOWN: [Status; 5] = [
+        Status::None,
+        Status::Working,
+        Status::EmergencyService,
+        Status::Vacation,
+        Status::Illness,
+    ];
+
+    /// The value stored for this status. Every backend and every write path
+    /// goes through here, so a status is always stored the same way.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::None => "None",
+            Status::Working => "Working",
+            Status::EmergencyService => "EmergencyService",
+            Status::Vacation => "Vacation",
+            Status::Illness => "Illness",
+            Status::Unknown => "Unknown",
+        }
+    }
+
+    /// Reads a stored status. Case, spaces, dashes and underscores are
+    /// ignored, so legacy spellings like `EMERGENCY_SERVICE` still match;
+    /// anything else is `Unknown` rather than an error.
+    pub fn decode(value: &str) -> Status {
+        let normalized: String = value
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        Status::KNOWN
+            .into_iter()
+            .find(|status| status.as_str().to_lowercase() == normalized)
+            .unwrap_or(Status::Unknown)
+    }
 }
 
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Status::None => write!(f, "None"),
-            Status::Working => write!(f, "Working"),
-            Status::EmergencyService => write!(f, "EmergencyService"),
-            Status::Vacation => write!(f, "Vacation"),
-            Status::Illness => write!(f, "Illness"),
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Status {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Never fails, so a single odd document cannot break a whole listing.
+/// Values that are not strings decode as `Unknown` too.
+impl<'de> Deserialize<'de> for Status {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Bson::deserialize(deserializer)? {
+            Bson::String(value) => Ok(Status::decode(&value)),
+            _ => Ok(Status::Unknown),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct Store {
-    #[serde(rename="_id", skip_serializing_if="Option::is_none")]
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub name: String,
-    pub location_id: String
+    /// Shown through the `locationId` resolver, like `Employee.rank_id`.
+    #[graphql(skip)]
+    pub location_id: Option<String>,
+    #[serde(default = "first_version")]
+    pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 #[derive(InputObject)]
 pub struct CreateStore {
     pub name: String,
-    pub location_id: String
+    pub location_id: String,
 }
 
-
 #[derive(InputObject)]
 pub struct FetchStore {
     pub id: String,
 }
 
+#[derive(InputObject)]
+pub struct UpdateStore {
+    pub id: String,
+    pub name: Option<String>,
+    pub location_id: Option<String>,
+    pub version: Option<i32>,
+}
+
+#[derive(InputObject)]
+pub struct DeleteStore {
+    pub id: String,
+}
+
+#[derive(InputObject)]
+pub struct RestoreStore {
+    pub id: String,
+}
+
+#[derive(InputObject, Default)]
+pub struct StoreFilter {
+    pub location_id: Option<String>,
+    pub name: Option<String>,
+    #[graphql(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum StoreOrderField {
+    Name,
+    LocationId,
+}
+
+#[derive(InputObject)]
+pub struct StoreOrderBy {
+    pub field: StoreOrderField,
+    #[graphql(default)]
+    pub direction: OrderDirection,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct Location {
-    #[serde(rename="_id", skip_serializing_if="Option::is_none")]
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub country: String,
     pub state: String,
+    #[serde(default = "first_version")]
+    pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
 }
 
 #[derive(InputObject)]
@@ -105,18 +286,88 @@ pub struct FetchLocation {
     pub id: String,
 }
 
+#[derive(InputObject)]
+pub struct UpdateLocation {
+    pub id: String,
+    pub country: Option<String>,
+    pub state: Option<String>,
+    pub version: Option<i32>,
+}
+
+#[derive(InputObject)]
+pub struct DeleteLocation {
+    pub id: String,
+}
+
+#[derive(InputObject)]
+pub struct RestoreLocation {
+    pub id: String,
+}
+
+#[derive(InputObject, Default)]
+pub struct LocationFilter {
+    pub country: Option<String>,
+    pub state: Option<String>,
+    #[graphql(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LocationOrderField {
+    Country,
+    State,
+}
+
+#[derive(InputObject)]
+pub struct LocationOrderBy {
+    pub field: LocationOrderField,
+    #[graphql(default)]
+    pub direction: OrderDirection,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct Rank {
-    #[serde(rename="_id", skip_serializing_if="Option::is_none")]
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub name: String,
     pub description: Option<String>,
+    #[serde(default)]
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageRanks)")]
+    pub permissions: Vec<Permission>,
+    #[serde(default = "first_version")]
+    pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+/// What the holders of a rank are allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum Permission {
+    /// Create employees and change any of their fields.
+    ManageEmployees,
+    /// Delete employees.
+    DeleteEmployees,
+    /// Change the own status, and nothing else, on the own employee entry.
+    UpdateOwnStatus,
+    ManageStores,
+    ManageLocations,
+    ManageRanks,
+    /// Plan, move and remove shifts.
+    ManageShifts,
+    /// Request and cancel absences for oneself.
+    RequestAbsences,
+    /// Request absences for any employee, and approve, reject or cancel them.
+    ManageAbsences,
 }
 
 #[derive(InputObject)]
 pub struct CreateRank {
     pub name: String,
     pub description: Option<String>,
+    pub permissions: Option<Vec<Permission>>,
 }
 
 #[derive(InputObject)]
@@ -124,7 +375,347 @@ pub struct FetchRank {
     pub id: String,
 }
 
+#[derive(InputObject)]
+pub struct UpdateRank {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub permissions: Option<Vec<Permission>>,
+    pub version: Option<i32>,
+}
+
+#[derive(InputObject)]
+pub struct DeleteRank {
+    pub id: String,
+}
+
+#[derive(InputObject)]
+pub struct RestoreRank {
+    pub id: String,
+}
+
+#[derive(InputObject, Default)]
+pub struct RankFilter {
+    pub name: Option<String>,
+    #[graphql(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum RankOrderField {
+    Name,
+}
+
+#[derive(InputObject)]
+pub struct RankOrderBy {
+    pub field: RankOrderField,
+    #[graphql(default)]
+    pub direction: OrderDirection,
+}
+
+/// A time an employee works at one of their stores. A shift runs from
+/// `startsAt` up to, but not including, `endsAt`.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct Shift {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub employee_id: String,
+    pub store_id: String,
+    pub starts_at: DateTime,
+    pub ends_at: DateTime,
+    /// The rank the employee has to hold to work the shift. Shown through the
+    /// `requiredRankId` resolver, like `Employee.rank_id`.
+    #[graphql(skip)]
+    pub required_rank_id: Option<String>,
+    #[serde(default = "first_version")]
+    pub version: i32,
+}
+
+#[derive(InputObject)]
+pub struct CreateShift {
+    pub employee_id: String,
+    pub store_id: String,
+    pub starts_at: DateTime,
+    pub ends_at: DateTime,
+    pub required_rank_id: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct FetchShift {
+    pub id: String,
+}
+
+/// Omitted fields are left untouched; `requiredRankId: null` drops the
+/// requirement. The shift is checked again as a whole.
+#[derive(InputObject)]
+pub struct UpdateShift {
+    pub id: String,
+    pub employee_id: Option<String>,
+    pub store_id: Option<String>,
+    pub starts_at: Option<DateTime>,
+    pub ends_at: Option<DateTime>,
+    #[graphql(default)]
+    pub required_rank_id: MaybeUndefined<String>,
+    pub version: Option<i32>,
+}
+
+#[derive(InputObject)]
+pub struct DeleteShift {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum AbsenceKind {
+    Vacation,
+    Illness,
+}
+
+impl AbsenceKind {
+    /// The status an employee has while an approved absence of this kind
+    /// covers the day.
+    pub fn status(&self) -> Status {
+        match self {
+            AbsenceKind::Vacation => Status::Vacation,
+            AbsenceKind::Illness => Status::Illness,
+        }
+    }
+}
+
+/// Where an absence is in its approval. Requests are approved or rejected
+/// once; requested and approved absences can still be cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum AbsenceState {
+    Requested,
+    Approved,
+    Rejected,
+    Cancelled,
+}
+
+impl AbsenceState {
+    /// Requested absences can be approved, rejected or cancelled, approved
+    /// ones only cancelled. Rejected and cancelled absences are final.
+    pub fn can_become(&self, next: AbsenceState) -> bool {
+        matches!(
+            (self, next),
+            (
+                AbsenceState::Requested,
+                AbsenceState::Approved | AbsenceState::Rejected | AbsenceState::Cancelled
+            ) | (AbsenceState::Approved, AbsenceState::Cancelled)
+        )
+    }
+
+    /// The name the state is stored under.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbsenceState::Requested => "Requested",
+            AbsenceState::Approved => "Approved",
+            AbsenceState::Rejected => "Rejected",
+            AbsenceState::Cancelled => "Cancelled",
+        }
+    }
+}
+
+/// Time off an employee asked for. `startsOn` and `endsOn` are both part of
+/// the absence.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct Absence {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub employee_id: String,
+    #[graphql(name = "type")]
+    pub kind: AbsenceKind,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: Option<String>,
+    pub state: AbsenceState,
+    pub requested_at: DateTime,
+    /// The subject of whoever approved, rejected or cancelled the absence.
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime>,
+    #[serde(default = "first_version")]
+    pub version: i32,
+}
+
+#[derive(InputObject)]
+pub struct RequestAbsence {
+    pub employee_id: String,
+    #[graphql(name = "type")]
+    pub kind: AbsenceKind,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct FetchAbsence {
+    pub id: String,
+}
+
+#[derive(InputObject)]
+pub struct DecideAbsence {
+    pub id: String,
+    /// The version the decision is based on. When given, the decision only
+    /// applies while the absence is still at that version.
+    pub version: Option<i32>,
+}
+
+/// Absences matching every given field. `from` and `to` select the absences
+/// overlapping those days.
+#[derive(InputObject, Default)]
+pub struct AbsenceFilter {
+    pub employee_id: Option<String>,
+    pub state_in: Option<Vec<AbsenceState>>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// A recorded change of an employee's status, whether by an update or by
+/// approving or cancelling an absence that covers the day. The first entry
+/// of an employee created with a status has no previous status.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+pub struct StatusTransition {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub employee_id: String,
+    pub previous_status: Option<Status>,
+    pub status: Option<Status>,
+    pub changed_at: DateTime,
+    /// The subject of whoever made the change.
+    pub changed_by: Option<String>,
+}
+
+/// How long one or more employees spent in a status, in seconds.
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject)]
+pub struct TimeInStatus {
+    pub status: Status,
+    pub seconds: i64,
+}
+
+/// One write made through a mutation. Entries are only ever appended.
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
-pub struct Error {
-    pub message: String,
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The subject of whoever made the change.
+    pub actor: Option<String>,
+    /// The mutation that made the change, such as `updateEmployee`.
+    pub operation: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub at: DateTime,
+    /// The changed fields, each as `{"before": ..., "after": ...}`.
+    pub diff: Document,
+    pub client_ip: Option<String>,
+}
+
+/// Audit entries matching every given field, made within `from`..`to`.
+#[derive(InputObject, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub operation: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Enum)]
+pub enum OrderDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(SimpleObject)]
+pub struct ConnectionFields {
+    pub total_count: u64,
+}
+
+/*
+ * Subscription Events
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct EmployeeStatusChange {
+    pub employee: Employee,
+    pub previous_status: Option<Status>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct StoreChange {
+    pub kind: ChangeKind,
+    pub store: Store,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct LocationChange {
+    pub kind: ChangeKind,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct RankChange {
+    pub kind: ChangeKind,
+    pub rank: Rank,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AbsenceKind, Employee, Status};
+    use mongodb::bson::{doc, from_document, to_bson, Bson};
+
+    #[test]
+    fn stores_canonical_names() {
+        assert_eq!(
+            to_bson(&Status::EmergencyService).unwrap(),
+            Bson::String(String::from("EmergencyService"))
+        );
+        assert!(Status::KNOWN
+            .iter()
+            .all(|status| Status::decode(status.as_str()) == *status));
+    }
+
+    #[test]
+    fn reads_legacy_spellings() {
+        assert_eq!(
+            Status::decode("EMERGENCY_SERVICE"),
+            Status::EmergencyService
+        );
+        assert_eq!(
+            Status::decode("emergency service"),
+            Status::EmergencyService
+        );
+        assert_eq!(Status::decode("working"), Status::Working);
+    }
+
+    #[test]
+    fn falls_back_to_unknown() {
+        let employee: Employee =
+            from_document(doc! {"first_name": "Jane", "last_name": "Doe", "status": "Sabbatical"})
+                .unwrap();
+        let numeric: Employee =
+            from_document(doc! {"first_name": "Jane", "last_name": "Doe", "status": 3}).unwrap();
+
+        assert_eq!(employee.status, Some(Status::Unknown));
+        assert_eq!(numeric.status, Some(Status::Unknown));
+    }
+
+    #[test]
+    fn stores_absence_kinds_as_their_status() {
+        for kind in [AbsenceKind::Vacation, AbsenceKind::Illness] {
+            assert_eq!(
+                to_bson(&kind).unwrap(),
+                Bson::String(String::from(kind.status().as_str()))
+            );
+        }
+    }
 }